@@ -0,0 +1,164 @@
+// Copyright 2024 The DocAssert Authors
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use miniz_oxide::inflate::{decompress_to_vec, decompress_to_vec_zlib};
+use reqwest::header::{HeaderMap, CONTENT_ENCODING};
+
+/// Content encodings of the compression algorithms defined for HTTP
+const COMPRESSION_ENCODINGS: [&str; 6] = ["gzip", "x-gzip", "deflate", "br", "zstd", "compress"];
+/// Value of the `Accept-Encoding` header for the encodings which can be decoded
+pub(crate) const ACCEPTED_ENCODINGS: &str = "gzip, deflate";
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const GZIP_HEADER_LEN: usize = 10;
+const GZIP_TRAILER_LEN: usize = 8;
+const FLAG_HEADER_CRC: u8 = 0x02;
+const FLAG_EXTRA: u8 = 0x04;
+const FLAG_NAME: u8 = 0x08;
+const FLAG_COMMENT: u8 = 0x10;
+
+/// Returns the content encodings of the response in the order they were applied.
+fn content_encodings(headers: &HeaderMap) -> Vec<String> {
+    headers
+        .get_all(CONTENT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|encoding| encoding.trim().to_lowercase())
+        .filter(|encoding| !encoding.is_empty() && encoding != "identity")
+        .collect()
+}
+
+/// Checks if the `Content-Encoding` header indicates a compression algorithm.
+pub(crate) fn is_compressed(headers: &HeaderMap) -> bool {
+    content_encodings(headers)
+        .iter()
+        .any(|encoding| COMPRESSION_ENCODINGS.contains(&encoding.as_str()))
+}
+
+/// Reverts the content encodings of the response in the reverse order they were applied.
+pub(crate) fn decompress(headers: &HeaderMap, bytes: &[u8]) -> Result<Vec<u8>, String> {
+    content_encodings(headers)
+        .iter()
+        .rev()
+        .try_fold(bytes.to_vec(), |bytes, encoding| decode(encoding, &bytes))
+}
+
+fn decode(encoding: &str, bytes: &[u8]) -> Result<Vec<u8>, String> {
+    match encoding {
+        "gzip" | "x-gzip" => gunzip(bytes),
+        // the deflate content encoding is the zlib format, not raw deflate
+        "deflate" => decompress_to_vec_zlib(bytes)
+            .map_err(|e| format!("error decoding deflate response body: {}", e)),
+        _ => Err(format!("unsupported content encoding {}", encoding)),
+    }
+}
+
+/// Decodes a single gzip member as defined in RFC 1952.
+fn gunzip(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    if bytes.len() < GZIP_HEADER_LEN + GZIP_TRAILER_LEN || bytes[..2] != GZIP_MAGIC {
+        return Err("invalid gzip header".to_string());
+    }
+
+    let flags = bytes[3];
+    let mut start = GZIP_HEADER_LEN;
+    if flags & FLAG_EXTRA != 0 {
+        let len = bytes
+            .get(start..start + 2)
+            .map(|len| u16::from_le_bytes([len[0], len[1]]) as usize)
+            .ok_or("truncated gzip header")?;
+        start += 2 + len;
+    }
+    for flag in [FLAG_NAME, FLAG_COMMENT] {
+        if flags & flag != 0 {
+            let len = bytes
+                .get(start..)
+                .and_then(|rest| rest.iter().position(|b| *b == 0))
+                .ok_or("truncated gzip header")?;
+            start += len + 1;
+        }
+    }
+    if flags & FLAG_HEADER_CRC != 0 {
+        start += 2;
+    }
+
+    let end = bytes.len() - GZIP_TRAILER_LEN;
+    let data = bytes.get(start..end).ok_or("truncated gzip header")?;
+    let decoded =
+        decompress_to_vec(data).map_err(|e| format!("error decoding gzip response body: {}", e))?;
+
+    let trailer = &bytes[end..];
+    let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    let size = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+    if crc != crc32(&decoded) || size != decoded.len() as u32 {
+        return Err("gzip checksum mismatch".to_string());
+    }
+
+    Ok(decoded)
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0u32, |crc, byte| {
+        (0..8).fold(crc ^ *byte as u32, |crc, _| {
+            (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg())
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::{HeaderMap, HeaderValue, CONTENT_ENCODING};
+
+    use super::*;
+
+    fn headers(content_encoding: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CONTENT_ENCODING,
+            HeaderValue::from_str(content_encoding).unwrap(),
+        );
+        headers
+    }
+
+    #[test]
+    fn test_is_compressed() {
+        assert!(is_compressed(&headers("gzip")));
+        assert!(is_compressed(&headers("BR")));
+        assert!(is_compressed(&headers("identity, deflate")));
+        assert!(!is_compressed(&headers("identity")));
+        assert!(!is_compressed(&HeaderMap::new()));
+    }
+
+    #[test]
+    fn test_decompress() {
+        let gzip = std::fs::read("tests/data/blog.json.gz").unwrap();
+        let body = std::fs::read("tests/data/blog.json").unwrap();
+        assert_eq!(decompress(&headers("gzip"), &gzip), Ok(body.clone()));
+
+        let deflate = miniz_oxide::deflate::compress_to_vec_zlib(&body, 6);
+        assert_eq!(decompress(&headers("deflate"), &deflate), Ok(body.clone()));
+
+        let mut corrupted = gzip.clone();
+        let len = corrupted.len();
+        corrupted[len - 8] ^= 0xff;
+        assert_eq!(
+            decompress(&headers("gzip"), &corrupted),
+            Err("gzip checksum mismatch".to_string())
+        );
+        assert_eq!(
+            decompress(&headers("br"), &gzip),
+            Err("unsupported content encoding br".to_string())
+        );
+        assert_eq!(decompress(&HeaderMap::new(), &body), Ok(body));
+    }
+}
@@ -13,9 +13,15 @@
 
 use std::collections::HashMap;
 use std::fmt::Display;
+use std::ops::RangeInclusive;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::Value;
 
 use crate::json_diff::path::Path;
+use crate::json_diff::{DiffFormat, JsonType, NumericMode};
 
 #[derive(Debug, Clone)]
 pub(crate) struct TestCase {
@@ -29,7 +35,10 @@ pub(crate) enum HttpMethod {
     Get,
     Post,
     Put,
+    Patch,
     Delete,
+    Head,
+    Options,
 }
 
 impl Display for HttpMethod {
@@ -38,7 +47,10 @@ impl Display for HttpMethod {
             HttpMethod::Get => write!(f, "GET"),
             HttpMethod::Post => write!(f, "POST"),
             HttpMethod::Put => write!(f, "PUT"),
+            HttpMethod::Patch => write!(f, "PATCH"),
             HttpMethod::Delete => write!(f, "DELETE"),
+            HttpMethod::Head => write!(f, "HEAD"),
+            HttpMethod::Options => write!(f, "OPTIONS"),
         }
     }
 }
@@ -51,7 +63,10 @@ impl FromStr for HttpMethod {
             "GET" => Ok(HttpMethod::Get),
             "POST" => Ok(HttpMethod::Post),
             "PUT" => Ok(HttpMethod::Put),
+            "PATCH" => Ok(HttpMethod::Patch),
             "DELETE" => Ok(HttpMethod::Delete),
+            "HEAD" => Ok(HttpMethod::Head),
+            "OPTIONS" => Ok(HttpMethod::Options),
             _ => Err(format!("{} is not a valid http method", s)),
         }
     }
@@ -59,18 +74,63 @@ impl FromStr for HttpMethod {
 
 #[derive(Debug, Clone)]
 pub(crate) struct Request {
-    // TODO add cert
     pub http_method: HttpMethod,
     pub headers: HashMap<String, String>,
     pub uri: String,
     pub body: Option<String>,
+    pub raw_body: Option<Vec<u8>>,
+    pub multipart: Option<Vec<MultipartField>>,
+    /// Fields of a form sent URL-encoded, documented one per line as `field: value`
+    pub form: Option<Vec<(String, String)>>,
+    /// Time to wait for the whole response, unbounded if not set
+    pub timeout: Option<Duration>,
+    /// Variable whose value patched with the JSON Patch in the body is sent instead
+    pub patch_of: Option<String>,
+    /// Whether a redirect response is returned instead of being followed
+    pub no_redirect: bool,
     pub line_number: usize,
 }
 
+/// Request of the tests, built with struct-update syntax from the defaults
+#[cfg(test)]
+impl Default for Request {
+    fn default() -> Self {
+        Request {
+            http_method: HttpMethod::Get,
+            headers: HashMap::new(),
+            uri: "/".to_string(),
+            body: None,
+            raw_body: None,
+            multipart: None,
+            form: None,
+            timeout: None,
+            patch_of: None,
+            no_redirect: false,
+            line_number: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum MultipartField {
+    Text {
+        name: String,
+        value: String,
+    },
+    File {
+        name: String,
+        file_name: String,
+        bytes: Vec<u8>,
+    },
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub(crate) struct RetryPolicy {
     pub max_retries: u64,
     pub delay: u64,
+    pub on: RetryOn,
+    /// Response status codes retried before asserting the response, whatever the retry mode
+    pub statuses: Vec<u16>,
 }
 
 impl Default for RetryPolicy {
@@ -78,18 +138,461 @@ impl Default for RetryPolicy {
         RetryPolicy {
             max_retries: 1,
             delay: 0,
+            on: RetryOn::default(),
+            statuses: vec![],
+        }
+    }
+}
+
+/// Kind of failures the request is retried on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum RetryOn {
+    /// Errors sending the request, e.g. a refused connection
+    Error,
+    /// Responses not matching the expected one
+    Assert,
+    #[default]
+    Both,
+}
+
+impl RetryOn {
+    pub fn errors(&self) -> bool {
+        matches!(self, RetryOn::Error | RetryOn::Both)
+    }
+
+    pub fn assertions(&self) -> bool {
+        matches!(self, RetryOn::Assert | RetryOn::Both)
+    }
+}
+
+impl FromStr for RetryOn {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(RetryOn::Error),
+            "assert" => Ok(RetryOn::Assert),
+            "both" => Ok(RetryOn::Both),
+            _ => Err(format!("{} is not a valid retry mode", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Stage {
+    BeforeAll,
+    #[default]
+    Main,
+    AfterAll,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Comparison {
+    Eq,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+}
+
+impl Comparison {
+    pub fn holds(&self, actual: usize, expected: usize) -> bool {
+        match self {
+            Comparison::Eq => actual == expected,
+            Comparison::Gt => actual > expected,
+            Comparison::Gte => actual >= expected,
+            Comparison::Lt => actual < expected,
+            Comparison::Lte => actual <= expected,
+        }
+    }
+}
+
+impl Display for Comparison {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Comparison::Eq => write!(f, "exactly"),
+            Comparison::Gt => write!(f, "more than"),
+            Comparison::Gte => write!(f, "at least"),
+            Comparison::Lt => write!(f, "less than"),
+            Comparison::Lte => write!(f, "at most"),
+        }
+    }
+}
+
+impl FromStr for Comparison {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "eq" => Ok(Comparison::Eq),
+            "gt" => Ok(Comparison::Gt),
+            "gte" => Ok(Comparison::Gte),
+            "lt" => Ok(Comparison::Lt),
+            "lte" => Ok(Comparison::Lte),
+            _ => Err(format!("{} is not a valid comparison", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct CountMatching {
+    pub path: String,
+    pub fragment: Value,
+    pub comparison: Comparison,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Each {
+    pub path: String,
+    pub fragment: Value,
+}
+
+/// Normalization applied to the actual values at the path before the comparison
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Transform {
+    pub path: String,
+    pub transformation: Transformation,
+}
+
+/// Pattern the actual value at the path has to match instead of being equal to the documented one
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct PatternMatch {
+    pub path: String,
+    pub pattern: String,
+}
+
+/// Type the actual value at the path has to be of, whatever its value
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct TypeCheck {
+    pub path: String,
+    pub json_type: JsonType,
+}
+
+/// Check of a value returning the reason it failed
+pub(crate) type Assertion = Arc<dyn Fn(&Value) -> Result<(), String> + Send + Sync>;
+
+/// Check registered with [`crate::DocAssert::assert_path`] called with every actual value at the path
+#[derive(Clone)]
+pub(crate) struct PathAssertion {
+    pub path: String,
+    pub assertion: Assertion,
+}
+
+impl std::fmt::Debug for PathAssertion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PathAssertion")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Transformation {
+    /// Sorts an array, numbers numerically and strings lexicographically
+    Sort,
+    Lowercase,
+    Trim,
+    /// Rounds a number to the given number of decimal places
+    Round(u32),
+}
+
+impl Display for Transformation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Transformation::Sort => write!(f, "sort"),
+            Transformation::Lowercase => write!(f, "lowercase"),
+            Transformation::Trim => write!(f, "trim"),
+            Transformation::Round(places) => write!(f, "round:{}", places),
+        }
+    }
+}
+
+impl FromStr for Transformation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "sort" => Ok(Transformation::Sort),
+            None if s == "lowercase" => Ok(Transformation::Lowercase),
+            None if s == "trim" => Ok(Transformation::Trim),
+            Some(("round", places)) => places
+                .parse::<u32>()
+                .map(Transformation::Round)
+                .map_err(|_| format!("{} is not a valid number of decimal places", places)),
+            _ => Err(format!("{} is not a valid transformation", s)),
+        }
+    }
+}
+
+/// Cookie the response has to set with the given attributes, e.g. `HttpOnly` or `SameSite=Strict`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct CookieCheck {
+    pub name: String,
+    pub attributes: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum TlsCheck {
+    CommonName(String),
+    NotExpired,
+}
+
+impl Display for TlsCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TlsCheck::CommonName(name) => write!(f, "cn={}", name),
+            TlsCheck::NotExpired => write!(f, "not-expired"),
+        }
+    }
+}
+
+impl FromStr for TlsCheck {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some(("cn", name)) if !name.is_empty() => Ok(TlsCheck::CommonName(name.to_string())),
+            None if s == "not-expired" => Ok(TlsCheck::NotExpired),
+            _ => Err(format!("{} is not a valid TLS check", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct RateLimit {
+    pub calls: u64,
+    pub header: String,
+}
+
+/// ETag of the response captured in the variable and compared with the one captured before
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Etag {
+    pub variable: String,
+    pub expectation: EtagExpectation,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum EtagExpectation {
+    /// Only captures the ETag
+    Capture,
+    /// The resource was modified since the ETag was captured
+    Changed,
+    /// The resource was not modified since the ETag was captured
+    Unchanged,
+}
+
+/// What a variable template captures from the response body
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Capture {
+    /// Value at the path
+    Value(Path),
+    /// Number of elements of the array at the path
+    Length(Path),
+}
+
+impl Display for Capture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Capture::Value(path) => write!(f, "{}", path),
+            Capture::Length(path) => write!(f, "length {}", path),
+        }
+    }
+}
+
+/// Expectations on how a streamed response arrives, durations are in milliseconds
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct Stream {
+    pub first_byte: Option<u64>,
+    pub min_chunks: Option<u64>,
+    pub within: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Poll {
+    pub timeout: u64,
+    pub interval: u64,
+    pub path: String,
+    pub value: Value,
+}
+
+/// Status code the response is expected to have, documented as `200`, `2xx` or `200-299`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ExpectedStatus {
+    Exact(u16),
+    /// Any status of the class, e.g. `2` for the successful ones
+    Class(u16),
+    /// Any status between the bounds, inclusive
+    Range(u16, u16),
+}
+
+impl ExpectedStatus {
+    pub fn range(&self) -> RangeInclusive<u16> {
+        match *self {
+            ExpectedStatus::Exact(code) => code..=code,
+            ExpectedStatus::Class(class) => class * 100..=class * 100 + 99,
+            ExpectedStatus::Range(start, end) => start..=end,
+        }
+    }
+
+    pub fn contains(&self, status: u16) -> bool {
+        self.range().contains(&status)
+    }
+}
+
+impl Display for ExpectedStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExpectedStatus::Exact(code) => write!(f, "{}", code),
+            ExpectedStatus::Class(class) => write!(f, "{}xx", class),
+            ExpectedStatus::Range(start, end) => write!(f, "{}-{}", start, end),
+        }
+    }
+}
+
+impl FromStr for ExpectedStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let code = |code: &str| {
+            let code = code
+                .parse::<u16>()
+                .map_err(|err| format!("invalid HTTP code: {}", err))?;
+            match (100..=599).contains(&code) {
+                true => Ok(code),
+                false => Err(format!("HTTP code {} outside of valid range", code)),
+            }
+        };
+
+        if let Some(class) = s.strip_suffix("xx").or_else(|| s.strip_suffix("XX")) {
+            return match class.parse::<u16>() {
+                Ok(digit) if class.len() == 1 && (1..=5).contains(&digit) => {
+                    Ok(ExpectedStatus::Class(digit))
+                }
+                _ => Err(format!("invalid HTTP code class {}", s)),
+            };
+        }
+        match s.split_once('-') {
+            Some((start, end)) => {
+                let (start, end) = (code(start)?, code(end)?);
+                if start > end {
+                    return Err(format!("invalid HTTP code range {}", s));
+                }
+                Ok(ExpectedStatus::Range(start, end))
+            }
+            None => Ok(ExpectedStatus::Exact(code(s)?)),
         }
     }
 }
 
 #[derive(Debug, Clone)]
 pub(crate) struct Response {
-    pub code: u16,
+    pub code: ExpectedStatus,
     pub headers: HashMap<String, String>,
     pub ignore_paths: Vec<String>,
     pub ignore_orders: Vec<String>,
+    pub set_equals: Vec<String>,
+    /// Paths of the arrays which have to contain the documented elements among any others
+    pub contains: Vec<String>,
+    /// Paths of the objects whose keys have to be in the documented order
+    pub key_orders: Vec<String>,
+    pub parse_json: Vec<String>,
+    pub exact_lengths: Vec<String>,
+    /// Paths which must not be present in the body
+    pub absent_paths: Vec<String>,
     pub body: Option<String>,
     pub line_number: usize,
-    pub variables: HashMap<String, Path>,
+    pub variables: HashMap<String, Capture>,
+    /// Names of the headers whose values are captured by the variables
+    pub header_variables: HashMap<String, String>,
+    /// Names of the cookies whose values are captured by the variables
+    pub cookie_variables: HashMap<String, String>,
+    pub etag: Option<Etag>,
     pub retries: RetryPolicy,
+    pub stage: Stage,
+    pub count_matchings: Vec<CountMatching>,
+    pub each: Vec<Each>,
+    pub transforms: Vec<Transform>,
+    pub patterns: Vec<PatternMatch>,
+    pub type_checks: Vec<TypeCheck>,
+    pub path_assertions: Vec<PathAssertion>,
+    pub cleared_cookies: Vec<String>,
+    pub cookies: Vec<CookieCheck>,
+    pub tls_checks: Vec<TlsCheck>,
+    pub rate_limit: Option<RateLimit>,
+    pub poll: Option<Poll>,
+    pub repeat: Option<u64>,
+    pub p95: Option<u64>,
+    /// Time in milliseconds the response has to arrive within
+    pub max_time: Option<u64>,
+    pub stream: Option<Stream>,
+    pub numeric_mode: Option<NumericMode>,
+    pub no_unexpected_errors: bool,
+    /// Takes the remaining captures when one of them fails instead of skipping them
+    pub continue_on_capture_failure: bool,
+    /// Notes when the documented body differs from the actual one only in the ignored paths
+    pub report_stale_example: bool,
+    /// Compares the body as plain text instead of JSON
+    pub text: bool,
+    /// Expects the response to be compressed, which the request accepts
+    pub compressed: bool,
+    /// Expects the body to be an RFC 7807 problem document
+    pub problem: bool,
+    /// Path of the JSON Schema file the body is validated against
+    pub schema: Option<String>,
+    pub base_body: Option<Value>,
+    /// How the differences of the bodies are rendered in the failures
+    pub diff_format: DiffFormat,
+}
+
+/// Response of the tests expecting `200 OK`, built with struct-update syntax from the defaults
+#[cfg(test)]
+impl Default for Response {
+    fn default() -> Self {
+        Response {
+            code: ExpectedStatus::Exact(200),
+            headers: HashMap::new(),
+            ignore_paths: vec![],
+            ignore_orders: vec![],
+            set_equals: vec![],
+            contains: vec![],
+            key_orders: vec![],
+            parse_json: vec![],
+            exact_lengths: vec![],
+            absent_paths: vec![],
+            body: None,
+            line_number: 2,
+            variables: HashMap::new(),
+            header_variables: HashMap::new(),
+            cookie_variables: HashMap::new(),
+            etag: None,
+            retries: RetryPolicy::default(),
+            stage: Stage::default(),
+            count_matchings: vec![],
+            each: vec![],
+            transforms: vec![],
+            patterns: vec![],
+            type_checks: vec![],
+            path_assertions: vec![],
+            cleared_cookies: vec![],
+            cookies: vec![],
+            tls_checks: vec![],
+            rate_limit: None,
+            poll: None,
+            repeat: None,
+            p95: None,
+            max_time: None,
+            stream: None,
+            numeric_mode: None,
+            no_unexpected_errors: false,
+            continue_on_capture_failure: false,
+            report_stale_example: false,
+            text: false,
+            compressed: false,
+            problem: false,
+            schema: None,
+            base_body: None,
+            diff_format: DiffFormat::default(),
+        }
+    }
 }
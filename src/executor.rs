@@ -12,24 +12,124 @@
 // limitations under the License.
 
 use std::collections::HashMap;
+use std::fmt::Display;
 use std::str::FromStr;
-use std::time::Duration;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime};
 
-use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
-use reqwest::{Body, Client, Method, Response};
+use base64::Engine;
+#[cfg(feature = "stream")]
+use futures_util::StreamExt;
+use regex::Regex;
+use reqwest::header::{
+    HeaderMap, HeaderName, HeaderValue, ACCEPT_ENCODING, CONTENT_TYPE, ETAG, SEC_WEBSOCKET_ACCEPT,
+    SEC_WEBSOCKET_KEY, SET_COOKIE,
+};
+use reqwest::multipart::{Form, Part};
+use reqwest::redirect::Policy;
+use reqwest::{Body, Client, Method, RequestBuilder, StatusCode};
 
-use crate::domain::{HttpMethod, Request, TestCase};
-use crate::json_diff::path::Path;
-use crate::json_diff::{diff, CompareMode, Config};
-use crate::Variables;
+use crate::compression;
+use crate::domain::{
+    CookieCheck, CountMatching, Each, Etag, EtagExpectation, ExpectedStatus, HttpMethod,
+    MultipartField, PathAssertion, Poll, RateLimit, Request, Stream, TestCase, TlsCheck, Transform,
+    Transformation,
+};
+use crate::json_diff::path::{Key, Path};
+use crate::json_diff::{contains, diff, CompareMode, Config, JsonType};
+use crate::json_patch;
+use crate::{extract_value, Failure, RedirectPolicy, Service, ServiceRequest, Variables};
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct Execution {
+    /// Status code of the last response
+    pub status: u16,
+    pub response_body: Option<String>,
+    /// Informational notes about a passed test
+    pub notes: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ExecutionError {
+    pub context: Option<String>,
+    pub failure: Failure,
+    pub response_body: Option<String>,
+}
+
+impl Display for ExecutionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.context {
+            Some(context) => write!(f, "{}: {}", context, self.failure),
+            None => write!(f, "{}", self.failure),
+        }
+    }
+}
+
+impl From<String> for ExecutionError {
+    fn from(err: String) -> Self {
+        ExecutionError {
+            context: None,
+            failure: Failure::Request(err),
+            response_body: None,
+        }
+    }
+}
+
+const DEFAULT_USER_AGENT: &str = concat!("doc-assert/", env!("CARGO_PKG_VERSION"));
+
+/// Options of the HTTP client the requests are sent with
+#[derive(Debug, Default)]
+pub(crate) struct HttpOptions<'a> {
+    pub user_agent: Option<&'a str>,
+    /// Path prepended to the URIs of the requests, e.g. `/api/v1`
+    pub base_path: Option<&'a str>,
+    /// Default time to wait for a response, overridden by the timeout of the request
+    pub timeout: Option<Duration>,
+    pub redirect_policy: RedirectPolicy,
+    /// Client certificate presented to the servers requiring mutual TLS
+    pub identity: Option<reqwest::Identity>,
+    /// Additional root certificate the server certificates are trusted with
+    pub root_certificate: Option<reqwest::Certificate>,
+    /// Whether any server certificate is accepted, even an invalid one
+    pub accept_invalid_certs: bool,
+    /// Proxy the requests are sent through instead of the ones of the environment
+    pub proxy: Option<reqwest::Proxy>,
+    /// Client built on the first request and reused by the following ones to pool the connections
+    pub client: OnceLock<Result<Client, String>>,
+    /// Client not following redirects, used by the requests with the `[no-redirect]` annotation
+    pub no_redirect_client: OnceLock<Result<Client, String>>,
+}
+
+/// Where the requests are sent to
+pub(crate) enum Target<'a> {
+    /// Base URL of the API tested over HTTP
+    Url(&'a str, &'a HttpOptions<'a>),
+    /// In-process handler
+    Service(&'a Service),
+}
 
 pub(crate) async fn execute(
-    base_url: &str,
+    target: &Target<'_>,
     test_case: TestCase,
     variables: &mut Variables,
-) -> Result<(), String> {
+) -> Result<Execution, ExecutionError> {
     let mut test_request = test_case.request;
     variables.replace_request_placeholders(&mut test_request)?;
+    if let Some(name) = &test_request.patch_of {
+        test_request.body = Some(patch_variable(
+            name,
+            test_request.body.as_deref(),
+            variables,
+        )?);
+    }
+    if let Some(fields) = test_request.form.take() {
+        // encoded once the placeholders are replaced, so their values are encoded too
+        test_request.body =
+            Some(serde_urlencoded::to_string(fields).map_err(|err| err.to_string())?);
+    }
+    if test_case.response.compressed {
+        accept_compression(&mut test_request);
+    }
 
     let test_request_line_number = test_request.line_number;
     let http_method = &test_request.http_method;
@@ -37,37 +137,89 @@ pub(crate) async fn execute(
 
     let mut test_response = test_case.response;
     variables.replace_response_placeholders(&mut test_response)?;
+    expect_websocket_accept(&test_request, &mut test_response);
+    if test_request.http_method == HttpMethod::Head {
+        // responses to HEAD requests never have a body, so there is no JSON to compare
+        test_response.body = None;
+    }
     let test_response_line_number: usize = test_response.line_number;
 
+    if let Some(poll) = &test_response.poll {
+        wait_for_poll_condition(target, &test_request, &test_response, poll).await?;
+    }
+
+    if let Some(rate_limit) = &test_response.rate_limit {
+        return execute_rate_limit(target, &test_request, &test_response, rate_limit, variables)
+            .await;
+    }
+
+    if let Some(stream) = &test_response.stream {
+        return execute_stream(target, &test_request, &test_response, stream, variables).await;
+    }
+
+    if test_response.repeat.is_some() || test_response.p95.is_some() {
+        return execute_repeated(target, &test_request, &test_response, variables).await;
+    }
+
     for i in 0..test_response.retries.max_retries {
-        let response = get_response(base_url, &test_request).await.map_err(|err| {
-            format!(
-                "error executing request {} {} defined at line {}: {}",
-                http_method, uri, test_request_line_number, err
-            )
-        });
+        let response = get_response(target, &test_request)
+            .await
+            .map_err(|err| ExecutionError {
+                context: Some(format!(
+                    "error executing request {} {} defined at line {}",
+                    http_method, uri, test_request_line_number
+                )),
+                failure: Failure::Request(err),
+                response_body: None,
+            });
 
         match response {
             Err(e) => {
-                if i == test_response.retries.max_retries - 1 {
+                if i == test_response.retries.max_retries - 1 || !test_response.retries.on.errors()
+                {
                     return Err(e);
                 }
                 tokio::time::sleep(Duration::from_millis(test_response.retries.delay)).await;
                 continue;
             }
             Ok(response) => {
-                let assert_response = assert_response(response, &test_response, variables)
-                    .await
-                    .map_err(|err| {
-                        format!(
-                            "error asserting response from {} {} defined at line {}: {}",
-                            http_method, uri, test_response_line_number, err
-                        )
+                if test_response.retries.statuses.contains(&response.status)
+                    && i < test_response.retries.max_retries - 1
+                {
+                    // e.g. a service responding 503 while warming up, not worth asserting yet
+                    tokio::time::sleep(Duration::from_millis(test_response.retries.delay)).await;
+                    continue;
+                }
+                let assert_response = assert_response(&response, &test_response, variables)
+                    .map_err(|failure| ExecutionError {
+                        context: Some(format!(
+                            "error asserting response from {} {} defined at line {}",
+                            http_method, uri, test_response_line_number
+                        )),
+                        failure,
+                        response_body: Some(response.body.clone()),
                     });
                 match assert_response {
-                    Ok(_) => return Ok(()),
+                    Ok(notes) => {
+                        return Ok(Execution {
+                            status: response.status,
+                            response_body: Some(response.body),
+                            notes,
+                        })
+                    }
+                    Err(e) if is_unexpected_error(response.status, &test_response) => {
+                        return Err(ExecutionError {
+                            context: Some(format!(
+                                "unexpected server error from {} {} documented at line {}",
+                                http_method, uri, test_response_line_number
+                            )),
+                            ..e
+                        });
+                    }
                     Err(e) => {
-                        if i == test_response.retries.max_retries - 1 {
+                        if i == test_response.retries.max_retries - 1
+                            || !test_response.retries.on.assertions()
+                        {
                             return Err(e);
                         }
                         tokio::time::sleep(Duration::from_millis(test_response.retries.delay))
@@ -79,269 +231,3965 @@ pub(crate) async fn execute(
         }
     }
 
-    Err("internal error executing request".to_string())
+    Err("internal error executing request".to_string().into())
+}
+
+/// Applies the JSON Patch in the request body to the value of the variable
+/// and returns the patched value to be sent instead.
+fn patch_variable(
+    name: &str,
+    patch: Option<&str>,
+    variables: &Variables,
+) -> Result<String, String> {
+    let document = variables
+        .map
+        .get(name)
+        .ok_or_else(|| format!("variable {} to patch is not defined", name))?;
+    let patch = serde_json::from_str(patch.unwrap_or_default())
+        .map_err(|err| format!("error parsing JSON Patch of variable {}: {}", name, err))?;
+
+    json_patch::apply(document, &patch)
+        .map(|patched| patched.to_string())
+        .map_err(|err| format!("error patching variable {}: {}", name, err))
+}
+
+/// For a documented WebSocket handshake, expects the `Sec-WebSocket-Accept` header derived
+/// from the request's `Sec-WebSocket-Key` unless the documentation states the header explicitly.
+fn expect_websocket_accept(test_request: &Request, test_response: &mut crate::domain::Response) {
+    if test_response.code != ExpectedStatus::Exact(StatusCode::SWITCHING_PROTOCOLS.as_u16())
+        || test_response
+            .headers
+            .keys()
+            .any(|name| name.eq_ignore_ascii_case(SEC_WEBSOCKET_ACCEPT.as_str()))
+    {
+        return;
+    }
+
+    let key = test_request
+        .headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(SEC_WEBSOCKET_KEY.as_str()));
+    if let Some((_, key)) = key {
+        test_response
+            .headers
+            .insert("Sec-WebSocket-Accept".to_string(), websocket_accept(key));
+    }
+}
+
+/// Accepts the compressed encodings which can be decoded unless the documented request
+/// accepts its own ones. Without the header servers send the response uncompressed.
+fn accept_compression(test_request: &mut Request) {
+    if !test_request
+        .headers
+        .keys()
+        .any(|name| name.eq_ignore_ascii_case(ACCEPT_ENCODING.as_str()))
+    {
+        test_request.headers.insert(
+            "Accept-Encoding".to_string(),
+            compression::ACCEPTED_ENCODINGS.to_string(),
+        );
+    }
+}
+
+/// Computes the `Sec-WebSocket-Accept` value as defined in RFC 6455.
+fn websocket_accept(key: &str) -> String {
+    const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+    let digest = sha1_smol::Sha1::from(format!("{}{}", key.trim(), WEBSOCKET_GUID)).digest();
+    base64::engine::general_purpose::STANDARD.encode(digest.bytes())
 }
 
-async fn assert_response(
-    response: Response,
+/// Sends the request repeatedly until the value at the poll path equals the expected one
+/// or the timeout elapses. The response is asserted afterwards as usual.
+async fn wait_for_poll_condition(
+    target: &Target<'_>,
+    test_request: &Request,
     test_response: &crate::domain::Response,
-    variables: &mut Variables,
+    poll: &Poll,
 ) -> Result<(), String> {
-    if test_response.code != response.status().as_u16() {
-        return Err(format!(
-            "expected response code {}, got {}",
-            test_response.code,
-            response.status().as_u16()
+    let path = Path::from_jsonpath(poll.path.as_str())
+        .map_err(|err| format!("invalid path {}: {}", poll.path, err))?;
+    let deadline = Instant::now() + Duration::from_millis(poll.timeout);
+
+    loop {
+        let response = get_response(target, test_request).await.ok();
+        if let Some(response) = response
+            .as_ref()
+            .filter(|response| is_unexpected_error(response.status, test_response))
+        {
+            return Err(format!(
+                "unexpected server error {} while polling {} {}",
+                response.status, test_request.http_method, test_request.uri
+            ));
+        }
+        let holds = response
+            .and_then(|response| parse_actual_body(response.body.as_str()).ok())
+            .and_then(|actual| extract_value(&path, &actual))
+            .is_some_and(|value| value == poll.value);
+        if holds || Instant::now() + Duration::from_millis(poll.interval) > deadline {
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_millis(poll.interval)).await;
+    }
+}
+
+/// Checks if the server responded with a 5xx status which wasn't documented
+/// and the run is configured to fail on such responses right away.
+fn is_unexpected_error(status: u16, test_response: &crate::domain::Response) -> bool {
+    test_response.no_unexpected_errors
+        && (500..600).contains(&status)
+        && !(500..600).any(|code| test_response.code.contains(code))
+}
+
+/// Sends the request the given number of times asserting each response and checking that
+/// the rate limit header decreases by one with every call, then expects one more call to be rejected.
+async fn execute_rate_limit(
+    target: &Target<'_>,
+    test_request: &Request,
+    test_response: &crate::domain::Response,
+    rate_limit: &RateLimit,
+    variables: &mut Variables,
+) -> Result<Execution, ExecutionError> {
+    let request_error = |failure| ExecutionError {
+        context: Some(format!(
+            "error executing request {} {} defined at line {}",
+            test_request.http_method, test_request.uri, test_request.line_number
+        )),
+        failure,
+        response_body: None,
+    };
+    let assertion_error = |failure, response: &ActualResponse| ExecutionError {
+        context: Some(format!(
+            "error asserting response from {} {} defined at line {}",
+            test_request.http_method, test_request.uri, test_response.line_number
+        )),
+        failure,
+        response_body: Some(response.body.clone()),
+    };
+
+    let mut previous_remaining: Option<i64> = None;
+    for call in 1..=rate_limit.calls {
+        let response = get_response(target, test_request)
+            .await
+            .map_err(|err| request_error(Failure::Request(err)))?;
+        assert_response(&response, test_response, variables)
+            .map_err(|failure| assertion_error(failure, &response))?;
+
+        let remaining = response
+            .headers
+            .get(rate_limit.header.as_str())
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<i64>().ok())
+            .ok_or_else(|| {
+                let failure = Failure::Header(format!(
+                    "expected numeric header {} in call {}",
+                    rate_limit.header, call
+                ));
+                assertion_error(failure, &response)
+            })?;
+        if let Some(previous) = previous_remaining {
+            if remaining != previous - 1 {
+                let failure = Failure::Header(format!(
+                    "expected header {} to decrease to {} in call {}, got {}",
+                    rate_limit.header,
+                    previous - 1,
+                    call,
+                    remaining
+                ));
+                return Err(assertion_error(failure, &response));
+            }
+        }
+        previous_remaining = Some(remaining);
+    }
+
+    let response = get_response(target, test_request)
+        .await
+        .map_err(|err| request_error(Failure::Request(err)))?;
+    if response.status != StatusCode::TOO_MANY_REQUESTS.as_u16() {
+        let failure = Failure::Status {
+            expected: StatusCode::TOO_MANY_REQUESTS.as_u16(),
+            actual: response.status,
+        };
+        return Err(assertion_error(failure, &response));
+    }
+
+    Ok(Execution {
+        status: response.status,
+        response_body: Some(response.body),
+        notes: vec![],
+    })
+}
+
+/// Sends the request the repeat number of times asserting each response and, if a p95 threshold
+/// is documented, checks the 95th percentile of the response times against it.
+async fn execute_repeated(
+    target: &Target<'_>,
+    test_request: &Request,
+    test_response: &crate::domain::Response,
+    variables: &mut Variables,
+) -> Result<Execution, ExecutionError> {
+    let context = format!(
+        "error asserting response from {} {} defined at line {}",
+        test_request.http_method, test_request.uri, test_response.line_number
+    );
+
+    let calls = test_response.repeat.unwrap_or(1);
+    let mut durations = Vec::with_capacity(calls as usize);
+    let (mut status, mut response_body) = (0, None);
+    let mut notes = vec![];
+    for _ in 0..calls {
+        let start = Instant::now();
+        let response = get_response(target, test_request)
+            .await
+            .map_err(|err| ExecutionError {
+                context: Some(format!(
+                    "error executing request {} {} defined at line {}",
+                    test_request.http_method, test_request.uri, test_request.line_number
+                )),
+                failure: Failure::Request(err),
+                response_body: None,
+            })?;
+        durations.push(start.elapsed());
+
+        notes = assert_response(&response, test_response, variables).map_err(|failure| {
+            ExecutionError {
+                context: Some(context.clone()),
+                failure,
+                response_body: Some(response.body.clone()),
+            }
+        })?;
+        (status, response_body) = (response.status, Some(response.body));
+    }
+
+    if let Some(threshold) = test_response.p95 {
+        let p95 = percentile(&mut durations, 95).as_millis();
+        if p95 > threshold as u128 {
+            return Err(ExecutionError {
+                context: Some(context),
+                failure: Failure::Response(format!(
+                    "expected p95 response time of at most {}ms, got {}ms over {} calls",
+                    threshold, p95, calls
+                )),
+                response_body,
+            });
+        }
+    }
+
+    Ok(Execution {
+        status,
+        response_body,
+        notes,
+    })
+}
+
+/// Sends the request reading the response as a stream of chunks, asserts the response as usual
+/// and checks when the chunks arrived.
+async fn execute_stream(
+    target: &Target<'_>,
+    test_request: &Request,
+    test_response: &crate::domain::Response,
+    stream: &Stream,
+    variables: &mut Variables,
+) -> Result<Execution, ExecutionError> {
+    let request_error = |err| ExecutionError {
+        context: Some(format!(
+            "error executing request {} {} defined at line {}",
+            test_request.http_method, test_request.uri, test_request.line_number
+        )),
+        failure: Failure::Request(err),
+        response_body: None,
+    };
+    let Target::Url(base_url, options) = target else {
+        return Err(request_error(
+            "stream assertions require an HTTP target".to_string(),
         ));
+    };
+
+    let start = Instant::now();
+    let response = request_builder(base_url, options, test_request)
+        .map_err(request_error)?
+        .send()
+        .await
+        .map_err(|e| request_error(send_error(e, test_request.timeout.or(options.timeout))))?;
+    let status = response.status().as_u16();
+    let headers = response.headers().clone();
+    let (body, arrivals) = read_chunks(response, start).await.map_err(request_error)?;
+    let response = ActualResponse {
+        status,
+        headers,
+        body: String::from_utf8_lossy(&body).into_owned(),
+        peer_certificate: None,
+        elapsed: start.elapsed(),
+    };
+
+    let notes = assert_response(&response, test_response, variables)
+        .and_then(|notes| {
+            assert_stream(stream, &arrivals)
+                .map(|_| notes)
+                .map_err(Failure::Response)
+        })
+        .map_err(|failure| ExecutionError {
+            context: Some(format!(
+                "error asserting response from {} {} defined at line {}",
+                test_request.http_method, test_request.uri, test_response.line_number
+            )),
+            failure,
+            response_body: Some(response.body.clone()),
+        })?;
+
+    Ok(Execution {
+        status: response.status,
+        response_body: Some(response.body),
+        notes,
+    })
+}
+
+/// Checks the arrival times of the chunks, measured from sending the request, against the expectations.
+fn assert_stream(stream: &Stream, arrivals: &[Duration]) -> Result<(), String> {
+    if let Some(first_byte) = stream.first_byte {
+        match arrivals.first() {
+            Some(arrival) if arrival.as_millis() <= first_byte as u128 => {}
+            Some(arrival) => {
+                return Err(format!(
+                    "expected first byte within {}ms, got it after {}ms",
+                    first_byte,
+                    arrival.as_millis()
+                ))
+            }
+            None => {
+                return Err(format!(
+                    "expected first byte within {}ms, got an empty body",
+                    first_byte
+                ))
+            }
+        }
+    }
+    if let Some(min_chunks) = stream.min_chunks {
+        if (arrivals.len() as u64) < min_chunks {
+            return Err(format!(
+                "expected at least {} chunks, got {}",
+                min_chunks,
+                arrivals.len()
+            ));
+        }
+    }
+    if let Some(within) = stream.within {
+        if let Some(last) = arrivals
+            .last()
+            .filter(|last| last.as_millis() > within as u128)
+        {
+            return Err(format!(
+                "expected the whole body within {}ms, got the last chunk after {}ms",
+                within,
+                last.as_millis()
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(feature = "stream"))]
+async fn read_chunks(
+    _response: reqwest::Response,
+    _start: Instant,
+) -> Result<(Vec<u8>, Vec<Duration>), String> {
+    Err("stream assertions require the stream feature".to_string())
+}
+
+/// Reads the body chunk by chunk recording when each of them arrived.
+#[cfg(feature = "stream")]
+async fn read_chunks(
+    response: reqwest::Response,
+    start: Instant,
+) -> Result<(Vec<u8>, Vec<Duration>), String> {
+    let mut body = vec![];
+    let mut arrivals = vec![];
+    let mut chunks = response.bytes_stream();
+    while let Some(chunk) = chunks.next().await {
+        let chunk = chunk.map_err(|e| e.to_string())?;
+        arrivals.push(start.elapsed());
+        body.extend_from_slice(&chunk);
+    }
+
+    Ok((body, arrivals))
+}
+
+/// Computes the given percentile of the durations using the nearest-rank method.
+fn percentile(durations: &mut [Duration], percentile: u32) -> Duration {
+    if durations.is_empty() {
+        return Duration::ZERO;
+    }
+    durations.sort();
+    let rank = (durations.len() * percentile as usize).div_ceil(100);
+    durations[rank.max(1) - 1]
+}
+
+/// Asserts the actual response and returns the informational notes about it.
+fn assert_response(
+    response: &ActualResponse,
+    test_response: &crate::domain::Response,
+    variables: &mut Variables,
+) -> Result<Vec<String>, Failure> {
+    let mut notes = vec![];
+    if !test_response.code.contains(response.status) {
+        return Err(match test_response.code {
+            ExpectedStatus::Exact(expected) => Failure::Status {
+                expected,
+                actual: response.status,
+            },
+            expected => Failure::StatusRange {
+                expected: expected.to_string(),
+                actual: response.status,
+            },
+        });
+    }
+    if let Some(max_time) = test_response.max_time {
+        let elapsed = response.elapsed.as_millis();
+        if elapsed > max_time as u128 {
+            return Err(Failure::Response(format!(
+                "response took {}ms, exceeding max {}ms",
+                elapsed, max_time
+            )));
+        }
     }
     for (key, val) in test_response.headers.iter() {
-        match response.headers().get(key.as_str()) {
+        match response.headers.get(key.as_str()) {
             Some(test_val) => {
                 if test_val != val.as_str() {
-                    return Err(format!(
+                    return Err(Failure::Header(format!(
                         "expected header {} to be {}, got {}",
                         key,
                         val,
                         test_val.to_str().unwrap()
-                    ));
+                    )));
                 }
             }
-            None => return Err(format!("expected header {} not found", key)),
+            None => {
+                return Err(Failure::Header(format!(
+                    "expected header {} not found",
+                    key
+                )))
+            }
         }
     }
-    if let Some(test_body) = test_response.body.as_ref() {
+    #[cfg(feature = "grpc-web")]
+    crate::grpc_web::assert_status(&response.headers, &test_response.headers)
+        .map_err(Failure::Response)?;
+    if test_response.compressed && !compression::is_compressed(&response.headers) {
+        return Err(Failure::Header("response was not compressed".to_string()));
+    }
+    for name in test_response.cleared_cookies.iter() {
+        assert_cookie_cleared(&response.headers, name).map_err(Failure::Header)?;
+    }
+    for cookie in test_response.cookies.iter() {
+        assert_cookie(&response.headers, cookie).map_err(Failure::Header)?;
+    }
+    for tls_check in test_response.tls_checks.iter() {
+        assert_tls(tls_check, response.peer_certificate.as_deref())?;
+    }
+    capture_headers(
+        &response.headers,
+        &test_response.header_variables,
+        variables,
+    )?;
+    capture_cookies(
+        &response.headers,
+        &test_response.cookie_variables,
+        variables,
+    )?;
+    if let Some(etag) = &test_response.etag {
+        assert_etag(&response.headers, etag, variables).map_err(Failure::Header)?;
+    }
+    if test_response.problem {
+        let actual = parse_actual_body(response.body.as_str())?;
+        assert_problem(response.status, &actual).map_err(Failure::Schema)?;
+    }
+    if let Some(test_body) = test_response.body.as_ref().filter(|_| test_response.text) {
+        assert_text(test_body, &response.body).map_err(Failure::Body)?;
+        return Ok(notes);
+    }
+    let test_body = test_response
+        .body
+        .as_ref()
+        .filter(|_| response.status != StatusCode::SWITCHING_PROTOCOLS.as_u16());
+    // the fragments are checked and the variables captured even when no body is documented
+    let checks_body = !test_response.count_matchings.is_empty()
+        || !test_response.each.is_empty()
+        || !test_response.variables.is_empty();
+    let actual = match test_body.is_some() || checks_body {
+        true => Some(prepare_actual_body(&response.body, test_response)?),
+        // the path assertions registered for the whole run only apply to the JSON bodies
+        false if !test_response.path_assertions.is_empty() => {
+            prepare_actual_body(&response.body, test_response).ok()
+        }
+        false => None,
+    };
+    if let (Some(test_body), Some(actual)) = (test_body, &actual) {
         let mut diff_config = Config::new(CompareMode::Strict);
+        if let Some(numeric_mode) = test_response.numeric_mode {
+            diff_config = diff_config.numeric_mode(numeric_mode);
+        }
         for path in test_response.ignore_paths.iter() {
             diff_config = diff_config.ignore_path(
-                Path::from_jsonpath(path.as_str())
+                Path::parse(path.as_str())
                     .map_err(|err| format!("invalid path {}: {}", path, err))?,
             );
         }
         for order in test_response.ignore_orders.iter() {
             diff_config = diff_config.ignore_order(
-                Path::from_jsonpath(order.as_str())
+                Path::parse(order.as_str())
                     .map_err(|err| format!("invalid path {}: {}", order, err))?,
             );
         }
+        for path in test_response.set_equals.iter() {
+            diff_config = diff_config.set_equal(
+                Path::parse(path.as_str())
+                    .map_err(|err| format!("invalid path {}: {}", path, err))?,
+            );
+        }
+        for path in test_response.contains.iter() {
+            diff_config = diff_config.subset(
+                Path::parse(path.as_str())
+                    .map_err(|err| format!("invalid path {}: {}", path, err))?,
+            );
+        }
+        for pattern_match in test_response.patterns.iter() {
+            diff_config = diff_config.match_pattern(
+                Path::parse(pattern_match.path.as_str())
+                    .map_err(|err| format!("invalid path {}: {}", pattern_match.path, err))?,
+                Regex::new(pattern_match.pattern.as_str())
+                    .map_err(|err| format!("invalid pattern {}: {}", pattern_match.pattern, err))?,
+            );
+        }
+        for type_check in test_response.type_checks.iter() {
+            diff_config = diff_config.check_type(
+                Path::parse(type_check.path.as_str())
+                    .map_err(|err| format!("invalid path {}: {}", type_check.path, err))?,
+                type_check.json_type,
+            );
+        }
 
-        let response_body = response.text().await.map_err(|e| e.to_string())?;
-        let actual = &serde_json::from_str::<serde_json::Value>(response_body.as_str())
-            .map_err(|err| format!("error parsing JSON response from the server: {}", err))?;
-        let expected = &serde_json::from_str::<serde_json::Value>(test_body.as_str())
-            .map_err(|err| format!("error parsing JSON: {}", err))?;
+        let mut expected = parse_expected_body(test_body.as_str())?;
+        if let (Some(base_body), true) = (&test_response.base_body, expected.is_object()) {
+            expected = merge(base_body, expected);
+        }
+        let expected = &expected;
+        let mut stale_config = diff_config.clone();
         let diff_result = diff(expected, actual, diff_config);
         if !diff_result.is_empty() {
-            return Err(format!(
-                "expected response differs from actual {}",
-                diff_result
+            let mut differences = diff_result
+                .iter()
+                .map(|d| d.format(test_response.diff_format))
+                .collect::<Vec<_>>();
+            if !test_response.transforms.is_empty() {
+                let transforms = test_response
+                    .transforms
                     .iter()
-                    .map(|d| d.to_string())
-                    .collect::<Vec<String>>()
-                    .join("\n"),
-            ));
+                    .map(|t| format!("{} at {}", t.transformation, t.path))
+                    .collect::<Vec<_>>();
+                differences.push(format!(
+                    "compared after applying the transforms: {}",
+                    transforms.join(", ")
+                ));
+            }
+            return Err(Failure::Body(differences));
+        }
+        for path in test_response.key_orders.iter() {
+            assert_key_order(path, expected, actual).map_err(|err| Failure::Body(vec![err]))?;
+        }
+        if test_response.report_stale_example && !stale_config.ignore_paths.is_empty() {
+            stale_config.ignore_paths.clear();
+            let stale_paths = diff(expected, actual, stale_config)
+                .iter()
+                .map(|d| display_path(d.path()))
+                .collect::<Vec<_>>();
+            if !stale_paths.is_empty() {
+                notes.push(format!(
+                    "example is stale in ignored fields: {}",
+                    stale_paths.join(", ")
+                ));
+            }
+        }
+    }
+    if let Some(actual) = &actual {
+        let fragment_config = fragment_config(test_response)?;
+        for count_matching in test_response.count_matchings.iter() {
+            assert_count_matching(count_matching, actual, &fragment_config)?;
+        }
+        for each in test_response.each.iter() {
+            assert_each(each, actual, &fragment_config)?;
+        }
+        for path_assertion in test_response.path_assertions.iter() {
+            assert_path(path_assertion, actual)?;
         }
 
         if !test_response.variables.is_empty() {
-            variables.obtain_from_response(actual, &test_response.variables)?;
+            variables.obtain_from_response(
+                actual,
+                &test_response.variables,
+                test_response.continue_on_capture_failure,
+            )?;
         }
     }
-    Ok(())
+    if let Some(schema) = &test_response.schema {
+        let actual = parse_actual_body(response.body.as_str())?;
+        assert_schema(schema, &actual).map_err(Failure::Schema)?;
+    }
+    if !test_response.absent_paths.is_empty() {
+        let actual = parse_actual_body(response.body.as_str())?;
+        assert_absent(&test_response.absent_paths, &actual).map_err(Failure::Body)?;
+    }
+    Ok(notes)
 }
 
-async fn get_response(base_url: &str, test_request: &Request) -> Result<Response, String> {
-    let mut request_builder = Client::new()
-        .request(
-            map_method(&test_request.http_method),
-            format!("{}{}", base_url, test_request.uri),
-        )
-        .headers(map_headers(&test_request.headers)?);
-    if let Some(body) = &test_request.body {
-        request_builder = request_builder.body(Body::from(body.clone()));
+/// Parses the actual body and applies the `[parse-json]` and `[transform]` annotations to it.
+fn prepare_actual_body(
+    body: &str,
+    test_response: &crate::domain::Response,
+) -> Result<serde_json::Value, String> {
+    let mut actual = parse_actual_body(body)?;
+    for path in test_response.parse_json.iter() {
+        parse_embedded_json(path, &mut actual)?;
     }
-    let response = request_builder.send().await.map_err(|e| e.to_string())?;
-    Ok(response)
+    for transform in test_response.transforms.iter() {
+        apply_transform(transform, &mut actual)?;
+    }
+    Ok(actual)
 }
 
-fn map_headers(headers: &HashMap<String, String>) -> Result<HeaderMap, String> {
-    let mut header_map = HeaderMap::new();
-    for (key, value) in headers {
-        let header_name = HeaderName::from_str(key.clone().as_str()).map_err(|e| e.to_string())?;
-        let header_value = HeaderValue::from_str(value.as_str()).map_err(|e| e.to_string())?;
-        header_map.insert(header_name, header_value);
+/// Comparison of the fragments of `[count-matching]` and `[each]` with the actual elements.
+fn fragment_config(test_response: &crate::domain::Response) -> Result<Config, String> {
+    let mut fragment_config = Config::new(CompareMode::Inclusive);
+    for path in test_response.exact_lengths.iter() {
+        fragment_config = fragment_config.exact_length(
+            Path::parse(path.as_str()).map_err(|err| format!("invalid path {}: {}", path, err))?,
+        );
     }
-    Ok(header_map)
+    Ok(fragment_config)
 }
 
-fn map_method(http_method: &HttpMethod) -> Method {
-    match http_method {
-        HttpMethod::Get => Method::GET,
-        HttpMethod::Post => Method::POST,
-        HttpMethod::Put => Method::PUT,
-        HttpMethod::Delete => Method::DELETE,
+/// Compares the plain text body with the documented one, whose lines keep their indentation.
+/// The lines of the actual body are joined with `\n` like the documented ones, so a byte order
+/// mark, `\r\n` line endings and a trailing line break don't fail the comparison.
+fn assert_text(expected: &str, actual: &str) -> Result<(), Vec<String>> {
+    let actual = actual
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .collect::<Vec<_>>()
+        .join("\n");
+    if actual != expected {
+        return Err(vec![format!(
+            "expected text {:?}, got {:?}",
+            expected, actual
+        )]);
     }
+
+    Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use std::collections::HashMap;
+/// Merges the documented body into the base one. Objects are merged recursively,
+/// any other value of the documented body replaces the base value.
+fn merge(base: &serde_json::Value, body: serde_json::Value) -> serde_json::Value {
+    match (base, body) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(body)) => {
+            let mut merged = base.clone();
+            for (key, value) in body {
+                let value = match merged.get(&key) {
+                    Some(base) => merge(base, value),
+                    None => value,
+                };
+                merged.insert(key, value);
+            }
+            serde_json::Value::Object(merged)
+        }
+        (_, body) => body,
+    }
+}
 
-    use serde_json::json;
+/// Parses the body sent by the server ignoring a leading UTF-8 BOM and surrounding whitespace.
+pub(crate) fn parse_actual_body(body: &str) -> Result<serde_json::Value, String> {
+    serde_json::from_str(body.trim_start_matches('\u{feff}').trim()).map_err(|err| {
+        format!(
+            "error parsing JSON response from the server: {} in body {:?}",
+            err, body
+        )
+    })
+}
 
-    use crate::domain::{HttpMethod, Request, Response, RetryPolicy, TestCase};
-    use crate::executor::execute;
-    use crate::json_diff::path::JSONPath;
-    use crate::Variables;
+#[cfg(not(feature = "json5"))]
+fn parse_expected_body(body: &str) -> Result<serde_json::Value, String> {
+    serde_json::from_str(body).map_err(|err| format!("error parsing JSON: {}", err))
+}
 
-    #[tokio::test]
-    async fn test_execute() {
-        let users_endpoint = "/users";
-        let header_name = "Content-Type";
-        let header_value = "application/json";
-        let request_body = "{\"name\":\"John\"}";
-        let request_body_template = "{\"name\":`name`}";
-        let response_body = "{\"id\": 1, \"name\": \"John\"}";
-        let response_status = 201;
-        let mut server = mockito::Server::new();
-        server
-            .mock("POST", users_endpoint)
-            .match_header(header_name, header_value)
-            .match_body(mockito::Matcher::PartialJsonString(
-                request_body.to_string(),
-            ))
-            .with_header(header_name, header_value)
-            .with_status(response_status)
-            .with_body(response_body)
-            .create();
+/// With the `json5` feature expected bodies may contain comments, trailing commas
+/// and other JSON5 extensions. Actual responses are always parsed as strict JSON.
+#[cfg(feature = "json5")]
+fn parse_expected_body(body: &str) -> Result<serde_json::Value, String> {
+    json5::from_str(body).map_err(|err| format!("error parsing JSON5: {}", err))
+}
 
-        let test_case = TestCase {
-            request: Request {
-                http_method: HttpMethod::Post,
-                headers: vec![(header_name.to_string(), header_value.to_string())]
-                    .into_iter()
-                    .collect(),
-                uri: users_endpoint.to_string(),
-                body: Some(request_body_template.to_string()),
-                line_number: 1,
-            },
-            response: Response {
-                code: response_status as u16,
-                headers: vec![(header_name.to_string(), header_value.to_string())]
-                    .into_iter()
-                    .collect(),
-                ignore_paths: vec!["$.id".to_string()],
-                ignore_orders: vec![],
-                body: Some(response_body.to_string()),
-                line_number: 2,
-                variables: HashMap::new(),
-                retries: RetryPolicy::default(),
-            },
-        };
+fn assert_count_matching(
+    count_matching: &CountMatching,
+    actual: &serde_json::Value,
+    fragment_config: &Config,
+) -> Result<(), String> {
+    let path = Path::from_jsonpath(count_matching.path.as_str())
+        .map_err(|err| format!("invalid path {}: {}", count_matching.path, err))?;
+    let value = extract_value(&path, actual);
+    let elements = value
+        .as_ref()
+        .and_then(|v| v.as_array())
+        .ok_or_else(|| format!("expected array at path {}", count_matching.path))?;
 
-        let mut variables = Variables::from_json(&json!({"name":"John"})).unwrap();
+    let found = elements
+        .iter()
+        .filter(|element| contains(element, &count_matching.fragment, fragment_config))
+        .count();
 
-        let result = execute(server.url().as_str(), test_case, &mut variables).await;
+    if !count_matching.comparison.holds(found, count_matching.count) {
+        return Err(format!(
+            "expected {} {} elements at path {} matching {}, found {}",
+            count_matching.comparison,
+            count_matching.count,
+            count_matching.path,
+            count_matching.fragment,
+            found
+        ));
+    }
 
-        match result {
-            Ok(_) => {}
-            Err(ref err) => assert_eq!("", err),
+    Ok(())
+}
+
+/// Checks that the fragment matches every element of the arrays found at the path.
+/// Wildcards in the path are expanded, so `$.orders[*].items` checks the items of every order.
+fn assert_each(
+    each: &Each,
+    actual: &serde_json::Value,
+    fragment_config: &Config,
+) -> Result<(), String> {
+    let path = Path::from_jsonpath(each.path.as_str())
+        .map_err(|err| format!("invalid path {}: {}", each.path, err))?;
+    let keys = match &path {
+        Path::Root => vec![],
+        Path::Keys(keys) => keys.clone(),
+    };
+
+    let mut arrays = vec![];
+    find_values(&keys, actual, Path::Root, &mut arrays);
+    if arrays.is_empty() {
+        return Err(format!("expected array at path {}", each.path));
+    }
+
+    for (array_path, array) in arrays {
+        let elements = array
+            .as_array()
+            .ok_or_else(|| format!("expected array at path {}", display_path(&array_path)))?;
+        if let Some(idx) = elements
+            .iter()
+            .position(|element| !contains(element, &each.fragment, fragment_config))
+        {
+            return Err(format!(
+                "expected each element at path {} to match {}, element {} does not match",
+                each.path,
+                each.fragment,
+                display_path(&array_path.append(Key::Idx(idx)))
+            ));
         }
-        assert!(result.is_ok());
     }
 
-    #[tokio::test]
-    async fn test_execute_chained() {
-        let users_endpoint = "/users";
-        let header_name = "Content-Type";
-        let header_value = "application/json";
-        let request_body = "{\"name\":\"John\"}";
+    Ok(())
+}
+
+#[cfg(not(feature = "key-order"))]
+fn assert_key_order(
+    path: &str,
+    _expected: &serde_json::Value,
+    _actual: &serde_json::Value,
+) -> Result<(), String> {
+    Err(format!(
+        "key order at path {} requires the key-order feature",
+        path
+    ))
+}
+
+/// Checks that the keys of each object at the path present in both bodies are in the same order.
+#[cfg(feature = "key-order")]
+fn assert_key_order(
+    path: &str,
+    expected: &serde_json::Value,
+    actual: &serde_json::Value,
+) -> Result<(), String> {
+    let keys = match Path::parse(path).map_err(|err| format!("invalid path {}: {}", path, err))? {
+        Path::Root => vec![],
+        Path::Keys(keys) => keys,
+    };
+
+    let mut expected_found = vec![];
+    find_values(&keys, expected, Path::Root, &mut expected_found);
+    let mut actual_found = vec![];
+    find_values(&keys, actual, Path::Root, &mut actual_found);
+
+    for (path, actual_value) in actual_found {
+        let Some((_, expected_value)) = expected_found.iter().find(|(p, _)| *p == path) else {
+            continue;
+        };
+        let (Some(expected_object), Some(actual_object)) =
+            (expected_value.as_object(), actual_value.as_object())
+        else {
+            continue;
+        };
+        let expected_keys = expected_object
+            .keys()
+            .filter(|key| actual_object.contains_key(*key))
+            .collect::<Vec<_>>();
+        let actual_keys = actual_object
+            .keys()
+            .filter(|key| expected_object.contains_key(*key))
+            .collect::<Vec<_>>();
+        if expected_keys != actual_keys {
+            return Err(format!(
+                "expected keys at path {} in order {:?}, got {:?}",
+                display_path(&path),
+                expected_keys,
+                actual_keys
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(feature = "json-schema"))]
+fn assert_schema(schema_path: &str, _actual: &serde_json::Value) -> Result<(), Vec<String>> {
+    Err(vec![format!(
+        "schema {} requires the json-schema feature",
+        schema_path
+    )])
+}
+
+/// Validates the body against the JSON Schema in the file and returns every violation found
+/// at the path of the invalid value.
+#[cfg(feature = "json-schema")]
+fn assert_schema(schema_path: &str, actual: &serde_json::Value) -> Result<(), Vec<String>> {
+    let schema = std::fs::read_to_string(schema_path)
+        .map_err(|err| format!("error reading schema {}: {}", schema_path, err))
+        .and_then(|schema| {
+            serde_json::from_str(&schema)
+                .map_err(|err| format!("error parsing schema {}: {}", schema_path, err))
+        })
+        .map_err(|err| vec![err])?;
+    let compiled = jsonschema::JSONSchema::compile(&schema)
+        .map_err(|err| vec![format!("invalid schema {}: {}", schema_path, err)])?;
+
+    compiled.validate(actual).map_err(|errors| {
+        errors
+            .map(|error| {
+                let path =
+                    Path::from_json_pointer(&error.instance_path.to_string()).unwrap_or(Path::Root);
+                format!("json atom at path \"{}\" is invalid: {}", path, error)
+            })
+            .collect()
+    })
+}
+
+/// Calls the registered check with each actual value at the path.
+fn assert_path(path_assertion: &PathAssertion, actual: &serde_json::Value) -> Result<(), String> {
+    let keys = match Path::from_jsonpath(path_assertion.path.as_str())
+        .map_err(|err| format!("invalid path {}: {}", path_assertion.path, err))?
+    {
+        Path::Root => vec![],
+        Path::Keys(keys) => keys,
+    };
+
+    let mut found = vec![];
+    find_values(&keys, actual, Path::Root, &mut found);
+    for (path, value) in found {
+        (path_assertion.assertion)(value).map_err(|err| {
+            format!(
+                "assertion on the value at path {} failed: {}",
+                display_path(&path),
+                err
+            )
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Replaces each actual string at the path with the JSON document it contains.
+fn parse_embedded_json(path: &str, actual: &mut serde_json::Value) -> Result<(), String> {
+    let keys = match Path::parse(path).map_err(|err| format!("invalid path {}: {}", path, err))? {
+        Path::Root => vec![],
+        Path::Keys(keys) => keys,
+    };
+
+    let mut found = vec![];
+    find_values(&keys, actual, Path::Root, &mut found);
+    let paths = found.into_iter().map(|(path, _)| path).collect::<Vec<_>>();
+
+    for path in paths {
+        let Some(value) = value_mut(actual, &path) else {
+            continue;
+        };
+        let serde_json::Value::String(embedded) = value else {
+            return Err(format!(
+                "expected a string with embedded JSON at path {}, got {}",
+                display_path(&path),
+                value
+            ));
+        };
+        *value = serde_json::from_str(embedded).map_err(|err| {
+            format!(
+                "error parsing JSON embedded at path {}: {} in string {:?}",
+                display_path(&path),
+                err,
+                embedded
+            )
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Applies the transformation to each actual value at the path.
+fn apply_transform(transform: &Transform, actual: &mut serde_json::Value) -> Result<(), String> {
+    let path = Path::from_jsonpath(transform.path.as_str())
+        .map_err(|err| format!("invalid path {}: {}", transform.path, err))?;
+    let keys = match &path {
+        Path::Root => vec![],
+        Path::Keys(keys) => keys.clone(),
+    };
+
+    let mut found = vec![];
+    find_values(&keys, actual, Path::Root, &mut found);
+    let paths = found.into_iter().map(|(path, _)| path).collect::<Vec<_>>();
+
+    for path in paths {
+        let Some(value) = value_mut(actual, &path) else {
+            continue;
+        };
+        transform_value(transform.transformation, value).map_err(|err| {
+            format!(
+                "error applying {} to the value at path {}: {}",
+                transform.transformation,
+                display_path(&path),
+                err
+            )
+        })?;
+    }
+
+    Ok(())
+}
+
+fn transform_value(
+    transformation: Transformation,
+    value: &mut serde_json::Value,
+) -> Result<(), String> {
+    use serde_json::Value;
+
+    match (transformation, value) {
+        (Transformation::Sort, Value::Array(elements)) => {
+            elements.sort_by(|a, b| match (a, b) {
+                (Value::Number(a), Value::Number(b)) => a
+                    .as_f64()
+                    .partial_cmp(&b.as_f64())
+                    .unwrap_or(std::cmp::Ordering::Equal),
+                (Value::String(a), Value::String(b)) => a.cmp(b),
+                (a, b) => a.to_string().cmp(&b.to_string()),
+            });
+        }
+        (Transformation::Lowercase, Value::String(s)) => *s = s.to_lowercase(),
+        (Transformation::Trim, Value::String(s)) => *s = s.trim().to_string(),
+        (Transformation::Round(places), Value::Number(n)) => {
+            let factor = 10f64.powi(places as i32);
+            let rounded = (n.as_f64().unwrap_or_default() * factor).round() / factor;
+            *n = match places {
+                0 => serde_json::Number::from(rounded as i64),
+                _ => serde_json::Number::from_f64(rounded)
+                    .ok_or_else(|| format!("{} is not a finite number", rounded))?,
+            };
+        }
+        (Transformation::Sort, value) => return Err(format!("expected array, got {}", value)),
+        (Transformation::Lowercase | Transformation::Trim, value) => {
+            return Err(format!("expected string, got {}", value))
+        }
+        (Transformation::Round(_), value) => return Err(format!("expected number, got {}", value)),
+    }
+
+    Ok(())
+}
+
+/// Returns the value at a path without wildcards or ranges.
+fn value_mut<'v>(
+    value: &'v mut serde_json::Value,
+    path: &Path,
+) -> Option<&'v mut serde_json::Value> {
+    let Path::Keys(keys) = path else {
+        return Some(value);
+    };
+
+    keys.iter().try_fold(value, |current, key| match key {
+        Key::Field(field) => current.get_mut(field),
+        Key::Idx(idx) => current.get_mut(*idx),
+        _ => None,
+    })
+}
+
+/// Collects the values matching the keys together with their concrete paths.
+fn find_values<'v>(
+    keys: &[Key],
+    value: &'v serde_json::Value,
+    path: Path,
+    found: &mut Vec<(Path, &'v serde_json::Value)>,
+) {
+    let Some((key, rest)) = keys.split_first() else {
+        found.push((path, value));
+        return;
+    };
+
+    match (key, value) {
+        (Key::Field(field), serde_json::Value::Object(map)) => {
+            if let Some(value) = map.get(field) {
+                find_values(rest, value, path.append(key.clone()), found);
+            }
+        }
+        (Key::Wildcard, serde_json::Value::Object(map)) => {
+            for (field, value) in map {
+                find_values(rest, value, path.append(Key::Field(field.clone())), found);
+            }
+        }
+        (Key::RecursiveDescent(field), _) => {
+            if let Some(value) = value.as_object().and_then(|map| map.get(field)) {
+                find_values(rest, value, path.append(Key::Field(field.clone())), found);
+            }
+            match value {
+                serde_json::Value::Object(map) => {
+                    for (name, value) in map {
+                        find_values(keys, value, path.append(Key::Field(name.clone())), found);
+                    }
+                }
+                serde_json::Value::Array(elements) => {
+                    for (idx, value) in elements.iter().enumerate() {
+                        find_values(keys, value, path.append(Key::Idx(idx)), found);
+                    }
+                }
+                _ => {}
+            }
+        }
+        (Key::Field(_) | Key::Wildcard, _) => {}
+        (_, serde_json::Value::Array(elements)) => {
+            for (idx, value) in elements.iter().enumerate() {
+                let included = match key {
+                    Key::Idx(i) => *i == idx,
+                    Key::IdxRange(start, end) => *start <= idx && idx < *end,
+                    Key::IdxRangeStart(start) => *start <= idx,
+                    Key::IdxRangeEnd(end) => idx < *end,
+                    _ => true,
+                };
+                if included {
+                    find_values(rest, value, path.append(Key::Idx(idx)), found);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn display_path(path: &Path) -> String {
+    match path {
+        Path::Root => "$".to_string(),
+        path => format!("${}", path),
+    }
+}
+
+/// Checks that none of the paths is present in the body and returns every one which is.
+fn assert_absent(paths: &[String], actual: &serde_json::Value) -> Result<(), Vec<String>> {
+    let mut present = vec![];
+    for path in paths {
+        let keys = match Path::parse(path)
+            .map_err(|err| vec![format!("invalid path {}: {}", path, err)])?
+        {
+            Path::Root => vec![],
+            Path::Keys(keys) => keys,
+        };
+        let mut found = vec![];
+        find_values(&keys, actual, Path::Root, &mut found);
+        present.extend(found.iter().map(|(path, _)| {
+            format!(
+                "expected path {} to be absent but it was present",
+                display_path(path)
+            )
+        }));
+    }
+
+    if present.is_empty() {
+        Ok(())
+    } else {
+        Err(present)
+    }
+}
+
+/// Members of an RFC 7807 problem document with their types and whether they are required.
+const PROBLEM_MEMBERS: [(&str, JsonType, bool); 5] = [
+    ("type", JsonType::String, true),
+    ("title", JsonType::String, true),
+    ("status", JsonType::Number, true),
+    ("detail", JsonType::String, false),
+    ("instance", JsonType::String, false),
+];
+
+/// Checks the body is an RFC 7807 problem document for the status and returns every violation found.
+fn assert_problem(status: u16, actual: &serde_json::Value) -> Result<(), Vec<String>> {
+    let Some(problem) = actual.as_object() else {
+        return Err(vec![format!(
+            "expected a problem document object, got {}",
+            JsonType::of(actual)
+        )]);
+    };
+
+    let mut violations = vec![];
+    for (member, json_type, required) in PROBLEM_MEMBERS {
+        match problem.get(member).map(JsonType::of) {
+            None if required => violations.push(format!("problem member {} is missing", member)),
+            Some(actual_type) if actual_type != json_type => violations.push(format!(
+                "expected problem member {} to be {}, got {}",
+                member, json_type, actual_type
+            )),
+            _ => {}
+        }
+    }
+    if let Some(problem_status) = problem.get("status").filter(|s| s.is_number()) {
+        if problem_status.as_u64() != Some(status as u64) {
+            violations.push(format!(
+                "problem member status {} does not match the response status {}",
+                problem_status, status
+            ));
+        }
+    }
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(violations)
+    }
+}
+
+/// Compares the ETag of the response with the one captured in the variable before, if expected,
+/// and captures the new one.
+fn assert_etag(headers: &HeaderMap, etag: &Etag, variables: &mut Variables) -> Result<(), String> {
+    let actual = headers
+        .get(ETAG)
+        .ok_or_else(|| "expected an ETag header in the response".to_string())?
+        .to_str()
+        .map_err(|err| format!("invalid ETag header: {}", err))?;
+    let previous = variables
+        .map
+        .get(&etag.variable)
+        .and_then(|value| value.as_str());
+
+    match (etag.expectation, previous) {
+        (EtagExpectation::Capture, _) => {}
+        (_, None) => {
+            return Err(format!(
+                "ETag variable {} has not been captured before",
+                etag.variable
+            ))
+        }
+        (EtagExpectation::Changed, Some(previous)) if previous == actual => {
+            return Err(format!(
+                "expected ETag to change from {}, got {}",
+                previous, actual
+            ))
+        }
+        (EtagExpectation::Unchanged, Some(previous)) if previous != actual => {
+            return Err(format!(
+                "expected ETag to stay {}, got {}",
+                previous, actual
+            ))
+        }
+        _ => {}
+    }
+
+    variables.insert_string(etag.variable.clone(), actual.to_string());
+    Ok(())
+}
+
+/// Stores the values of the headers in the variables named after them.
+fn capture_headers(
+    headers: &HeaderMap,
+    header_variables: &HashMap<String, String>,
+    variables: &mut Variables,
+) -> Result<(), String> {
+    for (name, header) in header_variables {
+        let value = headers
+            .get(header.as_str())
+            .ok_or_else(|| {
+                format!(
+                    "header {} of variable template {} not found in the response",
+                    header, name
+                )
+            })?
+            .to_str()
+            .map_err(|err| {
+                format!(
+                    "invalid value of header {} of variable template {}: {}",
+                    header, name, err
+                )
+            })?;
+        variables.insert_string(name.clone(), value.to_string());
+    }
+
+    Ok(())
+}
+
+/// Stores the values of the cookies set by the response in the variables named after them.
+fn capture_cookies(
+    headers: &HeaderMap,
+    cookie_variables: &HashMap<String, String>,
+    variables: &mut Variables,
+) -> Result<(), String> {
+    for (name, cookie) in cookie_variables {
+        let set_cookie = find_set_cookie(headers, cookie).ok_or_else(|| {
+            format!(
+                "cookie {} of variable template {} not found in the response",
+                cookie, name
+            )
+        })?;
+        let value = set_cookie
+            .split(';')
+            .next()
+            .and_then(|pair| pair.split_once('='))
+            .map(|(_, value)| value.trim())
+            .unwrap_or_default();
+        variables.insert_string(name.clone(), value.to_string());
+    }
+
+    Ok(())
+}
+
+/// Returns the `Set-Cookie` header of the cookie with the given name, if the response sets it.
+fn find_set_cookie<'h>(headers: &'h HeaderMap, name: &str) -> Option<&'h str> {
+    headers
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .find(|value| {
+            value
+                .split(';')
+                .next()
+                .and_then(|pair| pair.split_once('='))
+                .is_some_and(|(cookie_name, _)| cookie_name.trim() == name)
+        })
+}
+
+/// Checks that the cookie is set with each of the attributes. Attributes without a value, like
+/// `HttpOnly`, only have to be present, the others like `SameSite=Strict` need the same value.
+/// Names and values are compared case-insensitively.
+fn assert_cookie(headers: &HeaderMap, cookie: &CookieCheck) -> Result<(), String> {
+    let set_cookie = find_set_cookie(headers, &cookie.name)
+        .ok_or_else(|| format!("expected cookie {} to be set but it was not", cookie.name))?;
+
+    let actual = set_cookie
+        .split(';')
+        .skip(1)
+        .map(|attribute| {
+            let (key, value) = attribute.split_once('=').unwrap_or((attribute, ""));
+            (key.trim(), value.trim())
+        })
+        .collect::<Vec<_>>();
+    for attribute in cookie.attributes.iter() {
+        let present = match attribute.split_once('=') {
+            Some((key, value)) => actual.iter().any(|(actual_key, actual_value)| {
+                actual_key.eq_ignore_ascii_case(key) && actual_value.eq_ignore_ascii_case(value)
+            }),
+            None => actual
+                .iter()
+                .any(|(actual_key, _)| actual_key.eq_ignore_ascii_case(attribute)),
+        };
+        if !present {
+            return Err(format!(
+                "expected cookie {} to have attribute {}, got {}",
+                cookie.name, attribute, set_cookie
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn assert_cookie_cleared(headers: &HeaderMap, name: &str) -> Result<(), String> {
+    let set_cookie = find_set_cookie(headers, name)
+        .ok_or_else(|| format!("expected cookie {} to be cleared but it was not set", name))?;
+
+    let cleared = set_cookie.split(';').skip(1).any(|attribute| {
+        let (key, value) = attribute.split_once('=').unwrap_or((attribute, ""));
+        match key.trim().to_lowercase().as_str() {
+            "max-age" => value
+                .trim()
+                .parse::<i64>()
+                .is_ok_and(|max_age| max_age <= 0),
+            "expires" => httpdate::parse_http_date(value.trim())
+                .is_ok_and(|expires| expires <= SystemTime::now()),
+            _ => false,
+        }
+    });
+
+    if !cleared {
+        return Err(format!(
+            "expected cookie {} to be cleared, got {}",
+            name, set_cookie
+        ));
+    }
+
+    Ok(())
+}
+
+struct ActualResponse {
+    status: u16,
+    headers: HeaderMap,
+    body: String,
+    peer_certificate: Option<Vec<u8>>,
+    /// Time from sending the request until the whole body was read
+    elapsed: Duration,
+}
+
+#[cfg(not(feature = "tls-inspect"))]
+fn assert_tls(tls_check: &TlsCheck, _peer_certificate: Option<&[u8]>) -> Result<(), String> {
+    Err(format!(
+        "TLS check {} requires the tls-inspect feature",
+        tls_check
+    ))
+}
+
+#[cfg(feature = "tls-inspect")]
+fn assert_tls(tls_check: &TlsCheck, peer_certificate: Option<&[u8]>) -> Result<(), String> {
+    let der = peer_certificate.ok_or_else(|| {
+        format!(
+            "expected TLS certificate with {} but the server did not present any",
+            tls_check
+        )
+    })?;
+    let (_, certificate) = x509_parser::parse_x509_certificate(der)
+        .map_err(|err| format!("error parsing TLS certificate: {}", err))?;
+
+    match tls_check {
+        TlsCheck::CommonName(name) => {
+            let common_names = certificate
+                .subject()
+                .iter_common_name()
+                .filter_map(|cn| cn.as_str().ok())
+                .collect::<Vec<_>>();
+            if !common_names.contains(&name.as_str()) {
+                return Err(format!(
+                    "expected TLS certificate with common name {}, got {}",
+                    name,
+                    common_names.join(", ")
+                ));
+            }
+        }
+        TlsCheck::NotExpired => {
+            if certificate.validity().time_to_expiration().is_none() {
+                return Err(format!(
+                    "expected TLS certificate not to be expired, it expired on {}",
+                    certificate.validity().not_after
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn request_builder(
+    base_url: &str,
+    options: &HttpOptions,
+    test_request: &Request,
+) -> Result<RequestBuilder, String> {
+    let mut headers = map_headers(&test_request.headers)?;
+    if test_request.multipart.is_some() {
+        // the multipart content type including the boundary is set by the form
+        headers.remove(CONTENT_TYPE);
+    }
+    let client = if test_request.no_redirect {
+        no_redirect_client(options)?
+    } else {
+        client(options)?
+    };
+    let mut request_builder = client
+        .request(
+            map_method(&test_request.http_method),
+            request_url(base_url, options.base_path, &test_request.uri),
+        )
+        .headers(headers);
+    if let Some(fields) = &test_request.multipart {
+        request_builder = request_builder.multipart(multipart_form(fields));
+    }
+    if let Some(body) = &test_request.body {
+        request_builder = request_builder.body(Body::from(body.clone()));
+    }
+    if let Some(raw_body) = &test_request.raw_body {
+        request_builder = request_builder.body(Body::from(raw_body.clone()));
+    }
+    if let Some(timeout) = test_request.timeout {
+        request_builder = request_builder.timeout(timeout);
+    }
+    Ok(request_builder)
+}
+
+/// Joins the base URL, the base path and the URI of the request with single slashes.
+/// Absolute URIs, e.g. a captured link to another service, are requested as they are.
+fn request_url(base_url: &str, base_path: Option<&str>, uri: &str) -> String {
+    if uri.starts_with("http://") || uri.starts_with("https://") {
+        return uri.to_string();
+    }
+
+    match base_path.map(|path| path.trim_matches('/')) {
+        Some(base_path) if !base_path.is_empty() => format!(
+            "{}/{}/{}",
+            base_url.trim_end_matches('/'),
+            base_path,
+            uri.trim_start_matches('/')
+        ),
+        _ => format!("{}{}", base_url, uri),
+    }
+}
+
+/// Describes the error of sending the request or reading the response.
+fn send_error(err: reqwest::Error, timeout: Option<Duration>) -> String {
+    match timeout {
+        Some(timeout) if err.is_timeout() => timeout_error(timeout),
+        _ => err.to_string(),
+    }
+}
+
+fn timeout_error(timeout: Duration) -> String {
+    format!("request timed out after {}ms", timeout.as_millis())
+}
+
+async fn get_response(
+    target: &Target<'_>,
+    test_request: &Request,
+) -> Result<ActualResponse, String> {
+    let start = Instant::now();
+    let (base_url, options) = match target {
+        Target::Url(base_url, options) => (base_url, options),
+        Target::Service(service) => {
+            return match test_request.timeout {
+                Some(timeout) => tokio::time::timeout(timeout, dispatch(service, test_request))
+                    .await
+                    .map_err(|_| timeout_error(timeout))?,
+                None => dispatch(service, test_request).await,
+            }
+        }
+    };
+    let timeout = test_request.timeout.or(options.timeout);
+    let response = request_builder(base_url, options, test_request)?
+        .send()
+        .await
+        .map_err(|e| send_error(e, timeout))?;
+    let peer_certificate = peer_certificate(&response);
+    let status = response.status();
+    let mut headers = response.headers().clone();
+    // the connection is handed over to the upgraded protocol, so there is no body to read
+    let body = if status == StatusCode::SWITCHING_PROTOCOLS {
+        String::new()
+    } else {
+        read_body(response, &mut headers, timeout).await?
+    };
+    Ok(ActualResponse {
+        status: status.as_u16(),
+        headers,
+        body,
+        peer_certificate,
+        elapsed: start.elapsed(),
+    })
+}
+
+fn multipart_form(fields: &[MultipartField]) -> Form {
+    fields.iter().fold(Form::new(), |form, field| match field {
+        MultipartField::Text { name, value } => form.text(name.clone(), value.clone()),
+        MultipartField::File {
+            name,
+            file_name,
+            bytes,
+        } => form.part(
+            name.clone(),
+            Part::bytes(bytes.clone()).file_name(file_name.clone()),
+        ),
+    })
+}
+
+fn client<'o>(options: &'o HttpOptions) -> Result<&'o Client, String> {
+    options
+        .client
+        .get_or_init(|| build_client(options, options.redirect_policy))
+        .as_ref()
+        .map_err(Clone::clone)
+}
+
+fn no_redirect_client<'o>(options: &'o HttpOptions) -> Result<&'o Client, String> {
+    options
+        .no_redirect_client
+        .get_or_init(|| build_client(options, RedirectPolicy::None))
+        .as_ref()
+        .map_err(Clone::clone)
+}
+
+fn build_client(options: &HttpOptions, redirect_policy: RedirectPolicy) -> Result<Client, String> {
+    let builder = Client::builder()
+        .user_agent(options.user_agent.unwrap_or(DEFAULT_USER_AGENT))
+        .redirect(match redirect_policy {
+            RedirectPolicy::Follow => Policy::default(),
+            RedirectPolicy::Limited(max) => Policy::limited(max),
+            RedirectPolicy::None => Policy::none(),
+        });
+    let builder = match options.timeout {
+        Some(timeout) => builder.timeout(timeout),
+        None => builder,
+    };
+    let builder = match &options.identity {
+        Some(identity) => builder.identity(identity.clone()),
+        None => builder,
+    };
+    let builder = match &options.root_certificate {
+        Some(certificate) => builder.add_root_certificate(certificate.clone()),
+        None => builder,
+    };
+    let builder = builder.danger_accept_invalid_certs(options.accept_invalid_certs);
+    let builder = match &options.proxy {
+        Some(proxy) => builder.proxy(proxy.clone()),
+        None => builder,
+    };
+    #[cfg(feature = "tls-inspect")]
+    let builder = builder.tls_info(true);
+    builder.build().map_err(|e| e.to_string())
+}
+
+#[cfg(not(feature = "grpc-web"))]
+async fn read_body(
+    response: reqwest::Response,
+    headers: &mut HeaderMap,
+    timeout: Option<Duration>,
+) -> Result<String, String> {
+    read_text(response, headers, timeout).await
+}
+
+/// Reads the body as text, decompressing it first if the server compressed it.
+async fn read_text(
+    response: reqwest::Response,
+    headers: &HeaderMap,
+    timeout: Option<Duration>,
+) -> Result<String, String> {
+    if !compression::is_compressed(headers) {
+        return response.text().await.map_err(|e| send_error(e, timeout));
+    }
+
+    let bytes = response.bytes().await.map_err(|e| send_error(e, timeout))?;
+    String::from_utf8(compression::decompress(headers, &bytes)?)
+        .map_err(|e| format!("invalid UTF-8 in the decompressed body: {}", e))
+}
+
+/// With the `grpc-web` feature the framing of gRPC-Web JSON responses is stripped
+/// and the trailers are added to the headers, so they are asserted like any other header.
+#[cfg(feature = "grpc-web")]
+async fn read_body(
+    response: reqwest::Response,
+    headers: &mut HeaderMap,
+    timeout: Option<Duration>,
+) -> Result<String, String> {
+    if !crate::grpc_web::is_grpc_web_json(headers) {
+        return read_text(response, headers, timeout).await;
+    }
+
+    let bytes = response.bytes().await.map_err(|e| send_error(e, timeout))?;
+    let bytes = compression::decompress(headers, &bytes)?;
+    let (body, trailers) = crate::grpc_web::decode(&bytes)?;
+    headers.extend(map_headers(&trailers.into_iter().collect())?);
+    Ok(body)
+}
+
+#[cfg(not(feature = "tls-inspect"))]
+fn peer_certificate(_response: &reqwest::Response) -> Option<Vec<u8>> {
+    None
+}
+
+#[cfg(feature = "tls-inspect")]
+fn peer_certificate(response: &reqwest::Response) -> Option<Vec<u8>> {
+    response
+        .extensions()
+        .get::<reqwest::tls::TlsInfo>()
+        .and_then(|tls_info| tls_info.peer_certificate())
+        .map(|der| der.to_vec())
+}
+
+async fn dispatch(service: &Service, test_request: &Request) -> Result<ActualResponse, String> {
+    let start = Instant::now();
+    if test_request.multipart.is_some() {
+        return Err("multipart requests are not supported by in-process services".to_string());
+    }
+    let body = match (&test_request.body, &test_request.raw_body) {
+        (Some(body), _) => Some(body.as_bytes().to_vec()),
+        (None, raw_body) => raw_body.clone(),
+    };
+    let request = ServiceRequest::new(
+        test_request.http_method.to_string(),
+        test_request.uri.clone(),
+        test_request.headers.clone(),
+        body,
+    );
+    let response = service(request).await;
+
+    let mut headers = HeaderMap::new();
+    for (key, value) in response.headers {
+        let header_name = HeaderName::from_str(key.as_str()).map_err(|e| e.to_string())?;
+        let header_value = HeaderValue::from_str(value.as_str()).map_err(|e| e.to_string())?;
+        headers.append(header_name, header_value);
+    }
+    Ok(ActualResponse {
+        status: response.status,
+        headers,
+        body: response.body,
+        peer_certificate: None,
+        elapsed: start.elapsed(),
+    })
+}
+
+fn map_headers(headers: &HashMap<String, String>) -> Result<HeaderMap, String> {
+    let mut header_map = HeaderMap::new();
+    for (key, value) in headers {
+        let header_name = HeaderName::from_str(key.clone().as_str()).map_err(|e| e.to_string())?;
+        let header_value = HeaderValue::from_str(value.as_str()).map_err(|e| e.to_string())?;
+        header_map.insert(header_name, header_value);
+    }
+    Ok(header_map)
+}
+
+fn map_method(http_method: &HttpMethod) -> Method {
+    match http_method {
+        HttpMethod::Get => Method::GET,
+        HttpMethod::Post => Method::POST,
+        HttpMethod::Put => Method::PUT,
+        HttpMethod::Patch => Method::PATCH,
+        HttpMethod::Delete => Method::DELETE,
+        HttpMethod::Head => Method::HEAD,
+        HttpMethod::Options => Method::OPTIONS,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use serde_json::json;
+
+    use crate::domain::{
+        Capture, Comparison, CookieCheck, CountMatching, Each, ExpectedStatus, HttpMethod,
+        MultipartField, PathAssertion, Poll, RateLimit, Request, Response, RetryOn, RetryPolicy,
+        Stream, TestCase, Transform, Transformation,
+    };
+    #[cfg(feature = "json5")]
+    use crate::executor::parse_expected_body;
+    use crate::executor::{
+        apply_transform, assert_absent, assert_cookie, assert_cookie_cleared,
+        assert_count_matching, assert_each, assert_key_order, assert_problem, assert_schema,
+        assert_stream, capture_cookies, execute, merge, parse_actual_body, patch_variable,
+        request_url, ExecutionError, HttpOptions, Target,
+    };
+    #[cfg(feature = "key-order")]
+    use crate::json_diff::diff;
+    use crate::json_diff::path::{JSONPath, Path};
+    use crate::json_diff::{CompareMode, Config, NumericMode};
+    #[cfg(feature = "tls-inspect")]
+    use crate::{domain::TlsCheck, executor::assert_tls};
+    use crate::{Failure, Service, ServiceResponse, Variables};
+
+    #[tokio::test]
+    async fn test_execute() {
+        let users_endpoint = "/users";
+        let header_name = "Content-Type";
+        let header_value = "application/json";
+        let request_body = "{\"name\":\"John\"}";
+        let request_body_template = "{\"name\":`name`}";
+        let response_body = "{\"id\": 1, \"name\": \"John\"}";
+        let response_status = 201;
+        let mut server = mockito::Server::new();
+        server
+            .mock("POST", users_endpoint)
+            .match_header(header_name, header_value)
+            .match_body(mockito::Matcher::PartialJsonString(
+                request_body.to_string(),
+            ))
+            .with_header(header_name, header_value)
+            .with_status(response_status)
+            .with_body(response_body)
+            .create();
+
+        let test_case = TestCase {
+            request: Request {
+                http_method: HttpMethod::Post,
+                headers: vec![(header_name.to_string(), header_value.to_string())]
+                    .into_iter()
+                    .collect(),
+                uri: users_endpoint.to_string(),
+                body: Some(request_body_template.to_string()),
+                ..Default::default()
+            },
+            response: Response {
+                code: ExpectedStatus::Exact(response_status as u16),
+                headers: vec![(header_name.to_string(), header_value.to_string())]
+                    .into_iter()
+                    .collect(),
+                ignore_paths: vec!["$.id".to_string()],
+                body: Some(response_body.to_string()),
+                ..Default::default()
+            },
+        };
+
+        let mut variables = Variables::from_json(&json!({"name":"John"})).unwrap();
+
+        let result = execute(
+            &Target::Url(server.url().as_str(), &HttpOptions::default()),
+            test_case,
+            &mut variables,
+        )
+        .await;
+
+        match result {
+            Ok(_) => {}
+            Err(ref err) => assert_eq!("", err.to_string()),
+        }
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_execute_chained() {
+        let users_endpoint = "/users";
+        let header_name = "Content-Type";
+        let header_value = "application/json";
+        let request_body = "{\"name\":\"John\"}";
         let request_body_template = "{\"name\":`name`}";
         let response_body = "{\"id\": 1, \"name\": \"John\"}";
         let response_status = 201;
         let mut server = mockito::Server::new();
         server
-            .mock("POST", users_endpoint)
-            .match_header(header_name, header_value)
-            .match_body(mockito::Matcher::PartialJsonString(
-                request_body.to_string(),
-            ))
-            .with_header(header_name, header_value)
-            .with_status(response_status)
-            .with_body(response_body)
+            .mock("POST", users_endpoint)
+            .match_header(header_name, header_value)
+            .match_body(mockito::Matcher::PartialJsonString(
+                request_body.to_string(),
+            ))
+            .with_header(header_name, header_value)
+            .with_status(response_status)
+            .with_body(response_body)
+            .create();
+
+        server
+            .mock("GET", "/users/1")
+            .match_header(header_name, header_value)
+            .with_header(header_name, header_value)
+            .with_status(200)
+            .with_body(response_body)
+            .create();
+
+        let mut response_variables = HashMap::new();
+        response_variables.insert("id".to_string(), Capture::Value("$.id".jsonpath().unwrap()));
+
+        let test_case = TestCase {
+            request: Request {
+                http_method: HttpMethod::Post,
+                headers: vec![(header_name.to_string(), header_value.to_string())]
+                    .into_iter()
+                    .collect(),
+                uri: users_endpoint.to_string(),
+                body: Some(request_body_template.to_string()),
+                ..Default::default()
+            },
+            response: Response {
+                code: ExpectedStatus::Exact(response_status as u16),
+                headers: vec![(header_name.to_string(), header_value.to_string())]
+                    .into_iter()
+                    .collect(),
+                ignore_paths: vec!["$.id".to_string()],
+                body: Some(response_body.to_string()),
+                variables: response_variables,
+                ..Default::default()
+            },
+        };
+
+        let mut variables = Variables::from_json(&json!({"name":"John"})).unwrap();
+
+        let result = execute(
+            &Target::Url(server.url().as_str(), &HttpOptions::default()),
+            test_case,
+            &mut variables,
+        )
+        .await;
+
+        match result {
+            Ok(_) => {}
+            Err(ref err) => assert_eq!("", err.to_string()),
+        }
+        assert!(result.is_ok());
+
+        let test_case = TestCase {
+            request: Request {
+                headers: vec![(header_name.to_string(), header_value.to_string())]
+                    .into_iter()
+                    .collect(),
+                uri: format!("{}/`id`", users_endpoint),
+                line_number: 3,
+                ..Default::default()
+            },
+            response: Response {
+                headers: vec![(header_name.to_string(), header_value.to_string())]
+                    .into_iter()
+                    .collect(),
+                body: Some(response_body.to_string()),
+                line_number: 4,
+                ..Default::default()
+            },
+        };
+
+        let result = execute(
+            &Target::Url(server.url().as_str(), &HttpOptions::default()),
+            test_case,
+            &mut variables,
+        )
+        .await;
+
+        assert_eq!(Ok(()), result.map(|_| ()));
+    }
+
+    #[tokio::test]
+    async fn test_execute_case_insensitive_variable() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("POST", "/users")
+            .with_status(201)
+            .with_body(r#"{"id": "d8f7d454-c436-4e0f-9613-1d69036ad421"}"#)
+            .create();
+        server
+            .mock("GET", "/users/d8f7d454-c436-4e0f-9613-1d69036ad421")
+            .with_status(200)
+            .with_body(r#"{"id": "D8F7D454-C436-4E0F-9613-1D69036AD421"}"#)
+            .create();
+
+        let test_case = |http_method, uri: &str, code, body: &str| TestCase {
+            request: Request {
+                http_method,
+                uri: uri.to_string(),
+                ..Default::default()
+            },
+            response: Response {
+                code: ExpectedStatus::Exact(code),
+                body: Some(body.to_string()),
+                ..Default::default()
+            },
+        };
+
+        let url = server.url();
+        let target = Target::Url(url.as_str(), &HttpOptions::default());
+        let mut variables = Variables::new();
+
+        let mut create = test_case(HttpMethod::Post, "/users", 201, r#"{"id": "ignored"}"#);
+        create.response.ignore_paths = vec!["$.id".to_string()];
+        create.response.variables.insert(
+            "createdId".to_string(),
+            Capture::Value("$.id".jsonpath().unwrap()),
+        );
+        let result = execute(&target, create, &mut variables).await;
+        assert_eq!(Ok(()), result.map(|_| ()));
+
+        let get = test_case(
+            HttpMethod::Get,
+            "/users/`createdId`",
+            200,
+            r#"{"id": "@eqi:`createdId`"}"#,
+        );
+        let result = execute(&target, get, &mut variables).await;
+        assert_eq!(Ok(()), result.map(|_| ()));
+    }
+
+    #[tokio::test]
+    async fn test_execute_header_variable() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/login")
+            .with_status(201)
+            .with_header("Location", "/sessions/42")
+            .with_header("Authorization", "Bearer abc123")
+            .with_body(r#"{"status": "ok"}"#)
+            .create_async()
+            .await;
+        let session = server
+            .mock("GET", "/sessions/42")
+            .match_header("Authorization", "Bearer abc123")
+            .with_status(200)
+            .with_body(r#"{"id": 42}"#)
+            .create_async()
+            .await;
+
+        let test_case = |http_method, uri: &str, code, body: &str| TestCase {
+            request: Request {
+                http_method,
+                uri: uri.to_string(),
+                ..Default::default()
+            },
+            response: Response {
+                code: ExpectedStatus::Exact(code),
+                body: Some(body.to_string()),
+                ..Default::default()
+            },
+        };
+
+        let url = server.url();
+        let target = Target::Url(url.as_str(), &HttpOptions::default());
+        let mut variables = Variables::new();
+
+        let mut login = test_case(HttpMethod::Post, "/login", 201, r#"{"status": "ok"}"#);
+        login.response.header_variables = HashMap::from([
+            ("session".to_string(), "Location".to_string()),
+            ("token".to_string(), "authorization".to_string()),
+        ]);
+        let result = execute(&target, login, &mut variables).await;
+        assert_eq!(Ok(()), result.map(|_| ()));
+
+        let mut get = test_case(HttpMethod::Get, "`session`", 200, r#"{"id": 42}"#);
+        get.request
+            .headers
+            .insert("Authorization".to_string(), "`token`".to_string());
+        let result = execute(&target, get, &mut variables).await;
+        assert_eq!(Ok(()), result.map(|_| ()));
+        session.assert_async().await;
+
+        let mut login = test_case(HttpMethod::Post, "/login", 201, r#"{"status": "ok"}"#);
+        login.response.header_variables = HashMap::from([("etag".to_string(), "ETag".to_string())]);
+        let err = execute(&target, login, &mut variables).await.unwrap_err();
+        assert_eq!(
+            err.failure,
+            Failure::Response(
+                "header ETag of variable template etag not found in the response".to_string()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_root_array_ignore_order() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/blog")
+            .with_status(200)
+            .with_body(
+                r#"[
+                    {"id": 2, "title": "Second Blog", "body": "Second content"},
+                    {"id": 1, "title": "First Blog", "body": "First content"}
+                ]"#,
+            )
+            .create();
+
+        let test_case = TestCase {
+            request: Request {
+                uri: "/blog".to_string(),
+                ..Default::default()
+            },
+            response: Response {
+                ignore_paths: vec!["$[*].id".to_string()],
+                ignore_orders: vec!["$".to_string()],
+                body: Some(
+                    r#"[
+                        {"id": 0, "title": "First Blog", "body": "First content"},
+                        {"id": 0, "title": "Second Blog", "body": "Second content"}
+                    ]"#
+                    .to_string(),
+                ),
+                ..Default::default()
+            },
+        };
+
+        let result = execute(
+            &Target::Url(server.url().as_str(), &HttpOptions::default()),
+            test_case,
+            &mut Variables::new(),
+        )
+        .await;
+
+        assert_eq!(Ok(()), result.map(|_| ()));
+    }
+
+    #[test]
+    fn test_request_url() {
+        let base_url = "http://localhost:8080";
+        assert_eq!(
+            request_url(base_url, None, "/users"),
+            "http://localhost:8080/users"
+        );
+        for (base_url, base_path, uri) in [
+            (base_url, "/api/v1", "/users"),
+            ("http://localhost:8080/", "api/v1/", "users"),
+            (base_url, "/api/v1/", "/users"),
+        ] {
+            assert_eq!(
+                request_url(base_url, Some(base_path), uri),
+                "http://localhost:8080/api/v1/users"
+            );
+        }
+        assert_eq!(
+            request_url(base_url, Some("/"), "/users"),
+            "http://localhost:8080/users"
+        );
+        assert_eq!(
+            request_url(base_url, Some("/api/v1"), "https://example.com/users"),
+            "https://example.com/users"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_body_checks_without_documented_body() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/tasks")
+            .with_status(200)
+            .with_body(r#"{"tasks": [{"id": 1, "status": "done"}, {"id": 2, "status": "todo"}]}"#)
+            .create_async()
+            .await;
+
+        let url = server.url();
+        let execute_with = |response: Response| {
+            let url = url.clone();
+            async move {
+                let test_case = TestCase {
+                    request: Request {
+                        uri: "/tasks".to_string(),
+                        ..Default::default()
+                    },
+                    response,
+                };
+                execute(
+                    &Target::Url(url.as_str(), &HttpOptions::default()),
+                    test_case,
+                    &mut Variables::new(),
+                )
+                .await
+            }
+        };
+
+        let err = execute_with(Response {
+            count_matchings: vec![CountMatching {
+                path: "$.tasks".to_string(),
+                fragment: json!({"status": "done"}),
+                comparison: Comparison::Gte,
+                count: 2,
+            }],
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(
+            err.failure,
+            Failure::Response(
+                "expected at least 2 elements at path $.tasks matching {\"status\":\"done\"}, found 1"
+                    .to_string()
+            )
+        );
+
+        let err = execute_with(Response {
+            each: vec![Each {
+                path: "$.tasks".to_string(),
+                fragment: json!({"status": "done"}),
+            }],
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("expected each element at path $.tasks to match"));
+
+        let mut variables = Variables::new();
+        let test_case = TestCase {
+            request: Request {
+                uri: "/tasks".to_string(),
+                ..Default::default()
+            },
+            response: Response {
+                variables: HashMap::from([(
+                    "first".to_string(),
+                    Capture::Value("$.tasks[0].id".jsonpath().unwrap()),
+                )]),
+                path_assertions: vec![PathAssertion {
+                    path: "$.tasks[*].status".to_string(),
+                    assertion: Arc::new(|value| match value.as_str() {
+                        Some("done") => Ok(()),
+                        _ => Err(format!("{} is not done", value)),
+                    }),
+                }],
+                ..Default::default()
+            },
+        };
+        let err = execute(
+            &Target::Url(url.as_str(), &HttpOptions::default()),
+            test_case.clone(),
+            &mut variables,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            err.failure,
+            Failure::Response(
+                "assertion on the value at path $.tasks[1].status failed: \"todo\" is not done"
+                    .to_string()
+            )
+        );
+
+        let mut test_case = test_case;
+        test_case.response.path_assertions.clear();
+        execute(
+            &Target::Url(url.as_str(), &HttpOptions::default()),
+            test_case,
+            &mut variables,
+        )
+        .await
+        .unwrap();
+        assert_eq!(variables.map.get("first"), Some(&json!(1)));
+    }
+
+    #[test]
+    fn test_assert_count_matching() {
+        let count_matching = |comparison, count| CountMatching {
+            path: "$.tasks".to_string(),
+            fragment: json!({"status": "done"}),
+            comparison,
+            count,
+        };
+
+        let none_done = json!({"tasks": [{"id": 1, "status": "todo"}]});
+        let two_done = json!({"tasks": [
+            {"id": 1, "status": "done"},
+            {"id": 2, "status": "todo"},
+            {"id": 3, "status": "done"}
+        ]});
+        let inclusive = Config::new(CompareMode::Inclusive);
+
+        assert!(
+            assert_count_matching(&count_matching(Comparison::Gte, 2), &two_done, &inclusive)
+                .is_ok()
+        );
+        assert!(
+            assert_count_matching(&count_matching(Comparison::Eq, 2), &two_done, &inclusive)
+                .is_ok()
+        );
+        assert!(
+            assert_count_matching(&count_matching(Comparison::Lt, 3), &two_done, &inclusive)
+                .is_ok()
+        );
+        assert!(
+            assert_count_matching(&count_matching(Comparison::Eq, 0), &none_done, &inclusive)
+                .is_ok()
+        );
+        assert_eq!(
+            assert_count_matching(&count_matching(Comparison::Gte, 2), &none_done, &inclusive),
+            Err(
+                "expected at least 2 elements at path $.tasks matching {\"status\":\"done\"}, found 0"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            assert_count_matching(
+                &count_matching(Comparison::Gte, 1),
+                &json!({"tasks": {}}),
+                &inclusive
+            ),
+            Err("expected array at path $.tasks".to_string())
+        );
+    }
+
+    #[test]
+    fn test_assert_each() {
+        let each = |path: &str| Each {
+            path: path.to_string(),
+            fragment: json!({"owner": {}, "status": "done"}),
+        };
+        let inclusive = Config::new(CompareMode::Inclusive);
+
+        let uniform = json!({"tasks": [
+            {"id": 1, "status": "done", "owner": {"name": "John"}},
+            {"id": 2, "status": "done", "owner": {"name": "Jane"}}
+        ]});
+        assert_eq!(assert_each(&each("$.tasks"), &uniform, &inclusive), Ok(()));
+
+        let one_bad = json!({"tasks": [
+            {"id": 1, "status": "done", "owner": {"name": "John"}},
+            {"id": 2, "status": "todo", "owner": {"name": "Jane"}},
+            {"id": 3, "status": "done"}
+        ]});
+        assert_eq!(
+            assert_each(&each("$.tasks"), &one_bad, &inclusive),
+            Err("expected each element at path $.tasks to match {\"owner\":{},\"status\":\"done\"}, element $.tasks[1] does not match".to_string())
+        );
+
+        let nested =
+            json!({"projects": [{"tasks": uniform["tasks"]}, {"tasks": one_bad["tasks"]}]});
+        assert_eq!(
+            assert_each(&each("$.projects[*].tasks"), &nested, &inclusive),
+            Err("expected each element at path $.projects[*].tasks to match {\"owner\":{},\"status\":\"done\"}, element $.projects[1].tasks[1] does not match".to_string())
+        );
+        assert_eq!(
+            assert_each(&each("$.projects[0:1].tasks"), &nested, &inclusive),
+            Ok(())
+        );
+
+        assert_eq!(
+            assert_each(&each("$.tasks"), &json!({"tasks": {}}), &inclusive),
+            Err("expected array at path $.tasks".to_string())
+        );
+        assert_eq!(
+            assert_each(&each("$.tasks"), &json!({}), &inclusive),
+            Err("expected array at path $.tasks".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_raw_body() {
+        let bytes = vec![0x00, 0xff, 0x10, 0x80];
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("POST", "/upload")
+            .match_body(mockito::Matcher::from(bytes.clone()))
+            .with_status(204)
+            .create();
+
+        let test_case = TestCase {
+            request: Request {
+                http_method: HttpMethod::Post,
+                uri: "/upload".to_string(),
+                raw_body: Some(bytes),
+                ..Default::default()
+            },
+            response: Response {
+                code: ExpectedStatus::Exact(204),
+                ..Default::default()
+            },
+        };
+
+        let result = execute(
+            &Target::Url(server.url().as_str(), &HttpOptions::default()),
+            test_case,
+            &mut Variables::new(),
+        )
+        .await;
+
+        assert_eq!(Ok(()), result.map(|_| ()));
+        mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_execute_multipart() {
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("POST", "/upload")
+            .match_header(
+                "content-type",
+                mockito::Matcher::Regex("^multipart/form-data; boundary=".to_string()),
+            )
+            .match_body(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::Regex("My First Blog".to_string()),
+                mockito::Matcher::Regex(r#"filename="notes.txt""#.to_string()),
+                mockito::Matcher::Regex("hello file".to_string()),
+            ]))
+            .with_status(201)
+            .with_body_from_request(|request| {
+                let body = String::from_utf8_lossy(request.body().unwrap()).to_string();
+                let fields = regex::Regex::new(r#"name="(\w+)""#)
+                    .unwrap()
+                    .captures_iter(&body)
+                    .map(|caps| caps[1].to_string())
+                    .collect::<Vec<_>>();
+                json!({ "fields": fields }).to_string().into_bytes()
+            })
+            .create();
+
+        let test_case = TestCase {
+            request: Request {
+                http_method: HttpMethod::Post,
+                headers: vec![(
+                    "Content-Type".to_string(),
+                    "multipart/form-data".to_string(),
+                )]
+                .into_iter()
+                .collect(),
+                uri: "/upload".to_string(),
+                multipart: Some(vec![
+                    MultipartField::Text {
+                        name: "title".to_string(),
+                        value: "`title`".to_string(),
+                    },
+                    MultipartField::File {
+                        name: "attachment".to_string(),
+                        file_name: "notes.txt".to_string(),
+                        bytes: b"hello file".to_vec(),
+                    },
+                ]),
+                ..Default::default()
+            },
+            response: Response {
+                code: ExpectedStatus::Exact(201),
+                body: Some(r#"{"fields": ["title", "attachment"]}"#.to_string()),
+                ..Default::default()
+            },
+        };
+
+        let mut variables = Variables::from_json(&json!({"title": "My First Blog"})).unwrap();
+        let result = execute(
+            &Target::Url(server.url().as_str(), &HttpOptions::default()),
+            test_case,
+            &mut variables,
+        )
+        .await;
+
+        assert_eq!(Ok(()), result.map(|_| ()));
+        mock.assert();
+    }
+
+    #[test]
+    fn test_assert_cookie() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        for set_cookie in [
+            "theme=dark; Path=/",
+            "session=abc; Path=/; HttpOnly; Secure; SameSite=Strict",
+        ] {
+            headers.append(reqwest::header::SET_COOKIE, set_cookie.parse().unwrap());
+        }
+        let cookie = |name: &str, attributes: &[&str]| CookieCheck {
+            name: name.to_string(),
+            attributes: attributes.iter().map(|a| a.to_string()).collect(),
+        };
+
+        assert!(assert_cookie(&headers, &cookie("session", &[])).is_ok());
+        assert!(assert_cookie(
+            &headers,
+            &cookie("session", &["httponly", "Secure", "SameSite=strict"])
+        )
+        .is_ok());
+        assert_eq!(
+            assert_cookie(&headers, &cookie("theme", &["HttpOnly"])),
+            Err(
+                "expected cookie theme to have attribute HttpOnly, got theme=dark; Path=/"
+                    .to_string()
+            )
+        );
+        assert!(assert_cookie(&headers, &cookie("session", &["SameSite=Lax"])).is_err());
+        assert_eq!(
+            assert_cookie(&headers, &cookie("token", &[])),
+            Err("expected cookie token to be set but it was not".to_string())
+        );
+
+        let mut variables = Variables::new();
+        let cookie_variables = HashMap::from([("sid".to_string(), "session".to_string())]);
+        capture_cookies(&headers, &cookie_variables, &mut variables).unwrap();
+        assert_eq!(variables.map.get("sid"), Some(&json!("abc")));
+        let cookie_variables = HashMap::from([("sid".to_string(), "token".to_string())]);
+        assert!(capture_cookies(&headers, &cookie_variables, &mut variables).is_err());
+    }
+
+    #[test]
+    fn test_assert_cookie_cleared() {
+        let headers = |set_cookies: &[&str]| {
+            let mut headers = reqwest::header::HeaderMap::new();
+            for set_cookie in set_cookies {
+                headers.append(reqwest::header::SET_COOKIE, set_cookie.parse().unwrap());
+            }
+            headers
+        };
+
+        let cleared = [
+            "session=; Max-Age=0; Path=/",
+            "session=; Expires=Thu, 01 Jan 1970 00:00:00 GMT",
+            "theme=dark; Path=/",
+        ];
+        assert!(assert_cookie_cleared(&headers(&cleared[..1]), "session").is_ok());
+        assert!(assert_cookie_cleared(&headers(&cleared[1..]), "session").is_ok());
+
+        assert_eq!(
+            assert_cookie_cleared(&headers(&["session=abc; Max-Age=3600"]), "session"),
+            Err("expected cookie session to be cleared, got session=abc; Max-Age=3600".to_string())
+        );
+        assert!(assert_cookie_cleared(
+            &headers(&["session=abc; Expires=Fri, 01 Jan 2100 00:00:00 GMT"]),
+            "session"
+        )
+        .is_err());
+        assert_eq!(
+            assert_cookie_cleared(&headers(&["theme=; Max-Age=0"]), "session"),
+            Err("expected cookie session to be cleared but it was not set".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_logout_clears_cookie() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("POST", "/logout")
+            .with_status(204)
+            .with_header("Set-Cookie", "session=; Max-Age=0; Path=/; HttpOnly")
+            .create();
+
+        let test_case = TestCase {
+            request: Request {
+                http_method: HttpMethod::Post,
+                uri: "/logout".to_string(),
+                ..Default::default()
+            },
+            response: Response {
+                code: ExpectedStatus::Exact(204),
+                cleared_cookies: vec!["session".to_string()],
+                ..Default::default()
+            },
+        };
+
+        let result = execute(
+            &Target::Url(server.url().as_str(), &HttpOptions::default()),
+            test_case,
+            &mut Variables::new(),
+        )
+        .await;
+
+        assert_eq!(Ok(()), result.map(|_| ()));
+    }
+
+    #[tokio::test]
+    async fn test_execute_failure_breakdown() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/status")
+            .with_status(500)
+            .with_header("Content-Type", "application/json")
+            .with_body(r#"{"id": 1}"#)
+            .create();
+        server
+            .mock("GET", "/header")
+            .with_status(200)
+            .with_header("Content-Type", "text/plain")
+            .with_body(r#"{"id": 1}"#)
+            .create();
+        server
+            .mock("GET", "/body")
+            .with_status(200)
+            .with_header("Content-Type", "application/json")
+            .with_body(r#"{"id": 2, "name": "Jane"}"#)
+            .create();
+
+        let test_case = |uri: &str| TestCase {
+            request: Request {
+                uri: uri.to_string(),
+                ..Default::default()
+            },
+            response: Response {
+                headers: vec![("Content-Type".to_string(), "application/json".to_string())]
+                    .into_iter()
+                    .collect(),
+                body: Some(r#"{"id": 1, "name": "John"}"#.to_string()),
+                ..Default::default()
+            },
+        };
+
+        let url = server.url();
+
+        let err = execute(
+            &Target::Url(url.as_str(), &HttpOptions::default()),
+            test_case("/status"),
+            &mut Variables::new(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            err.failure,
+            Failure::Status {
+                expected: 200,
+                actual: 500
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "error asserting response from GET /status defined at line 2: expected response code 200, got 500"
+        );
+
+        let err = execute(
+            &Target::Url(url.as_str(), &HttpOptions::default()),
+            test_case("/header"),
+            &mut Variables::new(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            err.failure,
+            Failure::Header(
+                "expected header Content-Type to be application/json, got text/plain".to_string()
+            )
+        );
+
+        let err = execute(
+            &Target::Url(url.as_str(), &HttpOptions::default()),
+            test_case("/body"),
+            &mut Variables::new(),
+        )
+        .await
+        .unwrap_err();
+        match err.failure {
+            Failure::Body(differences) => assert_eq!(differences.len(), 2),
+            failure => panic!("expected body failure, got {:?}", failure),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_execute_rate_limit() {
+        let mut server = mockito::Server::new();
+        for remaining in ["2", "1", "0"] {
+            server
+                .mock("GET", "/limited")
+                .with_status(200)
+                .with_header("X-RateLimit-Remaining", remaining)
+                .expect(1)
+                .create();
+        }
+        server
+            .mock("GET", "/limited")
+            .with_status(429)
+            .with_header("X-RateLimit-Remaining", "0")
             .create();
+        server
+            .mock("GET", "/unlimited")
+            .with_status(200)
+            .with_header("X-RateLimit-Remaining", "10")
+            .create();
+
+        let test_case = |uri: &str| TestCase {
+            request: Request {
+                uri: uri.to_string(),
+                ..Default::default()
+            },
+            response: Response {
+                rate_limit: Some(RateLimit {
+                    calls: 3,
+                    header: "X-RateLimit-Remaining".to_string(),
+                }),
+                ..Default::default()
+            },
+        };
+
+        let url = server.url();
+
+        let result = execute(
+            &Target::Url(url.as_str(), &HttpOptions::default()),
+            test_case("/limited"),
+            &mut Variables::new(),
+        )
+        .await;
+        assert_eq!(Ok(()), result.map(|_| ()));
+
+        let err = execute(
+            &Target::Url(url.as_str(), &HttpOptions::default()),
+            test_case("/unlimited"),
+            &mut Variables::new(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            err.failure,
+            Failure::Header(
+                "expected header X-RateLimit-Remaining to decrease to 9 in call 2, got 10"
+                    .to_string()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_json_pointer() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/blog")
+            .with_status(200)
+            .with_body(r#"{"data": [{"id": 7, "date_upd": "2024-02-01"}]}"#)
+            .create_async()
+            .await;
+
+        let test_case = TestCase {
+            request: Request {
+                uri: "/blog".to_string(),
+                ..Default::default()
+            },
+            response: Response {
+                ignore_paths: vec!["/data/0/date_upd".to_string()],
+                body: Some(r#"{"data": [{"id": 7, "date_upd": "2024-01-01"}]}"#.to_string()),
+                variables: HashMap::from([(
+                    "id".to_string(),
+                    Capture::Value(Path::parse("/data/0/id").unwrap()),
+                )]),
+                ..Default::default()
+            },
+        };
+
+        let mut variables = Variables::new();
+        let url = server.url();
+        let result = execute(
+            &Target::Url(url.as_str(), &HttpOptions::default()),
+            test_case,
+            &mut variables,
+        )
+        .await;
+        assert_eq!(Ok(()), result.map(|_| ()));
+        assert_eq!(variables.map.get("id"), Some(&json!(7)));
+    }
+
+    #[tokio::test]
+    async fn test_execute_transform() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/blog")
+            .with_status(200)
+            .with_body(r#"{"tags": ["rust", "API"], "rating": 4.4999}"#)
+            .create_async()
+            .await;
+
+        let test_case = |body: &str| TestCase {
+            request: Request {
+                uri: "/blog".to_string(),
+                ..Default::default()
+            },
+            response: Response {
+                body: Some(body.to_string()),
+                transforms: vec![
+                    Transform {
+                        path: "$.tags[*]".to_string(),
+                        transformation: Transformation::Lowercase,
+                    },
+                    Transform {
+                        path: "$.tags".to_string(),
+                        transformation: Transformation::Sort,
+                    },
+                    Transform {
+                        path: "$.rating".to_string(),
+                        transformation: Transformation::Round(1),
+                    },
+                ],
+                ..Default::default()
+            },
+        };
+
+        let url = server.url();
+        let result = execute(
+            &Target::Url(url.as_str(), &HttpOptions::default()),
+            test_case(r#"{"tags": ["api", "rust"], "rating": 4.5}"#),
+            &mut Variables::new(),
+        )
+        .await;
+        assert_eq!(Ok(()), result.map(|_| ()));
+
+        let err = execute(
+            &Target::Url(url.as_str(), &HttpOptions::default()),
+            test_case(r#"{"tags": ["api", "rust"], "rating": 4.4}"#),
+            &mut Variables::new(),
+        )
+        .await
+        .unwrap_err();
+        match err.failure {
+            Failure::Body(differences) => assert_eq!(
+                differences.last(),
+                Some(&"compared after applying the transforms: lowercase at $.tags[*], sort at $.tags, round:1 at $.rating".to_string())
+            ),
+            failure => panic!("expected body failure, got {:?}", failure),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_execute_patch_and_head() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("PATCH", "/blog/1")
+            .match_body(r#"{"title": "Updated"}"#)
+            .with_status(200)
+            .with_body(r#"{"id": 1, "title": "Updated"}"#)
+            .create_async()
+            .await;
+        server
+            .mock("HEAD", "/blog/1")
+            .with_status(200)
+            .with_header("Content-Type", "application/json")
+            .create_async()
+            .await;
+
+        let test_case = |http_method, body: Option<&str>, response_body: &str| TestCase {
+            request: Request {
+                http_method,
+                uri: "/blog/1".to_string(),
+                body: body.map(|b| b.to_string()),
+                ..Default::default()
+            },
+            response: Response {
+                body: Some(response_body.to_string()),
+                ..Default::default()
+            },
+        };
+
+        let url = server.url();
+        let result = execute(
+            &Target::Url(url.as_str(), &HttpOptions::default()),
+            test_case(
+                HttpMethod::Patch,
+                Some(r#"{"title": "Updated"}"#),
+                r#"{"id": 1, "title": "Updated"}"#,
+            ),
+            &mut Variables::new(),
+        )
+        .await;
+        assert_eq!(Ok(()), result.map(|_| ()));
+
+        // the documented body of a HEAD response only illustrates the resource
+        let result = execute(
+            &Target::Url(url.as_str(), &HttpOptions::default()),
+            test_case(HttpMethod::Head, None, r#"{"id": 1, "title": "Updated"}"#),
+            &mut Variables::new(),
+        )
+        .await;
+        assert_eq!(Ok(()), result.map(|_| ()));
+    }
+
+    #[tokio::test]
+    async fn test_execute_text() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/health")
+            .with_status(200)
+            .with_header("Content-Type", "text/plain")
+            .with_body("OK\n")
+            .create_async()
+            .await;
+
+        let test_case = |body: &str| TestCase {
+            request: Request {
+                uri: "/health".to_string(),
+                ..Default::default()
+            },
+            response: Response {
+                body: Some(body.to_string()),
+                text: true,
+                ..Default::default()
+            },
+        };
+
+        let url = server.url();
+        let result = execute(
+            &Target::Url(url.as_str(), &HttpOptions::default()),
+            test_case("`status`"),
+            &mut Variables::from_json(&json!({"status": "OK"})).unwrap(),
+        )
+        .await;
+        assert_eq!(Ok(()), result.map(|_| ()));
+
+        let err = execute(
+            &Target::Url(url.as_str(), &HttpOptions::default()),
+            test_case("DOWN"),
+            &mut Variables::new(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            err.failure,
+            Failure::Body(vec!["expected text \"DOWN\", got \"OK\"".to_string()])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_compressed() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/blog/1")
+            .match_header("Accept-Encoding", "gzip, deflate")
+            .with_status(200)
+            .with_header("Content-Encoding", "gzip")
+            .with_body(std::fs::read("tests/data/blog.json.gz").unwrap())
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/blog/2")
+            .with_status(200)
+            .with_body(std::fs::read("tests/data/blog.json").unwrap())
+            .create_async()
+            .await;
+
+        let test_case = |uri: &str, body: &str| TestCase {
+            request: Request {
+                uri: uri.to_string(),
+                ..Default::default()
+            },
+            response: Response {
+                body: Some(body.to_string()),
+                compressed: true,
+                ..Default::default()
+            },
+        };
+
+        let url = server.url();
+        let result = execute(
+            &Target::Url(url.as_str(), &HttpOptions::default()),
+            test_case(
+                "/blog/1",
+                r#"{"id": 1, "title": "My First Blog", "body": "Blog content"}"#,
+            ),
+            &mut Variables::new(),
+        )
+        .await;
+        assert_eq!(Ok(()), result.map(|_| ()));
+
+        let err = execute(
+            &Target::Url(url.as_str(), &HttpOptions::default()),
+            test_case(
+                "/blog/2",
+                r#"{"id": 1, "title": "My First Blog", "body": "Blog content"}"#,
+            ),
+            &mut Variables::new(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            err.failure,
+            Failure::Header("response was not compressed".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_parse_json() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/events/1")
+            .with_status(200)
+            .with_body(r#"{"type": "created", "payload": "{\"id\": 1, \"tags\": [\"a\"]}"}"#)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/events/2")
+            .with_status(200)
+            .with_body(r#"{"type": "created", "payload": "{\"id\": 1,"}"#)
+            .create_async()
+            .await;
+
+        let test_case = |uri: &str, body: &str| TestCase {
+            request: Request {
+                uri: uri.to_string(),
+                ..Default::default()
+            },
+            response: Response {
+                parse_json: vec!["$.payload".to_string()],
+                body: Some(body.to_string()),
+                ..Default::default()
+            },
+        };
+
+        let url = server.url();
+        let result = execute(
+            &Target::Url(url.as_str(), &HttpOptions::default()),
+            test_case(
+                "/events/1",
+                r#"{"type": "created", "payload": {"id": 1, "tags": ["a"]}}"#,
+            ),
+            &mut Variables::new(),
+        )
+        .await;
+        assert_eq!(Ok(()), result.map(|_| ()));
+
+        let err = execute(
+            &Target::Url(url.as_str(), &HttpOptions::default()),
+            test_case(
+                "/events/1",
+                r#"{"type": "created", "payload": {"id": 2, "tags": ["a"]}}"#,
+            ),
+            &mut Variables::new(),
+        )
+        .await
+        .unwrap_err();
+        match err.failure {
+            Failure::Body(differences) => {
+                assert_eq!(differences.len(), 1);
+                assert!(
+                    differences[0].starts_with("json atoms at path \".payload.id\" are not equal")
+                );
+            }
+            failure => panic!("expected body failure, got {:?}", failure),
+        }
+
+        let err = execute(
+            &Target::Url(url.as_str(), &HttpOptions::default()),
+            test_case("/events/2", r#"{"type": "created", "payload": {"id": 1}}"#),
+            &mut Variables::new(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            err.failure,
+            Failure::Response(
+                "error parsing JSON embedded at path $.payload: EOF while parsing a value at line 1 column 9 in string \"{\\\"id\\\": 1,\"".to_string()
+            )
+        );
+    }
 
+    #[tokio::test]
+    async fn test_execute_timeout() {
+        let mut server = mockito::Server::new_async().await;
         server
+            .mock("GET", "/slow")
+            .with_status(200)
+            .with_chunked_body(|w| {
+                std::thread::sleep(Duration::from_millis(300));
+                w.write_all(br#"{"id": 1}"#)
+            })
+            .create_async()
+            .await;
+
+        let test_case = |timeout| TestCase {
+            request: Request {
+                uri: "/slow".to_string(),
+                timeout,
+                ..Default::default()
+            },
+            response: Response {
+                body: Some(r#"{"id": 1}"#.to_string()),
+                ..Default::default()
+            },
+        };
+
+        let url = server.url();
+        let result = execute(
+            &Target::Url(url.as_str(), &HttpOptions::default()),
+            test_case(Some(Duration::from_secs(5))),
+            &mut Variables::new(),
+        )
+        .await;
+        assert_eq!(Ok(()), result.map(|_| ()));
+
+        let err = execute(
+            &Target::Url(url.as_str(), &HttpOptions::default()),
+            test_case(Some(Duration::from_millis(100))),
+            &mut Variables::new(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "error executing request GET /slow defined at line 1: request timed out after 100ms"
+        );
+
+        // the timeout of the request overrides the default one
+        let options = HttpOptions {
+            timeout: Some(Duration::from_millis(100)),
+            ..HttpOptions::default()
+        };
+        let err = execute(
+            &Target::Url(url.as_str(), &options),
+            test_case(None),
+            &mut Variables::new(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            err.failure,
+            Failure::Request("request timed out after 100ms".to_string())
+        );
+        let result = execute(
+            &Target::Url(url.as_str(), &options),
+            test_case(Some(Duration::from_secs(5))),
+            &mut Variables::new(),
+        )
+        .await;
+        assert_eq!(Ok(()), result.map(|_| ()));
+
+        let service: Service = Box::new(|_| {
+            Box::pin(async {
+                tokio::time::sleep(Duration::from_millis(300)).await;
+                ServiceResponse::new(200)
+            })
+        });
+        let err = execute(
+            &Target::Service(&service),
+            test_case(Some(Duration::from_millis(100))),
+            &mut Variables::new(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            err.failure,
+            Failure::Request("request timed out after 100ms".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_max_time() {
+        let test_case = |max_time| TestCase {
+            request: Request {
+                uri: "/slow".to_string(),
+                ..Default::default()
+            },
+            response: Response {
+                max_time: Some(max_time),
+                ..Default::default()
+            },
+        };
+
+        let service: Service = Box::new(|_| {
+            Box::pin(async {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                ServiceResponse::new(200)
+            })
+        });
+        let result = execute(
+            &Target::Service(&service),
+            test_case(5000),
+            &mut Variables::new(),
+        )
+        .await;
+        assert_eq!(Ok(()), result.map(|_| ()));
+
+        let err = execute(
+            &Target::Service(&service),
+            test_case(50),
+            &mut Variables::new(),
+        )
+        .await
+        .unwrap_err();
+        let message = err.to_string();
+        assert!(
+            message.starts_with(
+                "error asserting response from GET /slow defined at line 2: response took "
+            ),
+            "{}",
+            message
+        );
+        assert!(message.ends_with("ms, exceeding max 50ms"), "{}", message);
+    }
+
+    #[tokio::test]
+    async fn test_execute_reuses_client() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
             .mock("GET", "/users/1")
-            .match_header(header_name, header_value)
-            .with_header(header_name, header_value)
             .with_status(200)
-            .with_body(response_body)
-            .create();
+            .with_body(r#"{"id": 1}"#)
+            .expect(2)
+            .create_async()
+            .await;
 
-        let mut response_variables = HashMap::new();
-        response_variables.insert("id".to_string(), "$.id".jsonpath().unwrap());
+        let test_case = || TestCase {
+            request: Request {
+                uri: "/users/1".to_string(),
+                ..Default::default()
+            },
+            response: Response {
+                body: Some(r#"{"id": 1}"#.to_string()),
+                ..Default::default()
+            },
+        };
+
+        let url = server.url();
+        let options = HttpOptions::default();
+        let target = Target::Url(url.as_str(), &options);
+        let result = execute(&target, test_case(), &mut Variables::new()).await;
+        assert_eq!(Ok(()), result.map(|_| ()));
+        let client = options.client.get().unwrap().as_ref().unwrap() as *const _;
+
+        let result = execute(&target, test_case(), &mut Variables::new()).await;
+        assert_eq!(Ok(()), result.map(|_| ()));
+        assert_eq!(
+            client,
+            options.client.get().unwrap().as_ref().unwrap() as *const _
+        );
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_execute_p95() {
+        // every call takes 5ms except the first `slow` ones taking 250ms
+        let service = |slow: usize| -> Service {
+            let calls = Arc::new(AtomicUsize::new(0));
+            Box::new(move |_| {
+                let call = calls.fetch_add(1, Ordering::SeqCst);
+                Box::pin(async move {
+                    let latency = if call < slow { 250 } else { 5 };
+                    tokio::time::sleep(Duration::from_millis(latency)).await;
+                    ServiceResponse::new(200)
+                })
+            })
+        };
+        let test_case = || TestCase {
+            request: Request {
+                uri: "/blog".to_string(),
+                ..Default::default()
+            },
+            response: Response {
+                repeat: Some(20),
+                p95: Some(100),
+                ..Default::default()
+            },
+        };
+
+        let one_slow = service(1);
+        let result = execute(
+            &Target::Service(&one_slow),
+            test_case(),
+            &mut Variables::new(),
+        )
+        .await;
+        assert_eq!(Ok(()), result.map(|_| ()));
+
+        let two_slow = service(2);
+        let Err(err) = execute(
+            &Target::Service(&two_slow),
+            test_case(),
+            &mut Variables::new(),
+        )
+        .await
+        else {
+            panic!("expected the p95 check to fail");
+        };
+        let Failure::Response(message) = err.failure else {
+            panic!("expected a response failure, got {:?}", err.failure);
+        };
+        assert!(message.starts_with("expected p95 response time of at most 100ms, got 25"));
+        assert!(message.ends_with("ms over 20 calls"));
+    }
+
+    #[test]
+    fn test_assert_stream() {
+        let stream = Stream {
+            first_byte: Some(100),
+            min_chunks: Some(3),
+            within: Some(1000),
+        };
+        let arrivals = |millis: &[u64]| {
+            millis
+                .iter()
+                .map(|m| Duration::from_millis(*m))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(assert_stream(&stream, &arrivals(&[20, 300, 600])), Ok(()));
+        assert_eq!(
+            assert_stream(&stream, &arrivals(&[150, 300, 600])),
+            Err("expected first byte within 100ms, got it after 150ms".to_string())
+        );
+        assert_eq!(
+            assert_stream(&stream, &arrivals(&[20, 600])),
+            Err("expected at least 3 chunks, got 2".to_string())
+        );
+        assert_eq!(
+            assert_stream(&stream, &arrivals(&[20, 300, 1200])),
+            Err(
+                "expected the whole body within 1000ms, got the last chunk after 1200ms"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            assert_stream(&stream, &[]),
+            Err("expected first byte within 100ms, got an empty body".to_string())
+        );
+    }
+
+    #[cfg(feature = "stream")]
+    #[tokio::test]
+    async fn test_execute_stream() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/events")
+            .with_status(200)
+            .with_chunked_body(|w| {
+                for event in ["[", r#"{"id": 1}"#, ",", r#"{"id": 2}"#, "]"] {
+                    w.write_all(event.as_bytes())?;
+                    w.flush()?;
+                    std::thread::sleep(Duration::from_millis(50));
+                }
+                Ok(())
+            })
+            .create_async()
+            .await;
+
+        let test_case = |stream| TestCase {
+            request: Request {
+                uri: "/events".to_string(),
+                ..Default::default()
+            },
+            response: Response {
+                body: Some(r#"[{"id": 1}, {"id": 2}]"#.to_string()),
+                stream: Some(stream),
+                ..Default::default()
+            },
+        };
+
+        let url = server.url();
+        let target = Target::Url(url.as_str(), &HttpOptions::default());
+        let result = execute(
+            &target,
+            test_case(Stream {
+                first_byte: Some(200),
+                min_chunks: Some(3),
+                within: None,
+            }),
+            &mut Variables::new(),
+        )
+        .await;
+        assert_eq!(Ok(()), result.map(|_| ()));
+
+        let Err(err) = execute(
+            &target,
+            test_case(Stream {
+                within: Some(100),
+                ..Stream::default()
+            }),
+            &mut Variables::new(),
+        )
+        .await
+        else {
+            panic!("expected the stream to arrive too late");
+        };
+        let Failure::Response(message) = err.failure else {
+            panic!("expected a response failure, got {:?}", err.failure);
+        };
+        assert!(message.starts_with("expected the whole body within 100ms"));
+    }
+
+    #[tokio::test]
+    async fn test_execute_poll() {
+        let mut server = mockito::Server::new();
+        let pending = server
+            .mock("GET", "/jobs/1")
+            .with_status(200)
+            .with_body(r#"{"id": 1, "status": "pending"}"#)
+            .expect(2)
+            .create();
+        server
+            .mock("GET", "/jobs/1")
+            .with_status(200)
+            .with_body(r#"{"id": 1, "status": "ready"}"#)
+            .create();
 
         let test_case = TestCase {
             request: Request {
-                http_method: HttpMethod::Post,
-                headers: vec![(header_name.to_string(), header_value.to_string())]
-                    .into_iter()
-                    .collect(),
-                uri: users_endpoint.to_string(),
-                body: Some(request_body_template.to_string()),
-                line_number: 1,
+                uri: "/jobs/1".to_string(),
+                ..Default::default()
             },
             response: Response {
-                code: response_status as u16,
-                headers: vec![(header_name.to_string(), header_value.to_string())]
-                    .into_iter()
-                    .collect(),
-                ignore_paths: vec!["$.id".to_string()],
-                ignore_orders: vec![],
-                body: Some(response_body.to_string()),
-                line_number: 2,
-                variables: response_variables,
-                retries: RetryPolicy::default(),
+                body: Some(r#"{"id": 1, "status": "ready"}"#.to_string()),
+                poll: Some(Poll {
+                    timeout: 3000,
+                    interval: 10,
+                    path: "$.status".to_string(),
+                    value: json!("ready"),
+                }),
+                ..Default::default()
             },
         };
 
-        let mut variables = Variables::from_json(&json!({"name":"John"})).unwrap();
+        let url = server.url();
+        let result = execute(
+            &Target::Url(url.as_str(), &HttpOptions::default()),
+            test_case,
+            &mut Variables::new(),
+        )
+        .await;
+
+        assert_eq!(Ok(()), result.map(|_| ()));
+        pending.assert();
+    }
+
+    #[tokio::test]
+    async fn test_execute_no_unexpected_errors() {
+        let test_case = |no_unexpected_errors, code, poll| TestCase {
+            request: Request {
+                uri: "/jobs/1".to_string(),
+                ..Default::default()
+            },
+            response: Response {
+                code: ExpectedStatus::Exact(code),
+                retries: RetryPolicy {
+                    max_retries: 3,
+                    delay: 10,
+                    on: RetryOn::Both,
+                    statuses: vec![],
+                },
+                poll,
+                no_unexpected_errors,
+                ..Default::default()
+            },
+        };
+        let poll = || {
+            Some(Poll {
+                timeout: 3000,
+                interval: 10,
+                path: "$.status".to_string(),
+                value: json!("ready"),
+            })
+        };
 
-        let result: Result<(), String> =
-            execute(server.url().as_str(), test_case, &mut variables).await;
+        // the first response is an accidental server error, the retry passes
+        for (no_unexpected_errors, code, poll, passes) in [
+            (false, 200, None, true),
+            (true, 200, None, false),
+            (true, 200, poll(), false),
+            (true, 500, None, true),
+        ] {
+            let mut server = mockito::Server::new_async().await;
+            server
+                .mock("GET", "/jobs/1")
+                .with_status(500)
+                .with_body(r#"{"status": "failed"}"#)
+                .expect(1)
+                .create();
+            server
+                .mock("GET", "/jobs/1")
+                .with_status(200)
+                .with_body(r#"{"status": "ready"}"#)
+                .expect_at_least(0)
+                .create();
 
-        match result {
-            Ok(_) => {}
-            Err(ref err) => assert_eq!("", err),
+            let url = server.url();
+            let result = execute(
+                &Target::Url(url.as_str(), &HttpOptions::default()),
+                test_case(no_unexpected_errors, code, poll.clone()),
+                &mut Variables::new(),
+            )
+            .await;
+
+            assert_eq!(
+                passes,
+                result.is_ok(),
+                "no unexpected errors {}, code {}, poll {:?}: {:?}",
+                no_unexpected_errors,
+                code,
+                poll,
+                result
+            );
+            if !passes && poll.is_none() {
+                let err = result.unwrap_err();
+                assert!(err
+                    .to_string()
+                    .starts_with("unexpected server error from GET /jobs/1 documented at line 2"));
+            }
         }
-        assert!(result.is_ok());
+    }
 
-        let test_case = TestCase {
+    #[tokio::test]
+    async fn test_execute_websocket_handshake() {
+        let mut server = mockito::Server::new_async().await;
+        // example handshake from RFC 6455
+        server
+            .mock("GET", "/chat")
+            .match_header("Upgrade", "websocket")
+            .match_header("Sec-WebSocket-Key", "dGhlIHNhbXBsZSBub25jZQ==")
+            .with_status(101)
+            .with_header("Upgrade", "websocket")
+            .with_header("Connection", "Upgrade")
+            .with_header("Sec-WebSocket-Accept", "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=")
+            .create();
+        server
+            .mock("GET", "/chat")
+            .match_header("Sec-WebSocket-Key", "x3JJHMbDL1EzLkh9GBhXDw==")
+            .with_status(101)
+            .with_header("Upgrade", "websocket")
+            .with_header("Connection", "Upgrade")
+            .with_header("Sec-WebSocket-Accept", "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=")
+            .create();
+
+        let test_case = |key: &str| TestCase {
             request: Request {
-                http_method: HttpMethod::Get,
-                headers: vec![(header_name.to_string(), header_value.to_string())]
+                headers: vec![
+                    ("Upgrade".to_string(), "websocket".to_string()),
+                    ("Connection".to_string(), "Upgrade".to_string()),
+                    ("Sec-WebSocket-Key".to_string(), key.to_string()),
+                    ("Sec-WebSocket-Version".to_string(), "13".to_string()),
+                ]
+                .into_iter()
+                .collect(),
+                uri: "/chat".to_string(),
+                ..Default::default()
+            },
+            response: Response {
+                code: ExpectedStatus::Exact(101),
+                headers: vec![("Upgrade".to_string(), "websocket".to_string())]
                     .into_iter()
                     .collect(),
-                uri: format!("{}/`id`", users_endpoint),
-                body: None,
-                line_number: 3,
+                ..Default::default()
+            },
+        };
+
+        let url = server.url();
+        let result = execute(
+            &Target::Url(url.as_str(), &HttpOptions::default()),
+            test_case("dGhlIHNhbXBsZSBub25jZQ=="),
+            &mut Variables::new(),
+        )
+        .await;
+        assert_eq!(Ok(()), result.map(|_| ()));
+
+        // the accept value doesn't match the key
+        let err = execute(
+            &Target::Url(url.as_str(), &HttpOptions::default()),
+            test_case("x3JJHMbDL1EzLkh9GBhXDw=="),
+            &mut Variables::new(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            err.failure,
+            Failure::Header(
+                "expected header Sec-WebSocket-Accept to be HSmrc0sMlYUkAGmm5OPpG2HaGWk=, got s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+                    .to_string()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_numeric_mode() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/stats")
+            .with_status(200)
+            .with_body(r#"{"count": 3.0, "average": 1.005}"#)
+            .create();
+
+        let test_case = |numeric_mode| TestCase {
+            request: Request {
+                uri: "/stats".to_string(),
+                ..Default::default()
             },
             response: Response {
-                code: 200,
-                headers: vec![(header_name.to_string(), header_value.to_string())]
+                body: Some(r#"{"count": 3, "average": 1.0}"#.to_string()),
+                numeric_mode,
+                ..Default::default()
+            },
+        };
+
+        let url = server.url();
+        for (numeric_mode, differences) in [
+            (None, 2),
+            (Some(NumericMode::Strict), 2),
+            (Some(NumericMode::AssumeFloat), 1),
+            (Some(NumericMode::Tolerance(0.001)), 1),
+            (Some(NumericMode::Tolerance(0.01)), 0),
+            (Some(NumericMode::SignificantFigures(2)), 0),
+        ] {
+            let result = execute(
+                &Target::Url(url.as_str(), &HttpOptions::default()),
+                test_case(numeric_mode),
+                &mut Variables::new(),
+            )
+            .await;
+
+            match result {
+                Ok(_) => assert_eq!(differences, 0, "{:?}", numeric_mode),
+                Err(ExecutionError {
+                    failure: Failure::Body(diffs),
+                    ..
+                }) => assert_eq!(differences, diffs.len(), "{:?}", numeric_mode),
+                Err(err) => panic!("unexpected error {}", err),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_execute_retry_on() {
+        let test_case = |on| TestCase {
+            request: Request {
+                uri: "/jobs/1".to_string(),
+                ..Default::default()
+            },
+            response: Response {
+                body: Some(r#"{"id": 1, "status": "ready"}"#.to_string()),
+                retries: RetryPolicy {
+                    max_retries: 3,
+                    delay: 200,
+                    on,
+                    statuses: vec![],
+                },
+                ..Default::default()
+            },
+        };
+
+        // the first response doesn't match, the second one does
+        for (on, passes) in [
+            (RetryOn::Error, false),
+            (RetryOn::Assert, true),
+            (RetryOn::Both, true),
+        ] {
+            let mut server = mockito::Server::new_async().await;
+            let pending = server
+                .mock("GET", "/jobs/1")
+                .with_status(200)
+                .with_body(r#"{"id": 1, "status": "pending"}"#)
+                .expect(1)
+                .create();
+            server
+                .mock("GET", "/jobs/1")
+                .with_status(200)
+                .with_body(r#"{"id": 1, "status": "ready"}"#)
+                .create();
+
+            let url = server.url();
+            let result = execute(
+                &Target::Url(url.as_str(), &HttpOptions::default()),
+                test_case(on),
+                &mut Variables::new(),
+            )
+            .await;
+
+            assert_eq!(passes, result.is_ok(), "retry on {:?}", on);
+            pending.assert();
+        }
+
+        // the listed status is retried even if mismatching responses are not
+        for (statuses, passes) in [(vec![], false), (vec![502, 503], true)] {
+            let mut server = mockito::Server::new_async().await;
+            let warming_up = server
+                .mock("GET", "/jobs/1")
+                .with_status(503)
+                .expect(1)
+                .create();
+            server
+                .mock("GET", "/jobs/1")
+                .with_status(200)
+                .with_body(r#"{"id": 1, "status": "ready"}"#)
+                .create();
+
+            let mut test_case = test_case(RetryOn::Error);
+            test_case.response.retries.statuses = statuses.clone();
+            let url = server.url();
+            let result = execute(
+                &Target::Url(url.as_str(), &HttpOptions::default()),
+                test_case,
+                &mut Variables::new(),
+            )
+            .await;
+
+            assert_eq!(passes, result.is_ok(), "retry on statuses {:?}", statuses);
+            warming_up.assert();
+        }
+
+        // nothing listens on the port, so the connection is refused
+        let url = {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            format!("http://{}", listener.local_addr().unwrap())
+        };
+        for (on, retried) in [
+            (RetryOn::Error, true),
+            (RetryOn::Assert, false),
+            (RetryOn::Both, true),
+        ] {
+            let start = Instant::now();
+            let err = execute(
+                &Target::Url(url.as_str(), &HttpOptions::default()),
+                test_case(on),
+                &mut Variables::new(),
+            )
+            .await
+            .unwrap_err();
+
+            assert!(matches!(err.failure, Failure::Request(_)));
+            assert_eq!(
+                retried,
+                start.elapsed() >= Duration::from_millis(400),
+                "retry on {:?}",
+                on
+            );
+        }
+    }
+
+    #[cfg(feature = "grpc-web")]
+    #[tokio::test]
+    async fn test_execute_grpc_web() {
+        let frame = |flags: u8, payload: &str| {
+            let mut frame = vec![flags];
+            frame.extend((payload.len() as u32).to_be_bytes());
+            frame.extend(payload.as_bytes());
+            frame
+        };
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/blog.Blog/GetPost")
+            .with_status(200)
+            .with_header("Content-Type", "application/grpc-web+json")
+            .with_body(
+                [
+                    frame(0x00, r#"{"id": 1, "title": "My First Blog"}"#),
+                    frame(0x80, "grpc-status:0\r\n"),
+                ]
+                .concat(),
+            )
+            .create();
+        server
+            .mock("POST", "/blog.Blog/DeletePost")
+            .with_status(200)
+            .with_header("Content-Type", "application/grpc-web+json")
+            .with_body(frame(
+                0x80,
+                "grpc-status:7\r\ngrpc-message:permission denied\r\n",
+            ))
+            .create();
+
+        let test_case = |uri: &str, headers: Vec<(&str, &str)>, body: Option<&str>| TestCase {
+            request: Request {
+                http_method: HttpMethod::Post,
+                uri: uri.to_string(),
+                body: Some(r#"{"id": 1}"#.to_string()),
+                ..Default::default()
+            },
+            response: Response {
+                headers: headers
                     .into_iter()
+                    .map(|(name, value)| (name.to_string(), value.to_string()))
                     .collect(),
-                ignore_paths: vec![],
-                ignore_orders: vec![],
-                body: Some(response_body.to_string()),
-                line_number: 4,
-                variables: HashMap::new(),
-                retries: RetryPolicy::default(),
+                body: body.map(|body| body.to_string()),
+                ..Default::default()
             },
         };
 
-        let result: Result<(), String> =
-            execute(server.url().as_str(), test_case, &mut variables).await;
+        let url = server.url();
+        let target = Target::Url(url.as_str(), &HttpOptions::default());
+        let result = execute(
+            &target,
+            test_case(
+                "/blog.Blog/GetPost",
+                vec![("grpc-status", "0")],
+                Some(r#"{"id": 1, "title": "My First Blog"}"#),
+            ),
+            &mut Variables::new(),
+        )
+        .await;
+        assert_eq!(Ok(()), result.map(|_| ()));
+
+        let err = execute(
+            &target,
+            test_case("/blog.Blog/DeletePost", vec![], None),
+            &mut Variables::new(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            err.failure,
+            Failure::Response("gRPC call failed with grpc-status 7: permission denied".to_string())
+        );
+
+        let result = execute(
+            &target,
+            test_case("/blog.Blog/DeletePost", vec![("grpc-status", "7")], None),
+            &mut Variables::new(),
+        )
+        .await;
+        assert_eq!(Ok(()), result.map(|_| ()));
+    }
+
+    #[test]
+    fn test_merge() {
+        let base = json!({"status": "ok", "meta": {"version": 1, "page": 1}, "data": null});
+        assert_eq!(
+            merge(&base, json!({"meta": {"page": 2}, "data": [1, 2]})),
+            json!({"status": "ok", "meta": {"version": 1, "page": 2}, "data": [1, 2]})
+        );
+        assert_eq!(
+            merge(&base, json!({"status": "error", "errors": ["not found"]})),
+            json!({"status": "error", "meta": {"version": 1, "page": 1}, "data": null, "errors": ["not found"]})
+        );
+        assert_eq!(merge(&base, json!([1, 2])), json!([1, 2]));
+    }
+
+    #[test]
+    fn test_parse_actual_body() {
+        assert_eq!(
+            parse_actual_body("\u{feff}\n  {\"id\": 1}\r\n"),
+            Ok(json!({"id": 1}))
+        );
+        assert_eq!(
+            parse_actual_body("\u{feff}{\"id\": }"),
+            Err("error parsing JSON response from the server: expected value at line 1 column 8 in body \"\\u{feff}{\\\"id\\\": }\"".to_string())
+        );
+    }
+
+    #[test]
+    fn test_patch_variable() {
+        let mut variables = Variables::new();
+        variables.insert_value("post".to_string(), json!({"title": "Draft"}));
+        let patch = r#"[{"op": "replace", "path": "/title", "value": "Published"}]"#;
+        assert_eq!(
+            patch_variable("post", Some(patch), &variables),
+            Ok(r#"{"title":"Published"}"#.to_string())
+        );
+        assert_eq!(
+            patch_variable("draft", Some(patch), &variables),
+            Err("variable draft to patch is not defined".to_string())
+        );
+        assert_eq!(
+            patch_variable("post", None, &variables),
+            Err("error parsing JSON Patch of variable post: EOF while parsing a value at line 1 column 0".to_string())
+        );
+        assert_eq!(
+            patch_variable(
+                "post",
+                Some(r#"[{"op": "remove", "path": "/body"}]"#),
+                &variables
+            ),
+            Err("error patching variable post: error applying JSON Patch operation 0: no value at /body".to_string())
+        );
+    }
+
+    #[test]
+    fn test_apply_transform() {
+        let transform = |path: &str, transformation: Transformation| Transform {
+            path: path.to_string(),
+            transformation,
+        };
+        let mut actual = json!({
+            "tags": ["rust", "api", "docs"],
+            "ids": [10, 2, 1.5],
+            "posts": [{"title": "  Hello  ", "author": "JOHN"}, {"title": "World ", "author": "Jane"}],
+            "score": 1.23456,
+            "total": 2.5
+        });
+
+        apply_transform(&transform("$.tags", Transformation::Sort), &mut actual).unwrap();
+        apply_transform(&transform("$.ids", Transformation::Sort), &mut actual).unwrap();
+        apply_transform(
+            &transform("$.posts[*].title", Transformation::Trim),
+            &mut actual,
+        )
+        .unwrap();
+        apply_transform(
+            &transform("$.posts[*].author", Transformation::Lowercase),
+            &mut actual,
+        )
+        .unwrap();
+        apply_transform(&transform("$.score", Transformation::Round(2)), &mut actual).unwrap();
+        apply_transform(&transform("$.total", Transformation::Round(0)), &mut actual).unwrap();
+        assert_eq!(
+            actual,
+            json!({
+                "tags": ["api", "docs", "rust"],
+                "ids": [1.5, 2, 10],
+                "posts": [{"title": "Hello", "author": "john"}, {"title": "World", "author": "jane"}],
+                "score": 1.23,
+                "total": 3
+            })
+        );
+
+        assert_eq!(
+            apply_transform(&transform("$.score", Transformation::Sort), &mut actual),
+            Err(
+                "error applying sort to the value at path $.score: expected array, got 1.23"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            apply_transform(&transform("$.ids[0]", Transformation::Trim), &mut actual),
+            Err(
+                "error applying trim to the value at path $.ids[0]: expected string, got 1.5"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            apply_transform(
+                &transform("$.missing", Transformation::Lowercase),
+                &mut actual
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_assert_problem() {
+        let problem = json!({
+            "type": "https://example.com/probs/not-found",
+            "title": "Blog not found",
+            "status": 404,
+            "detail": "Blog 7 does not exist",
+            "instance": "/blog/7"
+        });
+        assert_eq!(Ok(()), assert_problem(404, &problem));
+        assert_eq!(
+            Ok(()),
+            assert_problem(
+                404,
+                &json!({"type": "about:blank", "title": "Not Found", "status": 404})
+            )
+        );
+
+        let malformed = json!({"title": 404, "status": "404", "detail": ["missing"]});
+        assert_eq!(
+            Err(vec![
+                "problem member type is missing".to_string(),
+                "expected problem member title to be string, got number".to_string(),
+                "expected problem member status to be number, got string".to_string(),
+                "expected problem member detail to be string, got array".to_string(),
+            ]),
+            assert_problem(404, &malformed)
+        );
+        assert_eq!(
+            Err(vec![
+                "problem member status 500 does not match the response status 404".to_string()
+            ]),
+            assert_problem(
+                404,
+                &json!({"type": "about:blank", "title": "Error", "status": 500})
+            )
+        );
+        assert_eq!(
+            Err(vec![
+                "expected a problem document object, got array".to_string()
+            ]),
+            assert_problem(404, &json!([]))
+        );
+    }
+
+    #[cfg(feature = "json-schema")]
+    #[test]
+    fn test_assert_schema() {
+        let schema = "tests/data/blog.schema.json";
+        assert_eq!(
+            Ok(()),
+            assert_schema(
+                schema,
+                &json!({"id": 1, "title": "My First Blog", "tags": []})
+            )
+        );
+        assert_eq!(
+            Err(vec![
+                "json atom at path \".tags[1]\" is invalid: 2 is not of type \"string\""
+                    .to_string(),
+                "json atom at path \"(root)\" is invalid: \"title\" is a required property"
+                    .to_string(),
+            ]),
+            assert_schema(schema, &json!({"id": 1, "tags": ["a", 2]}))
+        );
+        assert!(assert_schema("tests/data/missing.schema.json", &json!({})).is_err());
+    }
+
+    #[cfg(not(feature = "json-schema"))]
+    #[test]
+    fn test_assert_schema_requires_feature() {
+        assert_eq!(
+            Err(vec![
+                "schema tests/data/blog.schema.json requires the json-schema feature".to_string()
+            ]),
+            assert_schema("tests/data/blog.schema.json", &json!({}))
+        );
+    }
+
+    #[test]
+    fn test_assert_absent() {
+        let user = json!({
+            "id": 1,
+            "name": "John",
+            "sessions": [{"id": 7}, {"id": 8, "token": "secret"}]
+        });
+        assert_eq!(Ok(()), assert_absent(&["$.password".to_string()], &user));
+        assert_eq!(
+            Err(vec![
+                "expected path $.name to be absent but it was present".to_string(),
+                "expected path $.sessions[1].token to be absent but it was present".to_string(),
+            ]),
+            assert_absent(
+                &["$.name".to_string(), "$.sessions[*].token".to_string()],
+                &user
+            )
+        );
+        assert_eq!(
+            Err(vec![
+                "expected path $.sessions[1].token to be absent but it was present".to_string()
+            ]),
+            assert_absent(&["$..token".to_string()], &user)
+        );
+    }
+
+    #[cfg(feature = "key-order")]
+    #[test]
+    fn test_assert_key_order() {
+        let expected = json!({
+            "data": {"id": 1, "name": "John", "email": "john@example.com"},
+            "meta": {"page": 1, "total": 2},
+            "items": [{"id": 1, "price": 10}, {"id": 2, "price": 20}]
+        });
+        let actual = json!({
+            "meta": {"total": 2, "page": 1},
+            "items": [{"id": 1, "price": 10}, {"price": 20, "id": 2}],
+            "data": {"id": 1, "created": "2024-01-01", "name": "John", "email": "john@example.com"}
+        });
+
+        // keys only in one of the bodies do not affect the order
+        assert_eq!(Ok(()), assert_key_order("$.data", &expected, &actual));
+        assert_eq!(Ok(()), assert_key_order("/data", &expected, &actual));
+        assert_eq!(Ok(()), assert_key_order("$.items[0]", &expected, &actual));
+        assert_eq!(Ok(()), assert_key_order("$.missing", &expected, &actual));
+        assert_eq!(
+            Err("expected keys at path $ in order [\"data\", \"meta\", \"items\"], got [\"meta\", \"items\", \"data\"]".to_string()),
+            assert_key_order("$", &expected, &actual)
+        );
+        assert_eq!(
+            Err("expected keys at path $.meta in order [\"page\", \"total\"], got [\"total\", \"page\"]".to_string()),
+            assert_key_order("$.meta", &expected, &actual)
+        );
+        assert_eq!(
+            Err("expected keys at path $.items[1] in order [\"id\", \"price\"], got [\"price\", \"id\"]".to_string()),
+            assert_key_order("$.items[*]", &expected, &actual)
+        );
+        // the objects outside the ordered paths are compared regardless of the order
+        assert!(diff(
+            &expected["meta"],
+            &actual["meta"],
+            Config::new(CompareMode::Strict)
+        )
+        .is_empty());
+    }
+
+    #[cfg(not(feature = "key-order"))]
+    #[test]
+    fn test_assert_key_order_requires_feature() {
+        let body = json!({"id": 1, "name": "John"});
+        assert_eq!(
+            Err("key order at path $ requires the key-order feature".to_string()),
+            assert_key_order("$", &body, &body)
+        );
+    }
+
+    #[cfg(feature = "json5")]
+    #[test]
+    fn test_parse_expected_body_json5() {
+        let body = r#"{
+            // identifier assigned by the server
+            "id": 1,
+            "name": "John",
+            /* trailing commas are allowed */
+            "tags": ["admin", "user",],
+        }"#;
+
+        assert_eq!(
+            Ok(json!({"id": 1, "name": "John", "tags": ["admin", "user"]})),
+            parse_expected_body(body)
+        );
+        assert!(parse_expected_body("{\"id\": }").is_err());
+    }
+
+    #[cfg(feature = "tls-inspect")]
+    #[test]
+    fn test_assert_tls() {
+        let valid = std::fs::read("tests/data/tls/valid.der").unwrap();
+        let expired = std::fs::read("tests/data/tls/expired.der").unwrap();
+        let common_name = TlsCheck::CommonName("example.com".to_string());
 
-        assert_eq!(Ok(()), result);
+        assert_eq!(Ok(()), assert_tls(&common_name, Some(&valid)));
+        assert_eq!(Ok(()), assert_tls(&TlsCheck::NotExpired, Some(&valid)));
+        assert_eq!(
+            Err(
+                "expected TLS certificate with common name example.com, got expired.example.com"
+                    .to_string()
+            ),
+            assert_tls(&common_name, Some(&expired))
+        );
+        assert!(assert_tls(&TlsCheck::NotExpired, Some(&expired)).is_err());
+        assert!(assert_tls(&TlsCheck::NotExpired, None).is_err());
     }
 }
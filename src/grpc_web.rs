@@ -0,0 +1,152 @@
+// Copyright 2024 The DocAssert Authors
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use reqwest::header::{HeaderMap, CONTENT_TYPE};
+
+/// Content type of the gRPC-Web responses using the JSON codec
+const GRPC_WEB_JSON: &str = "application/grpc-web+json";
+/// Flag of the frame carrying the trailers instead of a message
+const TRAILER_FLAG: u8 = 0x80;
+const FRAME_HEADER_LEN: usize = 5;
+
+/// Checks if the response is framed as gRPC-Web with the JSON codec.
+pub(crate) fn is_grpc_web_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with(GRPC_WEB_JSON))
+}
+
+/// Strips the gRPC-Web framing and returns the JSON body together with the trailers.
+///
+/// A single message becomes the body as is, multiple messages are collected into an array.
+pub(crate) fn decode(bytes: &[u8]) -> Result<(String, Vec<(String, String)>), String> {
+    let mut messages = vec![];
+    let mut trailers = vec![];
+    let mut rest = bytes;
+
+    while !rest.is_empty() {
+        if rest.len() < FRAME_HEADER_LEN {
+            return Err("truncated gRPC-Web frame header".to_string());
+        }
+        let flags = rest[0];
+        let len = u32::from_be_bytes([rest[1], rest[2], rest[3], rest[4]]) as usize;
+        let payload = rest
+            .get(FRAME_HEADER_LEN..FRAME_HEADER_LEN + len)
+            .ok_or_else(|| format!("truncated gRPC-Web frame of {} bytes", len))?;
+        let payload = std::str::from_utf8(payload)
+            .map_err(|e| format!("invalid UTF-8 in gRPC-Web frame: {}", e))?;
+
+        if flags & TRAILER_FLAG != 0 {
+            trailers.extend(
+                payload
+                    .split("\r\n")
+                    .filter_map(|line| line.split_once(':'))
+                    .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string())),
+            );
+        } else {
+            messages.push(payload);
+        }
+        rest = &rest[FRAME_HEADER_LEN + len..];
+    }
+
+    let body = match messages.as_slice() {
+        [] => String::new(),
+        [message] => message.to_string(),
+        messages => format!("[{}]", messages.join(",")),
+    };
+
+    Ok((body, trailers))
+}
+
+/// Fails if the call ended with a non-zero `grpc-status` the documentation doesn't expect.
+pub(crate) fn assert_status(
+    headers: &HeaderMap,
+    documented_headers: &std::collections::HashMap<String, String>,
+) -> Result<(), String> {
+    if !is_grpc_web_json(headers)
+        || documented_headers
+            .keys()
+            .any(|name| name.eq_ignore_ascii_case("grpc-status"))
+    {
+        return Ok(());
+    }
+
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+    match header("grpc-status") {
+        Some("0") | None => Ok(()),
+        Some(status) => Err(format!(
+            "gRPC call failed with grpc-status {}: {}",
+            status,
+            header("grpc-message").unwrap_or_default()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(flags: u8, payload: &str) -> Vec<u8> {
+        let mut frame = vec![flags];
+        frame.extend((payload.len() as u32).to_be_bytes());
+        frame.extend(payload.as_bytes());
+        frame
+    }
+
+    #[test]
+    fn test_decode() {
+        let body = [
+            frame(0x00, r#"{"id": 1}"#),
+            frame(TRAILER_FLAG, "grpc-status:0\r\ngrpc-message: OK\r\n"),
+        ]
+        .concat();
+        assert_eq!(
+            decode(&body),
+            Ok((
+                r#"{"id": 1}"#.to_string(),
+                vec![
+                    ("grpc-status".to_string(), "0".to_string()),
+                    ("grpc-message".to_string(), "OK".to_string())
+                ]
+            ))
+        );
+
+        let body = [frame(0x00, r#"{"id": 1}"#), frame(0x00, r#"{"id": 2}"#)].concat();
+        assert_eq!(
+            decode(&body),
+            Ok((r#"[{"id": 1},{"id": 2}]"#.to_string(), vec![]))
+        );
+
+        let truncated = &frame(0x00, r#"{"id": 1}"#)[..8];
+        assert!(decode(truncated).is_err());
+        assert!(decode(&[0x00, 0x00]).is_err());
+    }
+
+    #[test]
+    fn test_assert_status() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, GRPC_WEB_JSON.parse().unwrap());
+        headers.insert("grpc-status", "5".parse().unwrap());
+        headers.insert("grpc-message", "not found".parse().unwrap());
+
+        let mut documented = std::collections::HashMap::new();
+        assert_eq!(
+            assert_status(&headers, &documented),
+            Err("gRPC call failed with grpc-status 5: not found".to_string())
+        );
+
+        documented.insert("grpc-status".to_string(), "5".to_string());
+        assert_eq!(assert_status(&headers, &documented), Ok(()));
+    }
+}
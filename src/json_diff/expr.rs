@@ -0,0 +1,138 @@
+// Copyright 2024 The DocAssert Authors
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// Evaluates an arithmetic expression consisting of numbers, parentheses
+/// and the `+`, `-`, `*`, `/` and `%` operators.
+///
+/// Variables are replaced in the expression before it is evaluated,
+/// so only numeric literals are expected here.
+pub(crate) fn evaluate(expression: &str) -> Result<f64, String> {
+    let mut chars = expression.chars().peekable();
+    let value = parse_sum(&mut chars)?;
+
+    skip_whitespace(&mut chars);
+    match chars.next() {
+        None => Ok(value),
+        Some(c) => Err(format!("unexpected character '{}'", c)),
+    }
+}
+
+fn parse_sum(chars: &mut Peekable<Chars>) -> Result<f64, String> {
+    let mut value = parse_product(chars)?;
+
+    loop {
+        skip_whitespace(chars);
+        match chars.peek() {
+            Some('+') => {
+                chars.next();
+                value += parse_product(chars)?;
+            }
+            Some('-') => {
+                chars.next();
+                value -= parse_product(chars)?;
+            }
+            _ => return Ok(value),
+        }
+    }
+}
+
+fn parse_product(chars: &mut Peekable<Chars>) -> Result<f64, String> {
+    let mut value = parse_factor(chars)?;
+
+    loop {
+        skip_whitespace(chars);
+        let operator = match chars.peek() {
+            Some(c @ ('*' | '/' | '%')) => *c,
+            _ => return Ok(value),
+        };
+        chars.next();
+
+        let rhs = parse_factor(chars)?;
+        value = match operator {
+            '*' => value * rhs,
+            _ if rhs == 0.0 => return Err("division by zero".to_string()),
+            '/' => value / rhs,
+            _ => value % rhs,
+        };
+    }
+}
+
+fn parse_factor(chars: &mut Peekable<Chars>) -> Result<f64, String> {
+    skip_whitespace(chars);
+    match chars.peek() {
+        Some('-') => {
+            chars.next();
+            Ok(-parse_factor(chars)?)
+        }
+        Some('(') => {
+            chars.next();
+            let value = parse_sum(chars)?;
+            skip_whitespace(chars);
+            match chars.next() {
+                Some(')') => Ok(value),
+                _ => Err("missing closing parenthesis".to_string()),
+            }
+        }
+        Some(c) if c.is_ascii_digit() || *c == '.' => {
+            let mut number = String::new();
+            while let Some(c) = chars.peek() {
+                if !c.is_ascii_digit() && *c != '.' {
+                    break;
+                }
+                number.push(*c);
+                chars.next();
+            }
+            number
+                .parse::<f64>()
+                .map_err(|_| format!("invalid number {}", number))
+        }
+        Some(c) => Err(format!("unexpected character '{}'", c)),
+        None => Err("unexpected end of expression".to_string()),
+    }
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate() {
+        assert_eq!(evaluate("1 + 2"), Ok(3.0));
+        assert_eq!(evaluate("10 * 3"), Ok(30.0));
+        assert_eq!(evaluate("2 + 3 * 4"), Ok(14.0));
+        assert_eq!(evaluate("(2 + 3) * 4"), Ok(20.0));
+        assert_eq!(evaluate("10 - 4 - 3"), Ok(3.0));
+        assert_eq!(evaluate("7 / 2"), Ok(3.5));
+        assert_eq!(evaluate("7 % 2"), Ok(1.0));
+        assert_eq!(evaluate("-2.5 * 2"), Ok(-5.0));
+    }
+
+    #[test]
+    fn test_evaluate_errors() {
+        assert_eq!(evaluate("1 / 0"), Err("division by zero".to_string()));
+        assert_eq!(evaluate("1 % (2 - 2)"), Err("division by zero".to_string()));
+        assert!(evaluate("(1 + 2").is_err());
+        assert!(evaluate("1 +").is_err());
+        assert!(evaluate("price * 2").is_err());
+        assert!(evaluate("1 2").is_err());
+    }
+}
@@ -11,49 +11,138 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+//! Comparison of JSON values used to assert the response bodies.
+//!
+//! See the [crate documentation](crate) for an example of using it directly.
+
 #![allow(dead_code)]
 
-use std::{collections::HashSet, fmt};
+use std::{collections::HashSet, fmt, str::FromStr};
 
+use regex::Regex;
 use serde_json::Value;
 
 use misc::{Indent, Indexes};
 use path::{Key, Path};
+use units::Quantity;
 
+mod expr;
 mod misc;
 pub mod path;
+mod units;
+
+/// Prefix of the expected string values which are evaluated as arithmetic expressions
+/// and compared with the actual number.
+pub(crate) const EXPR_MATCHER: &str = "@expr:";
+/// Prefix of the expected string values which are compared with the actual string ignoring case.
+pub(crate) const EQI_MATCHER: &str = "@eqi:";
+/// Expected value matching any present value, including `null`. Only a missing field fails.
+pub(crate) const EXISTS_MATCHER: &str = "@exists";
+/// Key of the only field of an expected object whose value is a fragment matched against
+/// every value of the actual object, whatever its keys are.
+pub(crate) const KEYS_MATCHER: &str = "@keys";
+/// Prefix of the expected durations like `1500ms` which are compared with the actual duration
+/// in any unit.
+pub(crate) const DURATION_MATCHER: &str = "@duration:";
+/// Prefix of the expected sizes like `1MiB` which are compared with the actual size in any unit.
+pub(crate) const BYTES_MATCHER: &str = "@bytes:";
 
 /// Mode for how JSON values should be compared.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
-pub(crate) enum CompareMode {
-    /// The two JSON values don't have to be exactly equal. The "expected" value is only required to
-    /// be "contained" inside "actual". See [crate documentation](index.html) for examples.
-    ///
-    /// The mode used with [`assert_json_include`].
+pub enum CompareMode {
+    /// The two JSON values don't have to be exactly equal. The "actual" value is only required to
+    /// be "contained" inside "expected", so an empty "actual" array or object matches anything.
+    /// An empty "expected" array or object matches any "actual" one as well, unless the exact
+    /// length of the array is required.
     Inclusive,
     /// The two JSON values must be exactly equal.
-    ///
-    /// The mode used with [`assert_json_eq`].
     Strict,
 }
 
 /// How should numbers be compared.
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-pub(crate) enum NumericMode {
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum NumericMode {
     /// Different numeric types aren't considered equal.
     Strict,
     /// All numeric types are converted to float before comparison.
     AssumeFloat,
+    /// Numbers converted to float are equal if they differ by at most the given tolerance.
+    Tolerance(f64),
+    /// Numbers converted to float are equal if they match when rounded to the given number
+    /// of significant figures.
+    ///
+    /// Unlike [`NumericMode::Tolerance`] the accepted difference is relative to the magnitude
+    /// of the numbers, so both `0.0012345` and `12345.0` keep three meaningful digits at `3`.
+    SignificantFigures(u32),
+}
+
+/// Kind of a JSON value.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum JsonType {
+    Null,
+    Boolean,
+    Number,
+    String,
+    Array,
+    Object,
+}
+
+impl JsonType {
+    /// Returns the kind of the value.
+    pub fn of(value: &Value) -> Self {
+        match value {
+            Value::Null => JsonType::Null,
+            Value::Bool(_) => JsonType::Boolean,
+            Value::Number(_) => JsonType::Number,
+            Value::String(_) => JsonType::String,
+            Value::Array(_) => JsonType::Array,
+            Value::Object(_) => JsonType::Object,
+        }
+    }
+}
+
+impl fmt::Display for JsonType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonType::Null => write!(f, "null"),
+            JsonType::Boolean => write!(f, "boolean"),
+            JsonType::Number => write!(f, "number"),
+            JsonType::String => write!(f, "string"),
+            JsonType::Array => write!(f, "array"),
+            JsonType::Object => write!(f, "object"),
+        }
+    }
+}
+
+impl FromStr for JsonType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "null" => Ok(JsonType::Null),
+            "boolean" | "bool" => Ok(JsonType::Boolean),
+            "number" => Ok(JsonType::Number),
+            "string" => Ok(JsonType::String),
+            "array" => Ok(JsonType::Array),
+            "object" => Ok(JsonType::Object),
+            _ => Err(format!("{} is not a valid JSON type", s)),
+        }
+    }
 }
 
 /// Configuration for how JSON values should be compared.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone)]
 #[allow(missing_copy_implementations)]
-pub(crate) struct Config {
+pub struct Config {
     pub(crate) compare_mode: CompareMode,
     pub(crate) numeric_mode: NumericMode,
     pub(crate) ignore_paths: Vec<Path>,
     pub(crate) ignore_orders: Vec<Path>,
+    pub(crate) set_equals: Vec<Path>,
+    pub(crate) subsets: Vec<Path>,
+    pub(crate) exact_lengths: Vec<Path>,
+    pub(crate) patterns: Vec<(Path, Regex)>,
+    pub(crate) types: Vec<(Path, JsonType)>,
 }
 
 impl Config {
@@ -66,6 +155,11 @@ impl Config {
             numeric_mode: NumericMode::Strict,
             ignore_paths: vec![],
             ignore_orders: vec![],
+            set_equals: vec![],
+            subsets: vec![],
+            exact_lengths: vec![],
+            patterns: vec![],
+            types: vec![],
         }
     }
 
@@ -90,7 +184,7 @@ impl Config {
     }
 
     /// Checks if the given path should be ignored.
-    pub fn to_ignore(&self, path: &Path) -> bool {
+    pub(crate) fn to_ignore(&self, path: &Path) -> bool {
         self.ignore_paths.iter().any(|p| p.prefixes(path))
     }
 
@@ -102,16 +196,79 @@ impl Config {
     }
 
     /// Checks if the given path should be ignored order.
-    pub fn to_ignore_order(&self, path: &Path) -> bool {
+    pub(crate) fn to_ignore_order(&self, path: &Path) -> bool {
         self.ignore_orders.iter().any(|p| p == path)
     }
+
+    /// Add a path to the list of paths where arrays must contain the same elements
+    /// the same number of times, in any order.
+    pub fn set_equal(mut self, path: Path) -> Self {
+        self.set_equals.push(path);
+        self
+    }
+
+    /// Checks if the array at the given path should be compared as a multiset.
+    pub(crate) fn to_check_set_equal(&self, path: &Path) -> bool {
+        self.set_equals.iter().any(|p| p == path)
+    }
+
+    /// Add a path to the list of paths where arrays must contain each expected element,
+    /// in any order, while other actual elements are allowed.
+    pub fn subset(mut self, path: Path) -> Self {
+        self.subsets.push(path);
+        self
+    }
+
+    /// Checks if the expected array at the given path should be a subset of the actual one.
+    pub(crate) fn to_check_subset(&self, path: &Path) -> bool {
+        self.subsets.iter().any(|p| p == path)
+    }
+
+    /// Add a path to the list of paths where arrays must have the same length
+    /// even in the inclusive mode. This applies to the arrays at or below the path.
+    pub fn exact_length(mut self, path: Path) -> Self {
+        self.exact_lengths.push(path);
+        self
+    }
+
+    /// Checks if the array at the given path must have the same length as the expected one.
+    pub(crate) fn to_check_exact_length(&self, path: &Path) -> bool {
+        self.exact_lengths.iter().any(|p| p.prefixes(path))
+    }
+
+    /// Add a path where the actual value has to match the pattern instead of
+    /// being equal to the expected one.
+    pub fn match_pattern(mut self, path: Path, pattern: Regex) -> Self {
+        self.patterns.push((path, pattern));
+        self
+    }
+
+    /// Returns the pattern the actual value at the given path has to match, if any.
+    pub(crate) fn pattern_at(&self, path: &Path) -> Option<&Regex> {
+        self.patterns
+            .iter()
+            .find(|(p, _)| p.matches(path))
+            .map(|(_, pattern)| pattern)
+    }
+
+    /// Add a path where only the type of the actual value is checked instead of
+    /// comparing it with the expected one.
+    pub fn check_type(mut self, path: Path, json_type: JsonType) -> Self {
+        self.types.push((path, json_type));
+        self
+    }
+
+    /// Returns the type the actual value at the given path has to be of, if any.
+    pub(crate) fn type_at(&self, path: &Path) -> Option<JsonType> {
+        self.types
+            .iter()
+            .find(|(p, _)| p.matches(path))
+            .map(|(_, json_type)| *json_type)
+    }
 }
 
-pub(crate) fn diff<'a>(
-    expected: &'a Value,
-    actual: &'a Value,
-    config: Config,
-) -> Vec<Difference<'a>> {
+/// Compares the values and returns every difference found, empty if they match.
+pub fn diff<'a>(expected: &'a Value, actual: &'a Value, config: Config) -> Vec<Difference<'a>> {
     let mut acc = Accumulator::collector();
 
     diff_with(expected, actual, &config, Path::Root, &mut acc);
@@ -119,6 +276,22 @@ pub(crate) fn diff<'a>(
     acc.into_vec()
 }
 
+/// Checks if all the fields of the fragment are present in the value.
+/// The compare mode of the config is always inclusive.
+pub(crate) fn contains(value: &Value, fragment: &Value, config: &Config) -> bool {
+    let mut acc = Accumulator::flag();
+
+    diff_with(
+        value,
+        fragment,
+        &config.clone().compare_mode(CompareMode::Inclusive),
+        Path::Root,
+        &mut acc,
+    );
+
+    !acc.has_diff()
+}
+
 fn diff_with<'a>(
     expected: &'a Value,
     actual: &'a Value,
@@ -146,6 +319,10 @@ struct DiffFolder<'a, 'b> {
 
 macro_rules! accumulate {
     ($self:expr, $path:expr, $expected:expr, $actual:expr) => {
+        accumulate!($self, $path, $expected, $actual, None);
+    };
+
+    ($self:expr, $path:expr, $expected:expr, $actual:expr, $note:expr) => {
         $self.acc.accumulate(
             &$self.config,
             &$path,
@@ -154,6 +331,7 @@ macro_rules! accumulate {
                 actual: $actual,
                 path: $path,
                 compare_mode: $self.config.compare_mode,
+                note: $note,
             },
         );
 
@@ -175,6 +353,7 @@ macro_rules! direct_compare {
                         actual: Some(&self.actual),
                         path: self.path.clone(),
                         compare_mode: self.config.compare_mode,
+                        note: None,
                     },
                 );
 
@@ -189,12 +368,162 @@ macro_rules! direct_compare {
 impl<'a, 'b> DiffFolder<'a, 'b> {
     direct_compare!(on_null);
     direct_compare!(on_bool);
-    direct_compare!(on_string);
+    direct_compare!(on_plain_string);
+
+    fn on_string(&mut self, expected: &'a Value) {
+        let expected_str = expected.as_str().unwrap_or_default();
+        if expected_str == EXISTS_MATCHER {
+            // the field is present, otherwise it would be reported by the parent object
+        } else if let Some(expression) = expected_str.strip_prefix(EXPR_MATCHER) {
+            self.on_expression(expected, expression);
+        } else if let Some(value) = expected_str.strip_prefix(EQI_MATCHER) {
+            self.on_case_insensitive(expected, value);
+        } else if let Some(value) = expected_str.strip_prefix(DURATION_MATCHER) {
+            self.on_quantity(expected, value, Quantity::Duration);
+        } else if let Some(value) = expected_str.strip_prefix(BYTES_MATCHER) {
+            self.on_quantity(expected, value, Quantity::Bytes);
+        } else {
+            self.on_plain_string(expected);
+        }
+    }
+
+    /// Checks the actual string or number against the pattern instead of comparing it
+    /// with the expected value.
+    fn on_pattern(&mut self, expected: &'a Value, pattern: &Regex) {
+        let actual = match self.actual {
+            Value::String(s) => Some(s.clone()),
+            Value::Number(n) => Some(n.to_string()),
+            _ => None,
+        };
+        if actual.is_some_and(|actual| pattern.is_match(&actual)) {
+            return;
+        }
+
+        let note = format!(
+            "value at path \"{}\" does not match the pattern {}",
+            self.path, pattern
+        );
+        accumulate!(
+            self,
+            self.path.clone(),
+            Some(expected),
+            Some(self.actual),
+            Some(note)
+        );
+    }
+
+    /// Checks only the type of the actual value instead of comparing it with the expected value.
+    fn on_type(&mut self, expected: &'a Value, json_type: JsonType) {
+        let actual_type = JsonType::of(self.actual);
+        if actual_type == json_type {
+            return;
+        }
+
+        let note = format!(
+            "expected {} at path \"{}\", got {}",
+            json_type, self.path, actual_type
+        );
+        accumulate!(
+            self,
+            self.path.clone(),
+            Some(expected),
+            Some(self.actual),
+            Some(note)
+        );
+    }
+
+    fn on_case_insensitive(&mut self, expected: &'a Value, value: &str) {
+        let is_equal = self
+            .actual
+            .as_str()
+            .is_some_and(|actual| actual.to_lowercase() == value.to_lowercase());
+
+        if !is_equal {
+            accumulate!(self, self.path.clone(), Some(expected), Some(self.actual));
+        }
+    }
+
+    /// Compares quantities normalized to the base unit. The actual values without
+    /// a unit are assumed to be in the unit of the expected value.
+    fn on_quantity(&mut self, expected: &'a Value, value: &str, quantity: Quantity) {
+        let expected_quantity = match quantity.normalize(value, quantity.base_unit()) {
+            Ok(expected_quantity) => expected_quantity,
+            Err(err) => {
+                let note = format!("error parsing expected quantity: {}", err);
+                accumulate!(
+                    self,
+                    self.path.clone(),
+                    Some(expected),
+                    Some(self.actual),
+                    Some(note)
+                );
+                return;
+            }
+        };
+
+        let (expected_base, expected_unit) = expected_quantity;
+        let actual = match self.actual {
+            Value::Number(n) => Some(n.to_string()),
+            Value::String(s) => Some(s.clone()),
+            _ => None,
+        };
+        let note = match actual.map(|a| quantity.normalize(&a, expected_unit).map(|(v, _)| v)) {
+            Some(Ok(actual_base))
+                if (expected_base - actual_base).abs() <= 1e-9 * expected_base.abs().max(1.0) =>
+            {
+                return
+            }
+            Some(Ok(actual_base)) => format!(
+                "normalized values differ: expected {} {unit}, got {} {unit}",
+                expected_base,
+                actual_base,
+                unit = quantity.base_unit()
+            ),
+            Some(Err(err)) => format!("error parsing actual quantity: {}", err),
+            None => "actual value is not a quantity".to_string(),
+        };
+
+        accumulate!(
+            self,
+            self.path.clone(),
+            Some(expected),
+            Some(self.actual),
+            Some(note)
+        );
+    }
+
+    fn on_expression(&mut self, expected: &'a Value, expression: &str) {
+        let note = match (expr::evaluate(expression), self.actual.as_f64()) {
+            (Ok(computed), Some(actual)) if (computed - actual).abs() < 1e-9 => return,
+            (Ok(computed), _) => format!("expected expression evaluated to {}", computed),
+            (Err(err), _) => format!("error evaluating expected expression: {}", err),
+        };
+
+        accumulate!(
+            self,
+            self.path.clone(),
+            Some(expected),
+            Some(self.actual),
+            Some(note)
+        );
+    }
 
     fn on_number(&mut self, expected: &'a Value) {
         let is_equal = match self.config.numeric_mode {
             NumericMode::Strict => self.actual == expected,
             NumericMode::AssumeFloat => self.actual.as_f64() == expected.as_f64(),
+            NumericMode::Tolerance(tolerance) => match (self.actual.as_f64(), expected.as_f64()) {
+                (Some(actual), Some(expected)) => (actual - expected).abs() <= tolerance,
+                _ => false,
+            },
+            NumericMode::SignificantFigures(figures) => {
+                match (self.actual.as_f64(), expected.as_f64()) {
+                    (Some(actual), Some(expected)) => {
+                        round_significant(actual, figures) == round_significant(expected, figures)
+                    }
+                    _ => false,
+                }
+            }
         };
 
         if !is_equal {
@@ -204,10 +533,35 @@ impl<'a, 'b> DiffFolder<'a, 'b> {
 
     fn on_array(&mut self, expected: &'a Value) {
         if let Some(actual) = self.actual.as_array() {
+            let expected_value = expected;
             let expected = expected.as_array().unwrap();
 
             match self.config.compare_mode {
                 CompareMode::Inclusive => {
+                    // an empty expected array documents the type only
+                    if expected.is_empty() && !self.config.to_check_exact_length(&self.path) {
+                        return;
+                    }
+
+                    // by default the expected array may be longer than the actual one
+                    if actual.len() != expected.len()
+                        && self.config.to_check_exact_length(&self.path)
+                    {
+                        let note = format!(
+                            "expected array of length {}, got {}",
+                            expected.len(),
+                            actual.len()
+                        );
+                        accumulate!(
+                            self,
+                            self.path.clone(),
+                            Some(expected_value),
+                            Some(self.actual),
+                            Some(note)
+                        );
+                        return;
+                    }
+
                     for (idx, actual) in actual.iter().enumerate() {
                         if let Accumulator::Flag(true) = self.acc {
                             return;
@@ -243,7 +597,8 @@ impl<'a, 'b> DiffFolder<'a, 'b> {
                                 accumulate!(self, path.clone(), None, Some(actual));
                             }
                             (Some(expected), None) => {
-                                accumulate!(self, path.clone(), Some(expected), None);
+                                let note = missing_note(expected);
+                                accumulate!(self, path.clone(), Some(expected), None, note);
                             }
                             (None, None) => {
                                 unreachable!("at least one of the maps should have the key")
@@ -305,7 +660,9 @@ impl<'a, 'b> DiffFolder<'a, 'b> {
                 }
             }
 
-            if visited_keys.len() != expected.len() {
+            if visited_keys.len() != expected.len()
+                && self.config.compare_mode == CompareMode::Strict
+            {
                 accumulate!(
                     self,
                     self.path.clone(),
@@ -313,14 +670,101 @@ impl<'a, 'b> DiffFolder<'a, 'b> {
                     Some(self.actual)
                 );
             }
+        } else {
+            accumulate!(
+                self,
+                self.path.clone(),
+                Some(expected_json),
+                Some(self.actual)
+            );
+        }
+    }
+
+    /// Compares the arrays as multisets: each expected element has to be matched by a different
+    /// actual element, in any order, and no actual element may be left over unless extra ones are allowed.
+    fn on_array_set_equal(&mut self, expected_json: &'a Value, allow_extra: bool) {
+        let (Some(expected), Some(actual)) = (expected_json.as_array(), self.actual.as_array())
+        else {
+            accumulate!(
+                self,
+                self.path.clone(),
+                Some(expected_json),
+                Some(self.actual)
+            );
+            return;
+        };
+
+        let mut matched = vec![false; expected.len()];
+        let mut used = vec![false; actual.len()];
+        // equal elements are paired first, so that the expected elements with matchers
+        // don't take the actual elements other expected elements are equal to
+        for exact in [true, false] {
+            for (expected_idx, expected_value) in expected.iter().enumerate() {
+                if matched[expected_idx] {
+                    continue;
+                }
+
+                let found = actual.iter().enumerate().position(|(idx, actual_value)| {
+                    if used[idx] {
+                        return false;
+                    }
+                    if exact {
+                        return actual_value == expected_value;
+                    }
+                    let mut acc = Accumulator::flag();
+                    let path = self.path.append(Key::Idx(idx));
+                    diff_with(expected_value, actual_value, self.config, path, &mut acc);
+                    !acc.has_diff()
+                });
+                if let Some(idx) = found {
+                    used[idx] = true;
+                    matched[expected_idx] = true;
+                }
+            }
+        }
+
+        for (idx, expected_value) in expected.iter().enumerate() {
+            if !matched[idx] {
+                let note = format!("missing element {}", expected_value);
+                accumulate!(
+                    self,
+                    self.path.append(Key::Idx(idx)),
+                    Some(expected_value),
+                    None,
+                    Some(note)
+                );
+            }
+        }
+        for (idx, actual_value) in actual.iter().enumerate() {
+            if !used[idx] && !allow_extra {
+                let note = format!("extra element {}", actual_value);
+                accumulate!(
+                    self,
+                    self.path.append(Key::Idx(idx)),
+                    None,
+                    Some(actual_value),
+                    Some(note)
+                );
+            }
         }
     }
 
     fn on_object(&mut self, expected: &'a Value) {
+        if let Some(fragment) = expected
+            .as_object()
+            .filter(|expected| expected.len() == 1)
+            .and_then(|expected| expected.get(KEYS_MATCHER))
+        {
+            self.on_keys(expected, fragment);
+            return;
+        }
+
         if let Some(actual) = self.actual.as_object() {
             let expected = expected.as_object().unwrap();
 
             match self.config.compare_mode {
+                // an empty expected object documents the type only
+                CompareMode::Inclusive if expected.is_empty() => {}
                 CompareMode::Inclusive => {
                     for (key, actual) in actual.iter() {
                         if let Accumulator::Flag(true) = self.acc {
@@ -353,7 +797,8 @@ impl<'a, 'b> DiffFolder<'a, 'b> {
                                 accumulate!(self, path.clone(), None, Some(actual));
                             }
                             (Some(expected), None) => {
-                                accumulate!(self, path.clone(), Some(expected), None);
+                                let note = missing_note(expected);
+                                accumulate!(self, path.clone(), Some(expected), None, note);
                             }
                             (None, None) => {
                                 unreachable!("at least one of the maps should have the key")
@@ -366,6 +811,46 @@ impl<'a, 'b> DiffFolder<'a, 'b> {
             accumulate!(self, self.path.clone(), Some(expected), Some(self.actual));
         }
     }
+
+    /// Matches the fragment against every value of the actual object regardless of the keys
+    /// and reports the first key whose value doesn't match.
+    fn on_keys(&mut self, expected: &'a Value, fragment: &'a Value) {
+        let Some(actual) = self.actual.as_object() else {
+            accumulate!(self, self.path.clone(), Some(expected), Some(self.actual));
+            return;
+        };
+
+        let fragment_config = Config::new(CompareMode::Inclusive);
+        if let Some((key, value)) = actual
+            .iter()
+            .find(|(_, value)| !contains(value, fragment, &fragment_config))
+        {
+            let note = format!("value of key {} does not match {}", key, fragment);
+            accumulate!(
+                self,
+                self.path.append(Key::Field(key.clone())),
+                Some(fragment),
+                Some(value),
+                Some(note)
+            );
+        }
+    }
+}
+
+/// Explains why a value expected with the `@exists` matcher failed.
+/// Rounds the number to the given number of significant figures.
+fn round_significant(value: f64, figures: u32) -> f64 {
+    if value == 0.0 || !value.is_finite() {
+        return value;
+    }
+
+    let magnitude = value.abs().log10().floor() as i32;
+    let factor = 10f64.powi(figures as i32 - 1 - magnitude);
+    (value * factor).round() / factor
+}
+
+fn missing_note(expected: &Value) -> Option<String> {
+    (expected.as_str() == Some(EXISTS_MATCHER)).then(|| "expected the value to exist".to_string())
 }
 
 #[derive(Debug)]
@@ -423,25 +908,70 @@ impl<'a> Accumulator<'a> {
     }
 }
 
+/// Difference between the expected and the actual value at a path, described by its `Display` implementation.
 #[derive(Debug, PartialEq)]
-pub(crate) struct Difference<'a> {
+pub struct Difference<'a> {
     path: Path,
     expected: Option<&'a Value>,
     actual: Option<&'a Value>,
     compare_mode: CompareMode,
+    note: Option<String>,
 }
 
-impl<'a> fmt::Display for Difference<'a> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let json_to_string = |json: &Value| serde_json::to_string_pretty(json).unwrap();
+/// How a [`Difference`] is rendered by [`Difference::format`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiffFormat {
+    /// Whether the actual values are colored red and the expected ones green with ANSI escape codes
+    pub color: bool,
+    /// Whether the values are printed on the line of their path instead of in indented blocks
+    pub compact: bool,
+}
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const RESET: &str = "\x1b[0m";
+
+impl<'a> Difference<'a> {
+    /// Returns the path where the values differ.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Renders the difference in the given format, the `Display` implementation uses the default one.
+    pub fn format(&self, format: DiffFormat) -> String {
+        let mut output = String::new();
+        self.write(&mut output, format)
+            .expect("writing to a string never fails");
+        output
+    }
+
+    fn write(&self, f: &mut impl fmt::Write, format: DiffFormat) -> fmt::Result {
+        let json_to_string = |json: &Value| match format.compact {
+            true => json.to_string(),
+            false => serde_json::to_string_pretty(json).unwrap().indent(8),
+        };
+        let paint = |text: String, color: &str| match format.color {
+            true => format!("{}{}{}", color, text, RESET),
+            false => text,
+        };
 
         match (&self.compare_mode, &self.expected, &self.actual) {
-            (CompareMode::Inclusive, Some(expected), Some(actual)) => {
+            (_, Some(expected), Some(actual)) if format.compact => {
+                write!(
+                    f,
+                    "json atoms at path \"{}\" are not equal: actual {}, expected {}",
+                    self.path,
+                    paint(json_to_string(actual), RED),
+                    paint(json_to_string(expected), GREEN)
+                )?;
+            }
+            (CompareMode::Inclusive, Some(expected), Some(actual))
+            | (CompareMode::Strict, Some(expected), Some(actual)) => {
                 writeln!(f, "json atoms at path \"{}\" are not equal:", self.path)?;
                 writeln!(f, "    actual:")?;
-                writeln!(f, "{}", json_to_string(actual).indent(8))?;
+                writeln!(f, "{}", paint(json_to_string(actual), RED))?;
                 writeln!(f, "    expected:")?;
-                write!(f, "{}", json_to_string(expected).indent(8))?;
+                write!(f, "{}", paint(json_to_string(expected), GREEN))?;
             }
             (CompareMode::Inclusive, None, Some(_actual)) => {
                 write!(
@@ -455,13 +985,6 @@ impl<'a> fmt::Display for Difference<'a> {
             }
             (CompareMode::Inclusive, None, None) => unreachable!("can't both be missing"),
 
-            (CompareMode::Strict, Some(expected), Some(actual)) => {
-                writeln!(f, "json atoms at path \"{}\" are not equal:", self.path)?;
-                writeln!(f, "    actual:")?;
-                writeln!(f, "{}", json_to_string(actual).indent(8))?;
-                writeln!(f, "    expected:")?;
-                write!(f, "{}", json_to_string(expected).indent(8))?;
-            }
             (CompareMode::Strict, None, Some(_)) => {
                 write!(
                     f,
@@ -479,18 +1002,40 @@ impl<'a> fmt::Display for Difference<'a> {
             (CompareMode::Strict, None, None) => unreachable!("can't both be missing"),
         }
 
+        if let Some(note) = &self.note {
+            write!(f, "\n    {}", note)?;
+        }
+
         Ok(())
     }
 }
 
+impl<'a> fmt::Display for Difference<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write(f, DiffFormat::default())
+    }
+}
+
 fn fold_json<'a>(json: &'a Value, folder: &mut DiffFolder<'a, '_>) {
+    let config = folder.config;
+    if let Some(pattern) = config.pattern_at(&folder.path) {
+        return folder.on_pattern(json, pattern);
+    }
+    if let Some(json_type) = config.type_at(&folder.path) {
+        return folder.on_type(json, json_type);
+    }
+
     match json {
         Value::Null => folder.on_null(json),
         Value::Bool(_) => folder.on_bool(json),
         Value::Number(_) => folder.on_number(json),
         Value::String(_) => folder.on_string(json),
         Value::Array(_) => {
-            if folder.config.to_ignore_order(&folder.path) {
+            if folder.config.to_check_set_equal(&folder.path) {
+                folder.on_array_set_equal(json, false)
+            } else if folder.config.to_check_subset(&folder.path) {
+                folder.on_array_set_equal(json, true)
+            } else if folder.config.to_ignore_order(&folder.path) {
                 folder.on_array_unordered(json)
             } else {
                 folder.on_array(json)
@@ -602,6 +1147,148 @@ mod test {
         assert_eq!(diffs, vec![]);
     }
 
+    #[test]
+    fn test_diffing_tolerance() {
+        let differences = |expected: Value, actual: Value, numeric_mode| {
+            let config = Config::new(CompareMode::Inclusive).numeric_mode(numeric_mode);
+            diff(&expected, &actual, config).len()
+        };
+
+        assert_eq!(
+            differences(json!(1.0), json!(1.0001), NumericMode::AssumeFloat),
+            1
+        );
+        assert_eq!(
+            differences(json!(1.0), json!(1.0001), NumericMode::Tolerance(0.001)),
+            0
+        );
+        assert_eq!(
+            differences(json!(1), json!(0.9999), NumericMode::Tolerance(0.001)),
+            0
+        );
+        assert_eq!(
+            differences(json!(1.0), json!(1.01), NumericMode::Tolerance(0.001)),
+            1
+        );
+    }
+
+    #[test]
+    fn test_diffing_significant_figures() {
+        let differences = |expected: Value, actual: Value, figures| {
+            let config = Config::new(CompareMode::Inclusive)
+                .numeric_mode(NumericMode::SignificantFigures(figures));
+            diff(&expected, &actual, config).len()
+        };
+
+        assert_eq!(differences(json!(0.0012345), json!(0.0012349), 3), 0);
+        assert_eq!(differences(json!(-0.0012345), json!(-0.0012349), 3), 0);
+        assert_eq!(differences(json!(12345), json!(12349.7), 3), 0);
+        assert_eq!(differences(json!(0), json!(0.0), 3), 0);
+        assert_eq!(differences(json!(0.0012345), json!(0.0012351), 3), 1);
+        assert_eq!(differences(json!(0.0012345), json!(0.0012349), 5), 1);
+        assert_eq!(differences(json!(0.0012345), json!("0.00123"), 3), 1);
+    }
+
+    #[test]
+    fn test_diffing_pattern() {
+        let config = |path: &str| {
+            Config::new(CompareMode::Strict).match_pattern(
+                path.jsonpath().unwrap(),
+                Regex::new("^[a-z0-9-]+$").unwrap(),
+            )
+        };
+
+        let expected = json!({"id": 1, "slug": "my-first-blog"});
+        let actual = json!({"id": 1, "slug": "my-first-blog-1712"});
+        assert_eq!(diff(&expected, &actual, config("$.slug")), vec![]);
+
+        let actual = json!({"id": 1, "slug": 1712});
+        assert_eq!(diff(&expected, &actual, config("$.slug")), vec![]);
+
+        let actual = json!({"id": 1, "slug": "My First Blog"});
+        let diffs = diff(&expected, &actual, config("$.slug"));
+        assert_eq!(diffs.len(), 1);
+        assert!(diffs[0]
+            .to_string()
+            .ends_with("value at path \".slug\" does not match the pattern ^[a-z0-9-]+$"));
+
+        // the field still has to be present in the strict mode
+        let actual = json!({"id": 1});
+        let diffs = diff(&expected, &actual, config("$.slug"));
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(
+            diffs[0].to_string(),
+            "json atom at path \".slug\" is missing from actual"
+        );
+
+        let expected = json!({"posts": [{"slug": "a"}, {"slug": "b"}]});
+        let actual = json!({"posts": [{"slug": "first-post"}, {"slug": "Second"}]});
+        let diffs = diff(&expected, &actual, config("$.posts[*].slug"));
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].path().to_string(), ".posts[1].slug");
+    }
+
+    #[test]
+    fn test_difference_format() {
+        let expected = json!({"id": 1, "author": {"name": "John"}});
+        let actual = json!({"id": 1, "author": {"name": "Jane"}});
+        let diffs = diff(&expected, &actual, Config::new(CompareMode::Strict));
+        assert_eq!(diffs.len(), 1);
+
+        assert_eq!(diffs[0].format(DiffFormat::default()), diffs[0].to_string());
+        assert_eq!(
+            diffs[0].format(DiffFormat {
+                color: false,
+                compact: true
+            }),
+            "json atoms at path \".author.name\" are not equal: actual \"Jane\", expected \"John\""
+        );
+        assert_eq!(
+            diffs[0].format(DiffFormat {
+                color: true,
+                compact: true
+            }),
+            "json atoms at path \".author.name\" are not equal: actual \u{1b}[31m\"Jane\"\u{1b}[0m, expected \u{1b}[32m\"John\"\u{1b}[0m"
+        );
+        assert_eq!(
+            diffs[0].format(DiffFormat {
+                color: true,
+                compact: false
+            }),
+            "json atoms at path \".author.name\" are not equal:\n    actual:\n\u{1b}[31m        \"Jane\"\u{1b}[0m\n    expected:\n\u{1b}[32m        \"John\"\u{1b}[0m"
+        );
+    }
+
+    #[test]
+    fn test_diffing_type() {
+        let config = Config::new(CompareMode::Strict)
+            .check_type("$.id".jsonpath().unwrap(), JsonType::Number)
+            .check_type("$.tags[*]".jsonpath().unwrap(), JsonType::String)
+            .check_type("$.author".jsonpath().unwrap(), JsonType::Object);
+
+        let expected = json!({"id": 1, "tags": ["a"], "author": {"name": "John"}});
+        let actual = json!({"id": 42, "tags": ["b", "c"], "author": {"id": 7}});
+        // the arrays still have to be of the same length in the strict mode
+        let diffs = diff(&expected, &actual, config.clone());
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].path().to_string(), ".tags[1]");
+
+        let expected = json!({"id": 1, "tags": ["a", "b"], "author": {}});
+        let actual = json!({"id": "42", "tags": ["b", 3], "author": null});
+        let diffs = diff(&expected, &actual, config)
+            .iter()
+            .map(|d| d.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(diffs.len(), 3);
+        for note in [
+            "expected number at path \".id\", got string",
+            "expected string at path \".tags[1]\", got number",
+            "expected object at path \".author\", got null",
+        ] {
+            assert!(diffs.iter().any(|d| d.ends_with(note)), "{}", note);
+        }
+    }
+
     #[test]
     fn test_diffing_array() {
         // empty
@@ -618,7 +1305,7 @@ mod test {
         let expected = json!([]);
         let actual = json!([1]);
         let diffs = diff(&expected, &actual, Config::new(CompareMode::Inclusive));
-        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs, vec![]);
 
         // eq
         let expected = json!([1]);
@@ -729,6 +1416,72 @@ mod test {
         assert_eq!(diffs, vec![]);
     }
 
+    #[test]
+    fn test_empty_collections() {
+        // strict mode requires the actual collection to be exactly empty
+        for (expected, actual) in [
+            (json!([]), json!([1])),
+            (json!([1]), json!([])),
+            (json!({}), json!({ "a": 1 })),
+            (json!({ "a": 1 }), json!({})),
+            (json!({ "a": [] }), json!({ "a": [1] })),
+            (json!({ "a": {} }), json!({ "a": { "b": 1 } })),
+            (json!([[]]), json!([[1]])),
+            (json!([]), json!({})),
+            (json!({}), json!([])),
+            (json!({ "a": [] }), json!({ "a": null })),
+        ] {
+            let diffs = diff(&expected, &actual, Config::new(CompareMode::Strict));
+            assert_eq!(diffs.len(), 1, "{} vs {}", expected, actual);
+        }
+
+        for json in [json!([]), json!({}), json!({ "a": [] }), json!([{}])] {
+            let diffs = diff(&json, &json, Config::new(CompareMode::Strict));
+            assert_eq!(diffs, vec![]);
+        }
+
+        // in inclusive mode an empty actual collection is contained in anything
+        for (expected, actual) in [
+            (json!([1, 2]), json!([])),
+            (json!({ "a": 1 }), json!({})),
+            (json!({ "a": [1] }), json!({ "a": [] })),
+            (json!({ "a": { "b": 1 } }), json!({ "a": {} })),
+        ] {
+            let diffs = diff(&expected, &actual, Config::new(CompareMode::Inclusive));
+            assert_eq!(diffs, vec![], "{} vs {}", expected, actual);
+        }
+
+        // and an empty expected collection matches anything as well
+        for (expected, actual) in [
+            (json!([]), json!([1])),
+            (json!({}), json!({ "a": 1 })),
+            (json!({ "a": [] }), json!({ "a": [1, 2] })),
+            (json!([{}]), json!([{ "b": 1 }])),
+        ] {
+            let diffs = diff(&expected, &actual, Config::new(CompareMode::Inclusive));
+            assert_eq!(diffs, vec![], "{} vs {}", expected, actual);
+        }
+        let config = Config::new(CompareMode::Inclusive).exact_length(Path::Root);
+        assert_eq!(diff(&json!([]), &json!([1]), config).len(), 1);
+
+        // but it still has to be of the same type
+        for (expected, actual) in [
+            (json!([1]), json!({})),
+            (json!({ "a": 1 }), json!([])),
+            (json!([]), json!({})),
+            (json!({}), json!([])),
+        ] {
+            let diffs = diff(&expected, &actual, Config::new(CompareMode::Inclusive));
+            assert_eq!(diffs.len(), 1, "{} vs {}", expected, actual);
+        }
+
+        // empty fragments match any value of the same type
+        let inclusive = Config::new(CompareMode::Inclusive);
+        assert!(contains(&json!({ "a": 1 }), &json!({}), &inclusive));
+        assert!(contains(&json!([1, 2]), &json!([]), &inclusive));
+        assert!(!contains(&json!([1, 2]), &json!({}), &inclusive));
+    }
+
     #[test]
     fn test_object_deep_path() {
         let expected = json!({ "id": 1, "name": "John" });
@@ -807,6 +1560,439 @@ mod test {
         assert_eq!(diffs.len(), 2);
     }
 
+    #[test]
+    fn test_root_array() {
+        let expected = json!([{ "id": 1 }, { "id": 2 }]);
+        let actual = json!([{ "id": 1 }, { "id": 2 }]);
+        let diffs = diff(&expected, &actual, Config::new(CompareMode::Strict));
+        assert_eq!(diffs.len(), 0);
+
+        // arrays are compared positionally by default
+        let actual = json!([{ "id": 2 }, { "id": 1 }]);
+        let diffs = diff(&expected, &actual, Config::new(CompareMode::Strict));
+        assert_eq!(diffs.len(), 2);
+        let diffs = diff(&expected, &actual, Config::new(CompareMode::Inclusive));
+        assert_eq!(diffs.len(), 2);
+
+        // ignoring order at the root
+        let config = Config::new(CompareMode::Strict).ignore_order(Path::Root);
+        let diffs = diff(&expected, &actual, config);
+        assert_eq!(diffs.len(), 0);
+
+        let actual = json!([{ "id": 2 }]);
+        let config = Config::new(CompareMode::Strict).ignore_order(Path::Root);
+        let diffs = diff(&expected, &actual, config);
+        assert!(!diffs.is_empty());
+
+        // in inclusive mode every actual element has to be expected
+        let config = Config::new(CompareMode::Inclusive).ignore_order(Path::Root);
+        let diffs = diff(&expected, &actual, config);
+        assert_eq!(diffs.len(), 0);
+
+        let actual = json!([{ "id": 3 }, { "id": 1 }]);
+        let config = Config::new(CompareMode::Inclusive).ignore_order(Path::Root);
+        let diffs = diff(&expected, &actual, config);
+        assert_eq!(diffs.len(), 1);
+
+        // actual is not an array
+        let actual = json!({ "id": 1 });
+        let config = Config::new(CompareMode::Strict).ignore_order(Path::Root);
+        let diffs = diff(&expected, &actual, config);
+        assert_eq!(diffs.len(), 1);
+    }
+
+    #[test]
+    fn test_contains() {
+        let value = json!({ "id": 1, "status": "done", "tags": ["a", "b"] });
+        let inclusive = Config::new(CompareMode::Inclusive);
+        assert!(contains(&value, &json!({ "status": "done" }), &inclusive));
+        assert!(contains(
+            &value,
+            &json!({ "status": "done", "id": 1 }),
+            &inclusive
+        ));
+        assert!(contains(&value, &json!({}), &inclusive));
+        assert!(!contains(&value, &json!({ "status": "todo" }), &inclusive));
+        assert!(!contains(&value, &json!({ "owner": "John" }), &inclusive));
+        assert!(!contains(
+            &json!([1]),
+            &json!({ "status": "done" }),
+            &inclusive
+        ));
+    }
+
+    #[test]
+    fn test_ignore_recursive_descent() {
+        let config =
+            Config::new(CompareMode::Strict).ignore_path("$..timestamp".jsonpath().unwrap());
+
+        let expected = json!({
+            "timestamp": 1,
+            "user": { "profile": { "timestamp": 2, "name": "John" } },
+            "events": [{ "timestamp": 3, "type": "login" }]
+        });
+        let actual = json!({
+            "timestamp": 10,
+            "user": { "profile": { "timestamp": 20, "name": "John" } },
+            "events": [{ "timestamp": 30, "type": "logout" }]
+        });
+        let diffs = diff(&expected, &actual, config);
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].path, "$.events[0].type".jsonpath().unwrap());
+    }
+
+    #[test]
+    fn test_ignore_array_indices_strict() {
+        let ignore =
+            |path: &str| Config::new(CompareMode::Strict).ignore_path(path.jsonpath().unwrap());
+
+        // value mismatch at an ignored index
+        let expected = json!({ "rows": ["header", 1, 2] });
+        let actual = json!({ "rows": ["other header", 1, 2] });
+        let diffs = diff(&expected, &actual, ignore("$.rows[0]"));
+        assert_eq!(diffs, vec![]);
+
+        // value mismatch outside of the ignored index
+        let actual = json!({ "rows": ["other header", 1, 3] });
+        let diffs = diff(&expected, &actual, ignore("$.rows[0]"));
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].path, "$.rows[2]".jsonpath().unwrap());
+
+        // element missing from actual at an ignored index
+        let expected = json!({ "rows": [1, 2, "footer"] });
+        let actual = json!({ "rows": [1, 2] });
+        let diffs = diff(&expected, &actual, ignore("$.rows[2]"));
+        assert_eq!(diffs, vec![]);
+
+        // element missing from expected at an ignored index
+        let expected = json!({ "rows": [1, 2] });
+        let actual = json!({ "rows": [1, 2, "footer"] });
+        let diffs = diff(&expected, &actual, ignore("$.rows[2]"));
+        assert_eq!(diffs, vec![]);
+
+        // ranges
+        let expected = json!({ "rows": [0, 1, 2, 3] });
+        let actual = json!({ "rows": [9, 9, 2, 3] });
+        let diffs = diff(&expected, &actual, ignore("$.rows[0:2]"));
+        assert_eq!(diffs, vec![]);
+        let diffs = diff(&expected, &actual, ignore("$.rows[:2]"));
+        assert_eq!(diffs, vec![]);
+        let diffs = diff(&expected, &actual, ignore("$.rows[0:1]"));
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].path, "$.rows[1]".jsonpath().unwrap());
+
+        let actual = json!({ "rows": [0, 1, 9] });
+        let diffs = diff(&expected, &actual, ignore("$.rows[2:]"));
+        assert_eq!(diffs, vec![]);
+        let diffs = diff(&expected, &actual, ignore("$.rows[3:]"));
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].path, "$.rows[2]".jsonpath().unwrap());
+
+        // index and range combined
+        let expected = json!({ "rows": ["header", 1, 2, 3, 4] });
+        let actual = json!({ "rows": ["other header", 1, 2, 9, 9] });
+        let config = ignore("$.rows[0]").ignore_path("$.rows[3:]".jsonpath().unwrap());
+        let diffs = diff(&expected, &actual, config);
+        assert_eq!(diffs, vec![]);
+    }
+
+    #[test]
+    fn test_expression_matcher() {
+        let expected = json!({"total": "@expr:10 * 3", "sum": "@expr:1.5 + 2"});
+        let actual = json!({"total": 30, "sum": 3.5});
+        assert!(diff(&expected, &actual, Config::new(CompareMode::Strict)).is_empty());
+
+        let actual = json!({"total": 31, "sum": 3.5});
+        let diffs = diff(&expected, &actual, Config::new(CompareMode::Strict));
+        assert_eq!(diffs.len(), 1);
+        assert!(diffs[0]
+            .to_string()
+            .ends_with("expected expression evaluated to 30"));
+
+        let expected = json!({"total": "@expr:10 / (3 - 3)"});
+        let actual = json!({"total": 30});
+        let diffs = diff(&expected, &actual, Config::new(CompareMode::Strict));
+        assert_eq!(diffs.len(), 1);
+        assert!(diffs[0]
+            .to_string()
+            .ends_with("error evaluating expected expression: division by zero"));
+
+        let actual = json!({"total": "30"});
+        let expected = json!({"total": "@expr:10 * 3"});
+        assert_eq!(
+            diff(&expected, &actual, Config::new(CompareMode::Strict)).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_case_insensitive_matcher() {
+        let expected = json!({"id": "@eqi:d8f7d454-c436-4e0f-9613-1d69036ad421"});
+        for actual in [
+            json!({"id": "D8F7D454-C436-4E0F-9613-1D69036AD421"}),
+            json!({"id": "d8f7d454-c436-4e0f-9613-1d69036ad421"}),
+        ] {
+            assert!(diff(&expected, &actual, Config::new(CompareMode::Strict)).is_empty());
+        }
+
+        for actual in [
+            json!({"id": "d8f7d454-c436-4e0f-9613-1d69036ad422"}),
+            json!({"id": 1}),
+        ] {
+            assert_eq!(
+                diff(&expected, &actual, Config::new(CompareMode::Strict)).len(),
+                1
+            );
+        }
+    }
+
+    #[test]
+    fn test_quantity_matcher() {
+        let expected = json!({"timeout": "@duration:1500ms", "size": "@bytes:1MiB"});
+        for actual in [
+            json!({"timeout": "1.5s", "size": "1024KiB"}),
+            json!({"timeout": 1500, "size": 1}),
+            json!({"timeout": "1500", "size": "1 MiB"}),
+            json!({"timeout": "0.025min", "size": "1048.576kB"}),
+        ] {
+            assert!(diff(&expected, &actual, Config::new(CompareMode::Strict)).is_empty());
+        }
+
+        let actual = json!({"timeout": "1.4s", "size": "1MB"});
+        let diffs = diff(&expected, &actual, Config::new(CompareMode::Strict));
+        assert_eq!(diffs.len(), 2);
+        let notes = diffs
+            .iter()
+            .filter_map(|d| d.note.clone())
+            .collect::<Vec<_>>();
+        assert!(
+            notes.contains(&"normalized values differ: expected 1500 ms, got 1400 ms".to_string())
+        );
+        assert!(notes
+            .contains(&"normalized values differ: expected 1048576 B, got 1000000 B".to_string()));
+
+        // unit-less actual values are in the unit of the expected value
+        let expected = json!({"timeout": "@duration:1.5s"});
+        assert!(diff(
+            &expected,
+            &json!({"timeout": 1.5}),
+            Config::new(CompareMode::Strict)
+        )
+        .is_empty());
+
+        for actual in [
+            json!({"timeout": "1.5 parsecs"}),
+            json!({"timeout": true}),
+            json!({"timeout": "1.5MiB"}),
+        ] {
+            assert_eq!(
+                diff(&expected, &actual, Config::new(CompareMode::Strict)).len(),
+                1
+            );
+        }
+    }
+
+    #[test]
+    fn test_exact_length() {
+        let value = json!({"id": 1, "tags": ["a", "b"], "nested": {"ids": [1, 2]}});
+        let fragment = json!({"tags": ["a"], "nested": {"ids": [1]}});
+        assert!(contains(
+            &value,
+            &fragment,
+            &Config::new(CompareMode::Inclusive)
+        ));
+
+        let config = Config::new(CompareMode::Inclusive).exact_length("$.tags".jsonpath().unwrap());
+        assert!(!contains(&value, &fragment, &config));
+        assert!(contains(
+            &value,
+            &json!({"tags": ["a", "b"], "nested": {"ids": [1]}}),
+            &config
+        ));
+
+        let config = Config::new(CompareMode::Inclusive).exact_length(Path::Root);
+        assert!(!contains(
+            &value,
+            &json!({"tags": ["a", "b"], "nested": {"ids": [1]}}),
+            &config
+        ));
+        assert!(contains(
+            &value,
+            &json!({"tags": ["a", "b"], "nested": {"ids": [1, 2]}}),
+            &config
+        ));
+
+        let (expected, actual) = (json!([1, 2, 3]), json!([1, 2]));
+        let diffs = diff(
+            &expected,
+            &actual,
+            Config::new(CompareMode::Inclusive).exact_length(Path::Root),
+        );
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(
+            diffs[0].note,
+            Some("expected array of length 3, got 2".to_string())
+        );
+    }
+
+    #[test]
+    fn test_exists_matcher() {
+        let expected = json!({"id": "@exists", "tags": ["@exists"]});
+        for actual in [
+            json!({"id": null, "tags": [null]}),
+            json!({"id": 1, "tags": ["a"]}),
+            json!({"id": {"nested": true}, "tags": [[]]}),
+        ] {
+            assert!(diff(&expected, &actual, Config::new(CompareMode::Strict)).is_empty());
+        }
+
+        let actual = json!({"tags": []});
+        let diffs = diff(&expected, &actual, Config::new(CompareMode::Strict));
+        assert_eq!(diffs.len(), 2);
+        let missing = diffs.iter().find(|d| d.path.to_string() == ".id").unwrap();
+        assert_eq!(
+            missing.note,
+            Some("expected the value to exist".to_string())
+        );
+    }
+
+    #[test]
+    fn test_keys_matcher() {
+        let expected = json!({"jobs": {"@keys": {"status": "ok"}}});
+        for actual in [
+            json!({"jobs": {
+                "1b7c4c1e-5e0f-4a8e-9a4a-1f7a6c2a3d10": {"status": "ok", "attempts": 1},
+                "9f2d5a4b-3c6e-4d1f-8b7a-2e9c0d1f4a55": {"status": "ok", "attempts": 3}
+            }}),
+            json!({"jobs": {}}),
+        ] {
+            assert!(diff(&expected, &actual, Config::new(CompareMode::Strict)).is_empty());
+        }
+
+        let actual = json!({"jobs": {
+            "1b7c4c1e-5e0f-4a8e-9a4a-1f7a6c2a3d10": {"status": "ok"},
+            "9f2d5a4b-3c6e-4d1f-8b7a-2e9c0d1f4a55": {"status": "failed"}
+        }});
+        let diffs = diff(&expected, &actual, Config::new(CompareMode::Strict));
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(
+            diffs[0].path.to_string(),
+            ".jobs.9f2d5a4b-3c6e-4d1f-8b7a-2e9c0d1f4a55"
+        );
+        assert_eq!(
+            diffs[0].note,
+            Some(
+                "value of key 9f2d5a4b-3c6e-4d1f-8b7a-2e9c0d1f4a55 does not match {\"status\":\"ok\"}"
+                    .to_string()
+            )
+        );
+
+        let actual = json!({"jobs": [{"status": "ok"}]});
+        assert_eq!(
+            diff(&expected, &actual, Config::new(CompareMode::Strict)).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_set_equal() {
+        let config = Config::new(CompareMode::Strict).set_equal("$.tags".jsonpath().unwrap());
+        let notes = |expected: &Value, actual: &Value| {
+            diff(expected, actual, config.clone())
+                .into_iter()
+                .map(|d| format!("{} {}", d.path, d.note.unwrap_or_default()))
+                .collect::<Vec<_>>()
+        };
+
+        let expected = json!({"tags": ["a", "b", "a"]});
+        for actual in [
+            json!({"tags": ["a", "b", "a"]}),
+            json!({"tags": ["b", "a", "a"]}),
+            json!({"tags": ["a", "a", "b"]}),
+        ] {
+            assert_eq!(notes(&expected, &actual), Vec::<String>::new());
+        }
+
+        // the multiplicities have to be equal too
+        assert_eq!(
+            notes(&expected, &json!({"tags": ["a", "b", "b"]})),
+            vec![
+                ".tags[2] missing element \"a\"",
+                ".tags[2] extra element \"b\""
+            ]
+        );
+        assert_eq!(
+            notes(&expected, &json!({"tags": ["a", "b"]})),
+            vec![".tags[2] missing element \"a\""]
+        );
+        assert_eq!(
+            notes(&expected, &json!({"tags": ["b", "a", "a", "a"]})),
+            vec![".tags[3] extra element \"a\""]
+        );
+        assert_eq!(
+            notes(&json!({"tags": []}), &json!({"tags": ["a"]})),
+            vec![".tags[0] extra element \"a\""]
+        );
+        assert_eq!(
+            notes(&expected, &json!({"tags": "a"})).len(),
+            1,
+            "a value which is not an array differs"
+        );
+
+        // elements are compared like any other values, matchers included
+        let expected = json!({"tags": [{"id": 1, "name": "a"}, {"id": "@exists", "name": "b"}]});
+        let actual = json!({"tags": [{"id": 2, "name": "b"}, {"id": 1, "name": "a"}]});
+        assert_eq!(notes(&expected, &actual), Vec::<String>::new());
+
+        // equal elements are paired before the ones with matchers
+        let expected = json!({"tags": ["@exists", "a"]});
+        assert_eq!(
+            notes(&expected, &json!({"tags": ["a", "b"]})),
+            Vec::<String>::new()
+        );
+
+        // arrays at other paths keep the order
+        let expected = json!({"tags": ["a", "b"], "ids": [1, 2]});
+        let actual = json!({"tags": ["b", "a"], "ids": [2, 1]});
+        assert_eq!(notes(&expected, &actual).len(), 2);
+    }
+
+    #[test]
+    fn test_subset() {
+        let config = Config::new(CompareMode::Strict).subset("$.items".jsonpath().unwrap());
+        let notes = |expected: &Value, actual: &Value| {
+            diff(expected, actual, config.clone())
+                .into_iter()
+                .map(|d| format!("{} {}", d.path, d.note.unwrap_or_default()))
+                .collect::<Vec<_>>()
+        };
+
+        let expected = json!({"items": [{"id": 2}, {"id": 1}]});
+        for actual in [
+            json!({"items": [{"id": 1}, {"id": 2}]}),
+            json!({"items": [{"id": 3}, {"id": 2}, {"id": 4}, {"id": 1}]}),
+        ] {
+            assert_eq!(notes(&expected, &actual), Vec::<String>::new());
+        }
+
+        // each expected element needs its own actual element
+        assert_eq!(
+            notes(
+                &json!({"items": [{"id": 1}, {"id": 1}]}),
+                &json!({"items": [{"id": 1}, {"id": 2}]})
+            ),
+            vec![".items[1] missing element {\"id\":1}"]
+        );
+        assert_eq!(
+            notes(&expected, &json!({"items": [{"id": 2}]})),
+            vec![".items[1] missing element {\"id\":1}"]
+        );
+        assert_eq!(
+            notes(&json!({"items": []}), &json!({"items": [{"id": 1}]})),
+            Vec::<String>::new()
+        );
+    }
+
     #[test]
     fn test_complex_jsons() {
         let expected_path = "tests/data/expected.json";
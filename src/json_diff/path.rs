@@ -14,6 +14,7 @@
 use regex::Regex;
 use std::fmt;
 
+/// Location of a value in a JSON document, displayed without the leading `$`.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Path {
     Root,
@@ -29,10 +30,13 @@ pub enum Key {
     Wildcard,
     WildcardArray,
     Field(String),
+    /// Field with the name at any depth, written as `..name`
+    RecursiveDescent(String),
 }
 
-pub(crate) const JSON_PATH_REGEX: &str = r"\$\.?(([a-zA-Z_][a-zA-Z0-9_]*)*(\[\d+\]|\[\d*:\d*\]|(\[\*\]))?)(\.((([a-zA-Z_][a-zA-Z0-9_]*)(\[\d+\]|\[\d*:\d*\]|(\[\*\]))?)|\*))*";
-const JSON_PATH_REGEX_FULL: &str = r"^\$\.?(([a-zA-Z_][a-zA-Z0-9_]*)*(\[\d+\]|\[\d*:\d*\]|(\[\*\]))?)(\.((([a-zA-Z_][a-zA-Z0-9_]*)(\[\d+\]|\[\d*:\d*\]|(\[\*\]))?)|\*))*$";
+pub(crate) const JSON_PATH_REGEX: &str = r"\$\.?(([a-zA-Z_][a-zA-Z0-9_]*)*(\[\d+\]|\[\d*:\d*\]|(\[\*\]))?)(\.\.?((([a-zA-Z_][a-zA-Z0-9_]*)(\[\d+\]|\[\d*:\d*\]|(\[\*\]))?)|\*))*";
+pub(crate) const JSON_POINTER_REGEX: &str = r"(/([^/~()\s]|~[01])*)+";
+const JSON_PATH_REGEX_FULL: &str = r"^\$\.?(([a-zA-Z_][a-zA-Z0-9_]*)*(\[\d+\]|\[\d*:\d*\]|(\[\*\]))?)(\.\.?((([a-zA-Z_][a-zA-Z0-9_]*)(\[\d+\]|\[\d*:\d*\]|(\[\*\]))?)|\*))*$";
 
 // We cannot implement FromStr for Path because it would confict with timelines
 // https://stackoverflow.com/questions/28931515/how-do-i-implement-fromstr-with-a-concrete-lifetime
@@ -56,6 +60,7 @@ impl fmt::Display for Key {
             Key::IdxRangeEnd(end) => write!(f, "[:{}]", end),
             Key::Wildcard => write!(f, "*"),
             Key::WildcardArray => write!(f, "[*]"),
+            Key::RecursiveDescent(key) => write!(f, "..{}", key),
         }
     }
 }
@@ -77,30 +82,47 @@ impl Path {
             (Path::Root, Path::Root) => true,
             (Path::Root, Path::Keys(_)) => true,
             (Path::Keys(_), Path::Root) => false,
-            (Path::Keys(expected), Path::Keys(actual)) => {
-                if expected.len() > actual.len() {
-                    return false;
-                }
+            (Path::Keys(expected), Path::Keys(actual)) => Self::match_keys(expected, actual, false),
+        }
+    }
 
-                expected
-                    .iter()
-                    .zip(actual.iter())
-                    .all(|(expected, actual)| {
-                        if expected == actual {
-                            return true;
-                        }
-
-                        match (expected, actual) {
-                            (Key::Wildcard, Key::Field(_)) => true,
-                            (Key::WildcardArray, Key::Idx(_)) => true,
-                            (Key::IdxRange(a, b), Key::Idx(c)) => a <= c && c < b,
-                            (Key::IdxRangeStart(a), Key::Idx(b)) => a <= b,
-                            (Key::IdxRangeEnd(a), Key::Idx(b)) => b < a,
-                            _ => false,
-                        }
-                    })
-            }
+    /// Checks if the path, which may contain wildcards and ranges, points exactly at the other one.
+    pub(crate) fn matches(&self, other: &Path) -> bool {
+        match (self, other) {
+            (Path::Root, Path::Root) => true,
+            (Path::Root, Path::Keys(_)) | (Path::Keys(_), Path::Root) => false,
+            (Path::Keys(expected), Path::Keys(actual)) => Self::match_keys(expected, actual, true),
+        }
+    }
+
+    /// Matches the keys one by one, a recursive descent matching its field at any depth.
+    /// Unless exact, the expected keys only have to match the beginning of the actual ones.
+    fn match_keys(expected: &[Key], actual: &[Key], exact: bool) -> bool {
+        let Some((key, rest)) = expected.split_first() else {
+            return !exact || actual.is_empty();
+        };
+
+        if let Key::RecursiveDescent(field) = key {
+            return (0..actual.len()).any(|depth| {
+                actual[depth] == Key::Field(field.clone())
+                    && Self::match_keys(rest, &actual[depth + 1..], exact)
+            });
         }
+
+        let Some((actual_key, actual_rest)) = actual.split_first() else {
+            return false;
+        };
+        let matched = key == actual_key
+            || match (key, actual_key) {
+                (Key::Wildcard, Key::Field(_)) => true,
+                (Key::WildcardArray, Key::Idx(_)) => true,
+                (Key::IdxRange(a, b), Key::Idx(c)) => a <= c && c < b,
+                (Key::IdxRangeStart(a), Key::Idx(b)) => a <= b,
+                (Key::IdxRangeEnd(a), Key::Idx(b)) => b < a,
+                _ => false,
+            };
+
+        matched && Self::match_keys(rest, actual_rest, exact)
     }
 
     pub(crate) fn from_jsonpath(jsonpath: &str) -> Result<Self, Box<dyn std::error::Error>> {
@@ -115,13 +137,59 @@ impl Path {
         }
 
         let mut keys = Vec::new();
+        let mut recursive = false;
 
-        for segment in jsonpath
-            .trim_matches('$')
-            .split(|c| c == '.' || c == '[')
-            .skip(1)
-        {
-            keys.push(Self::parse_token(segment)?);
+        for segment in jsonpath.trim_matches('$').split(['.', '[']).skip(1) {
+            // the empty segment between the dots of `..` makes the next field recursive
+            if segment.is_empty() && !recursive {
+                recursive = true;
+                continue;
+            }
+            match (recursive, Self::parse_token(segment)?) {
+                (true, Key::Field(field)) if !field.is_empty() => {
+                    keys.push(Key::RecursiveDescent(field))
+                }
+                (true, _) => return Err("recursive descent is supported for fields only".into()),
+                (false, key) => keys.push(key),
+            }
+            recursive = false;
+        }
+
+        Ok(Path::Keys(keys))
+    }
+
+    /// Parses a JSONPath or, if it starts with `/`, a JSON Pointer.
+    pub fn parse(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        if path.starts_with('/') {
+            Self::from_json_pointer(path)
+        } else {
+            Self::from_jsonpath(path)
+        }
+    }
+
+    /// Converts an RFC 6901 JSON Pointer like `/data/0/id`. Numeric tokens refer to array elements.
+    pub(crate) fn from_json_pointer(pointer: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        if pointer.is_empty() {
+            return Ok(Path::Root);
+        }
+        let tokens = pointer.strip_prefix('/').ok_or("invalid JSON Pointer")?;
+
+        let mut keys = Vec::new();
+        for token in tokens.split('/') {
+            let mut escapes = token.split('~').skip(1);
+            if escapes.any(|rest| !rest.starts_with(['0', '1'])) {
+                return Err(format!("invalid escape in JSON Pointer token {}", token).into());
+            }
+            let token = token.replace("~1", "/").replace("~0", "~");
+
+            let is_index = !token.is_empty()
+                && token.bytes().all(|b| b.is_ascii_digit())
+                && (token == "0" || !token.starts_with('0'));
+            if is_index {
+                keys.push(Key::Idx(token.parse()?));
+            } else {
+                keys.push(Key::Field(token));
+            }
         }
 
         Ok(Path::Keys(keys))
@@ -305,6 +373,73 @@ mod test {
         assert!(path1.prefixes(&path2));
     }
 
+    #[test]
+    fn test_recursive_descent() {
+        assert_eq!(
+            "$..id".jsonpath().unwrap(),
+            Path::Keys(vec![Key::RecursiveDescent("id".into())])
+        );
+        assert_eq!(
+            "$.data..id[0]".jsonpath().unwrap(),
+            Path::Keys(vec![
+                Key::Field("data".into()),
+                Key::RecursiveDescent("id".into()),
+                Key::Idx(0),
+            ])
+        );
+        assert_eq!("$.data..id".jsonpath().unwrap().to_string(), ".data..id");
+        assert!("$..*".jsonpath().is_err());
+        assert!("$...id".jsonpath().is_err());
+        assert!("$.a..".jsonpath().is_err());
+
+        let path = "$..id".jsonpath().unwrap();
+        assert!(path.prefixes(&"$.id".jsonpath().unwrap()));
+        assert!(path.prefixes(&"$.user.profile.id".jsonpath().unwrap()));
+        assert!(path.prefixes(&"$.users[1].id.value".jsonpath().unwrap()));
+        assert!(!path.prefixes(&"$.user.uid".jsonpath().unwrap()));
+        assert!(!path.prefixes(&Path::Root));
+        assert!(path.matches(&"$.user.profile.id".jsonpath().unwrap()));
+        assert!(!path.matches(&"$.users[1].id.value".jsonpath().unwrap()));
+
+        let path = "$.data..meta.created".jsonpath().unwrap();
+        assert!(path.prefixes(&"$.data.items[0].meta.created".jsonpath().unwrap()));
+        assert!(!path.prefixes(&"$.meta.created".jsonpath().unwrap()));
+        assert!(!path.prefixes(&"$.data.meta.updated".jsonpath().unwrap()));
+    }
+
+    #[test]
+    fn test_parse_json_pointer() {
+        assert_eq!(Path::from_json_pointer("").unwrap(), Path::Root);
+        assert_eq!(
+            Path::from_json_pointer("/data/0/id").unwrap(),
+            Path::Keys(vec![
+                Key::Field("data".into()),
+                Key::Idx(0),
+                Key::Field("id".into()),
+            ])
+        );
+        assert_eq!(
+            Path::from_json_pointer("/a~1b/m~0n/01/").unwrap(),
+            Path::Keys(vec![
+                Key::Field("a/b".into()),
+                Key::Field("m~n".into()),
+                Key::Field("01".into()),
+                Key::Field("".into()),
+            ])
+        );
+        assert!(Path::from_json_pointer("data/0").is_err());
+        assert!(Path::from_json_pointer("/a~2b").is_err());
+
+        assert_eq!(
+            Path::parse("/data/0").unwrap(),
+            "$.data[0]".jsonpath().unwrap()
+        );
+        assert_eq!(
+            Path::parse("$.data[0]").unwrap(),
+            "$.data[0]".jsonpath().unwrap()
+        );
+    }
+
     #[test]
     fn test_prefixes_validation() {
         let path1 = "$.a.b.c".jsonpath();
@@ -0,0 +1,117 @@
+// Copyright 2024 The DocAssert Authors
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+/// Kind of a quantity compared regardless of the unit it is expressed in.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) enum Quantity {
+    /// Normalized to milliseconds
+    Duration,
+    /// Normalized to bytes
+    Bytes,
+}
+
+impl Quantity {
+    pub fn base_unit(&self) -> &'static str {
+        match self {
+            Quantity::Duration => "ms",
+            Quantity::Bytes => "B",
+        }
+    }
+
+    fn multiplier(&self, unit: &str) -> Option<f64> {
+        let multiplier = match (self, unit) {
+            (Quantity::Duration, "ns") => 1e-6,
+            (Quantity::Duration, "us" | "µs") => 1e-3,
+            (Quantity::Duration, "ms") => 1.0,
+            (Quantity::Duration, "s") => 1e3,
+            (Quantity::Duration, "m" | "min") => 60e3,
+            (Quantity::Duration, "h") => 3600e3,
+            (Quantity::Duration, "d") => 86400e3,
+            (Quantity::Bytes, "B") => 1.0,
+            (Quantity::Bytes, "KB" | "kB") => 1e3,
+            (Quantity::Bytes, "MB") => 1e6,
+            (Quantity::Bytes, "GB") => 1e9,
+            (Quantity::Bytes, "TB") => 1e12,
+            (Quantity::Bytes, "KiB") => 1024.0,
+            (Quantity::Bytes, "MiB") => 1024.0 * 1024.0,
+            (Quantity::Bytes, "GiB") => 1024.0 * 1024.0 * 1024.0,
+            (Quantity::Bytes, "TiB") => 1024.0 * 1024.0 * 1024.0 * 1024.0,
+            _ => return None,
+        };
+
+        Some(multiplier)
+    }
+
+    /// Parses a value like `1.5s` and returns it in the base unit together with its unit.
+    ///
+    /// Values without a unit are expressed in the `default_unit`.
+    pub fn normalize<'a>(
+        &self,
+        value: &'a str,
+        default_unit: &'a str,
+    ) -> Result<(f64, &'a str), String> {
+        let value = value.trim();
+        let split = value
+            .find(|c: char| !c.is_ascii_digit() && c != '.' && c != '-')
+            .unwrap_or(value.len());
+        let (number, unit) = value.split_at(split);
+        let unit = match unit.trim() {
+            "" => default_unit,
+            unit => unit,
+        };
+
+        let number = number
+            .parse::<f64>()
+            .map_err(|_| format!("invalid quantity {}", value))?;
+        let multiplier = self
+            .multiplier(unit)
+            .ok_or_else(|| format!("unknown unit {} in {}", unit, value))?;
+
+        Ok((number * multiplier, unit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(
+            Quantity::Duration.normalize("1500ms", "ms"),
+            Ok((1500.0, "ms"))
+        );
+        assert_eq!(
+            Quantity::Duration.normalize("1.5s", "ms"),
+            Ok((1500.0, "s"))
+        );
+        assert_eq!(
+            Quantity::Duration.normalize("2 min", "ms"),
+            Ok((120000.0, "min"))
+        );
+        assert_eq!(Quantity::Duration.normalize("1.5", "s"), Ok((1500.0, "s")));
+        assert_eq!(
+            Quantity::Bytes.normalize("1MiB", "B"),
+            Ok((1048576.0, "MiB"))
+        );
+        assert_eq!(
+            Quantity::Bytes.normalize("1024KiB", "B"),
+            Ok((1048576.0, "KiB"))
+        );
+        assert_eq!(Quantity::Bytes.normalize("1.5 kB", "B"), Ok((1500.0, "kB")));
+
+        assert!(Quantity::Duration.normalize("1.5 parsecs", "ms").is_err());
+        assert!(Quantity::Bytes.normalize("1s", "B").is_err());
+        assert!(Quantity::Bytes.normalize("MiB", "B").is_err());
+    }
+}
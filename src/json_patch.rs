@@ -0,0 +1,207 @@
+// Copyright 2024 The DocAssert Authors
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde_json::Value;
+
+/// Applies the JSON Patch (RFC 6902) operations to the document.
+///
+/// The operations are applied in order and the first failing one aborts the patch.
+pub(crate) fn apply(document: &Value, patch: &Value) -> Result<Value, String> {
+    let operations = patch
+        .as_array()
+        .ok_or_else(|| format!("expected an array of JSON Patch operations, got {}", patch))?;
+
+    let mut document = document.clone();
+    for (idx, operation) in operations.iter().enumerate() {
+        apply_operation(&mut document, operation)
+            .map_err(|err| format!("error applying JSON Patch operation {}: {}", idx, err))?;
+    }
+
+    Ok(document)
+}
+
+fn apply_operation(document: &mut Value, operation: &Value) -> Result<(), String> {
+    let field = |name: &str| {
+        operation
+            .get(name)
+            .ok_or_else(|| format!("missing field {} in {}", name, operation))
+    };
+    let pointer = |name: &str| {
+        field(name)?
+            .as_str()
+            .ok_or_else(|| format!("field {} of {} is not a string", name, operation))
+    };
+
+    match operation.get("op").and_then(Value::as_str) {
+        Some("add") => add(document, pointer("path")?, field("value")?.clone()),
+        Some("remove") => remove(document, pointer("path")?).map(|_| ()),
+        Some("replace") => {
+            let value = field("value")?.clone();
+            *get_mut(document, pointer("path")?)? = value;
+            Ok(())
+        }
+        Some("move") => {
+            let (from, path) = (pointer("from")?, pointer("path")?);
+            if path.starts_with(&format!("{}/", from)) {
+                return Err(format!("cannot move {} into its own child {}", from, path));
+            }
+            let value = remove(document, from)?;
+            add(document, path, value)
+        }
+        Some("copy") => {
+            let value = get_mut(document, pointer("from")?)?.clone();
+            add(document, pointer("path")?, value)
+        }
+        Some("test") => {
+            let (path, expected) = (pointer("path")?, field("value")?);
+            let actual = get_mut(document, path)?;
+            if actual != expected {
+                return Err(format!("expected {} at {}, got {}", expected, path, actual));
+            }
+            Ok(())
+        }
+        Some(op) => Err(format!("{} is not a valid JSON Patch operation", op)),
+        None => Err(format!("missing field op in {}", operation)),
+    }
+}
+
+/// Splits the pointer into the pointer of the parent and the unescaped last token.
+fn split_last(pointer: &str) -> Result<(&str, String), String> {
+    let (parent, last) = pointer
+        .rsplit_once('/')
+        .ok_or_else(|| format!("invalid JSON Pointer {}", pointer))?;
+    Ok((parent, unescape(last)))
+}
+
+fn unescape(token: &str) -> String {
+    token.replace("~1", "/").replace("~0", "~")
+}
+
+fn get_mut<'v>(document: &'v mut Value, pointer: &str) -> Result<&'v mut Value, String> {
+    if !pointer.is_empty() && !pointer.starts_with('/') {
+        return Err(format!("invalid JSON Pointer {}", pointer));
+    }
+
+    document
+        .pointer_mut(pointer)
+        .ok_or_else(|| format!("no value at {}", pointer))
+}
+
+fn add(document: &mut Value, pointer: &str, value: Value) -> Result<(), String> {
+    if pointer.is_empty() {
+        *document = value;
+        return Ok(());
+    }
+
+    let (parent, token) = split_last(pointer)?;
+    match get_mut(document, parent)? {
+        Value::Object(object) => {
+            object.insert(token, value);
+        }
+        Value::Array(array) if token == "-" => array.push(value),
+        Value::Array(array) => {
+            let idx = index(&token, array.len() + 1, pointer)?;
+            array.insert(idx, value);
+        }
+        other => return Err(format!("cannot add to {} at {}", other, parent)),
+    }
+
+    Ok(())
+}
+
+fn remove(document: &mut Value, pointer: &str) -> Result<Value, String> {
+    let (parent, token) = split_last(pointer)?;
+    match get_mut(document, parent)? {
+        Value::Object(object) => object
+            .remove(&token)
+            .ok_or_else(|| format!("no value at {}", pointer)),
+        Value::Array(array) => {
+            let idx = index(&token, array.len(), pointer)?;
+            Ok(array.remove(idx))
+        }
+        other => Err(format!("cannot remove from {} at {}", other, parent)),
+    }
+}
+
+/// Parses the array index which has to be lower than the bound.
+fn index(token: &str, bound: usize, pointer: &str) -> Result<usize, String> {
+    token
+        .parse::<usize>()
+        .ok()
+        .filter(|idx| *idx < bound && (token == "0" || !token.starts_with('0')))
+        .ok_or_else(|| format!("invalid array index {} in {}", token, pointer))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_apply() {
+        let document = json!({"title": "Draft", "tags": ["a", "b"], "meta": {"views": 1}});
+        let patch = json!([
+            {"op": "replace", "path": "/title", "value": "Published"},
+            {"op": "add", "path": "/tags/-", "value": "c"},
+            {"op": "add", "path": "/tags/0", "value": "z"},
+            {"op": "remove", "path": "/tags/1"},
+            {"op": "copy", "from": "/meta/views", "path": "/views"},
+            {"op": "move", "from": "/meta", "path": "/stats"},
+            {"op": "test", "path": "/stats/views", "value": 1}
+        ]);
+        assert_eq!(
+            apply(&document, &patch),
+            Ok(
+                json!({"title": "Published", "tags": ["z", "b", "c"], "views": 1, "stats": {"views": 1}})
+            )
+        );
+    }
+
+    #[test]
+    fn test_apply_errors() {
+        let document = json!({"title": "Draft", "tags": ["a"]});
+        assert_eq!(
+            apply(&document, &json!({"op": "add"})),
+            Err("expected an array of JSON Patch operations, got {\"op\":\"add\"}".to_string())
+        );
+        assert_eq!(
+            apply(
+                &document,
+                &json!([{"op": "replace", "path": "/title", "value": "x"}, {"op": "remove", "path": "/body"}])
+            ),
+            Err("error applying JSON Patch operation 1: no value at /body".to_string())
+        );
+        assert_eq!(
+            apply(
+                &document,
+                &json!([{"op": "add", "path": "/tags/5", "value": "b"}])
+            ),
+            Err(
+                "error applying JSON Patch operation 0: invalid array index 5 in /tags/5"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            apply(&document, &json!([{"op": "test", "path": "/title", "value": "Final"}])),
+            Err("error applying JSON Patch operation 0: expected \"Final\" at /title, got \"Draft\"".to_string())
+        );
+        assert_eq!(
+            apply(&document, &json!([{"op": "merge", "path": "/title"}])),
+            Err(
+                "error applying JSON Patch operation 0: merge is not a valid JSON Patch operation"
+                    .to_string()
+            )
+        );
+    }
+}
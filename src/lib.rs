@@ -15,19 +15,42 @@
 #![allow(clippy::while_let_on_iterator)]
 
 use crate::{
-    domain::{Request, Response},
-    json_diff::path::{Key, Path},
+    domain::{Capture, MultipartField, PathAssertion, Request, Response, TestCase},
+    executor::{HttpOptions, Target},
+    json_diff::{
+        path::{Key, Path},
+        DiffFormat,
+    },
 };
+use base64::Engine;
+use futures_util::StreamExt;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use reqwest::header::AUTHORIZATION;
+use serde::Serialize;
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Display;
+use std::future::Future;
+use std::io::IsTerminal;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use std::vec;
 
+mod compression;
 mod domain;
 mod executor;
-mod json_diff;
+#[cfg(feature = "grpc-web")]
+mod grpc_web;
+pub mod json_diff;
+mod json_patch;
+#[cfg(feature = "openapi")]
+mod openapi;
 mod parser;
 
+pub use parser::{parse_with_spans, BlockKind};
+
 /// Builder for the assertions.
 ///
 /// The builder is used to configure the assertions.
@@ -54,9 +77,158 @@ mod parser;
 pub struct DocAssert<'a> {
     url: Option<&'a str>,
     doc_paths: Vec<&'a str>,
+    changed_paths: Option<Vec<&'a str>>,
     pub(crate) variables: Variables,
+    check_determinism: bool,
+    no_unexpected_errors: bool,
+    continue_on_capture_failure: bool,
+    report_stale_examples: bool,
+    textual_markers: bool,
+    color: ColorChoice,
+    compact_diff: bool,
+    strict_parsing: bool,
+    request_delay: Option<Duration>,
+    environment: Option<&'a str>,
+    environment_variables: HashMap<&'a str, Variables>,
+    response_dump_dir: Option<&'a str>,
+    service: Option<Service>,
+    functions: Functions,
+    user_agent: Option<&'a str>,
+    base_path: Option<&'a str>,
+    timeout: Option<Duration>,
+    redirect_policy: RedirectPolicy,
+    client_identity: Option<(&'a str, &'a str)>,
+    root_certificate: Option<&'a str>,
+    accept_invalid_certs: bool,
+    proxy: Option<&'a str>,
+    concurrency: Option<usize>,
+    authorization: Option<String>,
+    correlation_header: Option<&'a str>,
+    random_seed: Option<u64>,
+    base_response: Option<Value>,
+    path_assertions: Vec<PathAssertion>,
+    observer: Option<Observer>,
+    #[cfg(feature = "openapi")]
+    openapi_path: Option<&'a str>,
+}
+
+/// Callback notified of the progress of the tests, set with [`DocAssert::assert_with_observer`]
+type Observer = Arc<dyn Fn(TestEvent) + Send + Sync>;
+
+/// Function callable in the placeholders, registered with [`DocAssert::register_function`]
+pub(crate) type Function = Arc<dyn Fn(&[Value]) -> Value + Send + Sync>;
+
+/// Functions callable in the placeholders by name
+#[derive(Clone, Default)]
+pub(crate) struct Functions(HashMap<String, Function>);
+
+impl std::fmt::Debug for Functions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.0.keys()).finish()
+    }
+}
+
+/// Source of the generated values, seeded with [`DocAssert::with_random_seed`] for reproducible runs
+#[derive(Debug, Clone)]
+pub(crate) struct Generator(StdRng);
+
+impl Default for Generator {
+    fn default() -> Self {
+        Generator(StdRng::from_entropy())
+    }
+}
+
+impl Generator {
+    pub(crate) fn seeded(seed: u64) -> Self {
+        Generator(StdRng::seed_from_u64(seed))
+    }
+
+    pub(crate) fn uuid(&mut self) -> String {
+        uuid::Builder::from_random_bytes(self.0.gen())
+            .into_uuid()
+            .to_string()
+    }
+
+    /// Evaluates a built-in generator like `uuid()`, `now()`, `now(epoch)` or `randomInt(1, 100)`.
+    pub(crate) fn generate(&mut self, name: &str, args: &[&str]) -> Result<Value, String> {
+        let now = || {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_err(|e| e.to_string())
+        };
+        match (name, args) {
+            ("uuid", []) => Ok(Value::String(self.uuid())),
+            ("now", []) => Ok(Value::String(iso8601(now()?))),
+            ("now", ["epoch"]) => Ok(now()?.as_secs().into()),
+            ("randomInt", [min, max]) => {
+                let parse = |arg: &str| {
+                    arg.parse::<i64>()
+                        .map_err(|_| format!("invalid randomInt bound {}", arg))
+                };
+                let (min, max) = (parse(min)?, parse(max)?);
+                if min > max {
+                    return Err(format!("invalid randomInt range {}, {}", min, max));
+                }
+                Ok(self.0.gen_range(min..=max).into())
+            }
+            _ => Err(format!(
+                "invalid arguments of {}: ({})",
+                name,
+                args.join(", ")
+            )),
+        }
+    }
+}
+
+/// Reads the client certificate and its private key from a PKCS #12 archive.
+fn load_identity(path: &str, password: &str) -> Result<reqwest::Identity, String> {
+    let der = std::fs::read(path)
+        .map_err(|e| format!("cannot read client certificate {}: {}", path, e))?;
+    reqwest::Identity::from_pkcs12_der(&der, password)
+        .map_err(|e| format!("invalid client certificate {}: {}", path, e))
+}
+
+/// Reads a root certificate in the PEM format.
+fn load_certificate(path: &str) -> Result<reqwest::Certificate, String> {
+    let pem =
+        std::fs::read(path).map_err(|e| format!("cannot read root certificate {}: {}", path, e))?;
+    reqwest::Certificate::from_pem(&pem)
+        .map_err(|e| format!("invalid root certificate {}: {}", path, e))
+}
+
+/// Formats the time since the Unix epoch as an ISO 8601 UTC timestamp like `2023-11-14T22:13:20Z`.
+fn iso8601(since_epoch: Duration) -> String {
+    let secs = since_epoch.as_secs();
+    let (days, time) = (secs / 86_400, secs % 86_400);
+
+    // converts the days to the civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3_600,
+        time % 3_600 / 60,
+        time % 60
+    )
 }
 
+/// In-process handler the requests are dispatched to instead of sending them over HTTP
+pub(crate) type Service = Box<
+    dyn Fn(ServiceRequest) -> Pin<Box<dyn Future<Output = ServiceResponse> + Send>> + Send + Sync,
+>;
+
 impl<'a> DocAssert<'a> {
     /// Constructs a new, empty `DocAssert` builder.
     ///
@@ -73,7 +245,39 @@ impl<'a> DocAssert<'a> {
         Self {
             url: None,
             doc_paths: vec![],
+            changed_paths: None,
             variables: Variables::new(),
+            check_determinism: false,
+            no_unexpected_errors: false,
+            continue_on_capture_failure: false,
+            report_stale_examples: false,
+            textual_markers: false,
+            color: ColorChoice::default(),
+            compact_diff: false,
+            strict_parsing: false,
+            request_delay: None,
+            environment: None,
+            environment_variables: HashMap::new(),
+            response_dump_dir: None,
+            service: None,
+            functions: Functions::default(),
+            user_agent: None,
+            base_path: None,
+            timeout: None,
+            redirect_policy: RedirectPolicy::default(),
+            client_identity: None,
+            root_certificate: None,
+            accept_invalid_certs: false,
+            proxy: None,
+            concurrency: None,
+            authorization: None,
+            correlation_header: None,
+            random_seed: None,
+            base_response: None,
+            path_assertions: vec![],
+            observer: None,
+            #[cfg(feature = "openapi")]
+            openapi_path: None,
         }
     }
 
@@ -109,6 +313,42 @@ impl<'a> DocAssert<'a> {
         self
     }
 
+    /// Sets the paths to several documentation files at once.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #![allow(unused_mut)]
+    /// use doc_assert::DocAssert;
+    /// let mut doc_assert = DocAssert::new().with_doc_paths(vec!["README.md", "docs/blog.md"]);
+    /// ```
+    pub fn with_doc_paths(mut self, doc_paths: Vec<&'a str>) -> Self {
+        self.doc_paths.extend(doc_paths);
+        self
+    }
+
+    /// Runs only the documentation files among the given changed files, e.g. the ones listed by
+    /// `git diff --name-only main`, so the selection can be computed outside of DocAssert.
+    ///
+    /// The paths are compared as written, ignoring `.` components, so both lists have to be
+    /// relative to the same directory. When none of the documentation files changed no test runs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #![allow(unused_mut)]
+    /// use doc_assert::DocAssert;
+    /// let mut doc_assert = DocAssert::new()
+    ///     .with_doc_paths(vec!["README.md", "docs/blog.md"])
+    ///     .with_changed_paths(vec!["./docs/blog.md", "src/main.rs"]);
+    /// ```
+    pub fn with_changed_paths(mut self, changed_paths: Vec<&'a str>) -> Self {
+        self.changed_paths
+            .get_or_insert_with(Vec::new)
+            .extend(changed_paths);
+        self
+    }
+
     /// Sets the variables to be used in the assertions.
     ///
     /// The variables will be used to replace the placeholders in the documentation.
@@ -129,390 +369,4137 @@ impl<'a> DocAssert<'a> {
         self
     }
 
-    /// Execute the assertions
+    /// Sets the variables specific to the given environment.
     ///
-    /// The assertions will be executed and a report will be returned
+    /// When the environment is selected with [`DocAssert::with_environment`] its variables
+    /// are added to the ones set with [`DocAssert::with_variables`], overriding them in case of conflicts.
     ///
     /// # Examples
     ///
     /// ```
     /// # #![allow(unused_mut)]
     /// use doc_assert::DocAssert;
-    /// async fn test() {
-    ///     let mut doc_assert = DocAssert::new()
-    ///         .with_url("http://localhost:8080")
-    ///         .with_doc_path("path/to/README.md");
-    ///     match doc_assert.assert().await {
-    ///         Ok(report) => {
-    ///             // handle success
-    ///         }
-    ///         Err(err) => {
-    ///             // handle error
-    ///         }
-    ///     };
-    /// }
+    /// use doc_assert::Variables;
+    ///
+    /// let mut staging = Variables::new();
+    /// staging.insert_int("user_id".to_string(), 1);
+    /// let mut production = Variables::new();
+    /// production.insert_int("user_id".to_string(), 42);
+    /// let mut doc_assert = DocAssert::new()
+    ///     .with_environment_variables("staging", staging)
+    ///     .with_environment_variables("production", production)
+    ///     .with_environment("staging");
     /// ```
-    pub async fn assert(mut self) -> Result<Report, AssertionError> {
-        let url = self.url.take().expect("URL is required");
-        let mut total_count = 0;
-        let mut failed_count = 0;
-        let mut summary = String::new();
-        let mut failures = String::new();
-
-        for doc_path in self.doc_paths {
-            let test_cases = parser::parse(doc_path.to_string())
-                .map_err(|e| AssertionError::ParsingError(e.clone()))?;
-            for tc in test_cases {
-                total_count += 1;
-                let id = format!(
-                    "{} {} ({}:{})",
-                    tc.request.http_method, tc.request.uri, doc_path, tc.request.line_number
-                );
-                match executor::execute(url, tc, &mut self.variables).await {
-                    Ok(_) => summary.push_str(format!("{} ✅\n", id).as_str()),
-                    Err(err) => {
-                        summary.push_str(format!("{} ❌\n", id).as_str());
-                        failures.push_str(format!("-------------\n{}: {}\n", id, err).as_str());
-                        failed_count += 1;
-                    }
-                }
-            }
-        }
-
-        if failed_count == 0 {
-            Ok(Report {
-                total_count,
-                failed_count,
-                summary,
-                failures: None,
-            })
-        } else {
-            Err(AssertionError::TestSuiteError(Report {
-                total_count,
-                failed_count,
-                summary,
-                failures: Some(failures),
-            }))
-        }
-    }
-}
-
-impl<'a> Default for DocAssert<'a> {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
-/// Report of the assertions
-///
-/// The report contains the total number of tests, the number of failed tests,
-/// a summary of passed and failed tests, and detailed information about
-/// the failed assertions.
-///
-/// # Examples
-///
-/// ```
-/// # #![allow(unused_mut)]
-/// use doc_assert::DocAssert;
-/// use doc_assert::Variables;
-///
-/// async fn test() {
-///     let mut doc_assert = DocAssert::new()
-///         .with_url("http://localhost:8080")
-///         .with_doc_path("path/to/README.md");
-///     match doc_assert.assert().await {
-///         Ok(report) => {
-///             println!("{}", report);
-///         }
-///         Err(err) => {
-///             // handle error
-///         }
-///     };
-/// }
-pub struct Report {
-    /// Total number of tests
-    total_count: usize,
-    /// Number of failed tests
-    failed_count: usize,
-    /// Summary of passed and failed tests
-    summary: String,
-    /// Detailed information about the failed assertions
-    failures: Option<String>,
-}
-
-impl Display for Report {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match &self.failures {
-            Some(failures) => write!(
-                f,
-                "{} tests\n{}\nfailures:\n{}\ntest result: FAILED. {} passed; {} failed",
-                self.total_count,
-                self.summary,
-                failures,
-                self.total_count - self.failed_count,
-                self.failed_count
-            ),
-            None => write!(
-                f,
-                "{} tests\n{}\ntest result: PASSED. {} passed; 0 failed",
-                self.total_count, self.summary, self.total_count
-            ),
-        }
+    pub fn with_environment_variables(
+        mut self,
+        environment: &'a str,
+        variables: Variables,
+    ) -> Self {
+        self.environment_variables.insert(environment, variables);
+        self
     }
-}
-
-/// Error type for DocAssert run
-pub enum AssertionError {
-    /// Error parsing the documentation file
-    ParsingError(String),
-    /// Error executing tests
-    TestSuiteError(Report),
-}
-
-/// Variables to be used in the request and response bodies.
-///
-/// The variables are used to replace placeholders in the request
-/// and response bodies in case some values need to be shared between requests and responses.
-///
-/// # Examples
-///
-/// Variables can be passed one by one with specified type:
-///
-/// ```
-/// # use doc_assert::Variables;
-/// # use serde_json::Value;
-/// let mut variables = Variables::new();
-/// variables.insert_string("name".to_string(), "John".to_string());
-/// variables.insert_int("age".to_string(), 30);
-/// ```
-///
-/// A `Value` can be passed directly:
-///
-/// ```
-/// # use doc_assert::Variables;
-/// # use serde_json::Value;
-/// let mut variables = Variables::new();
-/// variables.insert_value("name".to_string(), Value::String("John".to_string()));
-/// variables.insert_value("age".to_string(), Value::Number(serde_json::Number::from(30)));
-/// ```
-///
-/// Alternatively, they can be passed as a JSON object:
-///
-/// ```
-/// # use doc_assert::Variables;
-/// # use serde_json::Value;
-/// let json = r#"{"name": "John", "age": 30}"#;
-/// let variables = Variables::from_json(&serde_json::from_str(json).unwrap()).unwrap();
-/// ```
-///
-#[derive(Debug, Clone, Default)]
-pub struct Variables {
-    map: HashMap<String, Value>,
-}
 
-impl Variables {
-    /// Constructs a new `Variables`.
+    /// Selects the environment whose variables will be used in the assertions.
     ///
     /// # Examples
     ///
     /// ```
-    /// # use doc_assert::Variables;
-    /// let variables = Variables::new();
+    /// # #![allow(unused_mut)]
+    /// use doc_assert::DocAssert;
+    /// let mut doc_assert = DocAssert::new().with_environment("staging");
     /// ```
-    pub fn new() -> Self {
-        Self {
-            map: HashMap::new(),
-        }
+    pub fn with_environment(mut self, environment: &'a str) -> Self {
+        self.environment = Some(environment);
+        self
     }
 
-    /// Constructs a new `Variables` from a JSON object.
+    /// Enables the determinism check.
+    ///
+    /// The test suite will be executed twice, each time starting from the initial
+    /// variables, and the run will fail if any test passed in one run and failed in the other
+    /// or ran only once. Unless [`DocAssert::with_random_seed`] is set, the second run generates
+    /// other random values, so the tests depending on them are caught as well.
     ///
     /// # Examples
     ///
     /// ```
-    /// # use doc_assert::Variables;
-    /// # use serde_json::Value;
-    /// let json = r#"{"name": "John", "age": 30}"#;
-    /// let variables = Variables::from_json(&serde_json::from_str(json).unwrap()).unwrap();
+    /// # #![allow(unused_mut)]
+    /// use doc_assert::DocAssert;
+    /// let mut doc_assert = DocAssert::new().with_determinism_check(true);
     /// ```
-    pub fn from_json(json: &Value) -> Result<Self, String> {
-        let mut map = HashMap::new();
-
-        if let Value::Object(obj) = json {
-            for (key, value) in obj {
-                map.insert(key.clone(), value.clone());
-            }
-        } else {
-            return Err("variables must be an object".to_string());
-        }
-
-        Ok(Self { map })
+    pub fn with_determinism_check(mut self, check_determinism: bool) -> Self {
+        self.check_determinism = check_determinism;
+        self
     }
 
-    /// Inserts a `Value` into the `Variables`.
-    ///
-    /// This can be useful when more complex types are needed.
-    /// Since `Variables` is a wrapper around `HashMap` if you insert duplicate
-    /// keys the value will be overwritten.
+    /// Fails a test right away when the server responds with a 5xx status that isn't documented,
+    /// even if the request would be retried and pass afterwards.
     ///
     /// # Examples
     ///
     /// ```
-    /// # use doc_assert::Variables;
-    /// # use serde_json::Value;
-    /// let mut variables = Variables::new();
-    /// variables.insert_value("name".to_string(), Value::String("John".to_string()));
+    /// # #![allow(unused_mut)]
+    /// use doc_assert::DocAssert;
+    /// let mut doc_assert = DocAssert::new().with_no_unexpected_errors(true);
     /// ```
-    pub fn insert_value(&mut self, name: String, value: Value) {
-        self.map.insert(name, value);
+    pub fn with_no_unexpected_errors(mut self, no_unexpected_errors: bool) -> Self {
+        self.no_unexpected_errors = no_unexpected_errors;
+        self
     }
 
-    /// Inserts a `String` into the `Variables`.
+    /// Keeps capturing the variables of a response when one of its `[let]` captures fails.
+    ///
+    /// The test with the failed capture still fails and the variable is left unset, but the
+    /// other variables of the response are captured, so the later tests depending only on them
+    /// still run as documented. By default the remaining captures of the response are skipped.
     ///
     /// # Examples
     ///
     /// ```
-    /// # use doc_assert::Variables;
-    /// let mut variables = Variables::new();
-    /// variables.insert_string("name".to_string(), "John".to_string());
+    /// # #![allow(unused_mut)]
+    /// use doc_assert::DocAssert;
+    /// let mut doc_assert = DocAssert::new().with_continue_on_capture_failure(true);
     /// ```
-    pub fn insert_string(&mut self, name: String, value: String) {
-        self.map.insert(name, Value::String(value));
+    pub fn with_continue_on_capture_failure(mut self, continue_on_capture_failure: bool) -> Self {
+        self.continue_on_capture_failure = continue_on_capture_failure;
+        self
     }
 
-    /// Inserts an `i64` into the `Variables`.
+    /// Notes the passed tests whose documented body differs from the actual one only in
+    /// the ignored paths, so that stale examples can be refreshed without failing the run.
+    ///
+    /// The notes are listed in the report and available with [`TestResult::notes`].
     ///
     /// # Examples
     ///
     /// ```
-    /// # use doc_assert::Variables;
-    /// let mut variables = Variables::new();
-    /// variables.insert_int("age".to_string(), 30);
+    /// # #![allow(unused_mut)]
+    /// use doc_assert::DocAssert;
+    /// let mut doc_assert = DocAssert::new().with_stale_example_report(true);
     /// ```
-    pub fn insert_int(&mut self, name: String, value: i64) {
-        self.map
-            .insert(name, Value::Number(serde_json::Number::from(value)));
+    pub fn with_stale_example_report(mut self, report_stale_examples: bool) -> Self {
+        self.report_stale_examples = report_stale_examples;
+        self
     }
 
-    /// Inserts an `f64` into the `Variables`.
+    /// Marks the results in the report with `PASS` and `FAIL` instead of emoji.
+    ///
+    /// Useful for CI log parsers and screen readers which don't handle emoji well.
     ///
     /// # Examples
     ///
     /// ```
-    /// # use doc_assert::Variables;
-    /// let mut variables = Variables::new();
-    /// variables.insert_float("age".to_string(), 30.0);
+    /// # #![allow(unused_mut)]
+    /// use doc_assert::DocAssert;
+    /// let mut doc_assert = DocAssert::new().with_textual_markers(true);
     /// ```
-    pub fn insert_float(&mut self, name: String, value: f64) {
-        self.map.insert(
-            name,
-            Value::Number(serde_json::Number::from_f64(value).unwrap()),
-        );
+    pub fn with_textual_markers(mut self, textual_markers: bool) -> Self {
+        self.textual_markers = textual_markers;
+        self
     }
 
-    /// Inserts a `bool` into the `Variables`.
+    /// Colors the actual values of the body differences red and the expected ones green.
+    ///
+    /// With [`ColorChoice::Auto`] the differences are colored only when the standard output
+    /// is a terminal, so piped reports stay plain. Disabled by default.
     ///
     /// # Examples
     ///
     /// ```
-    /// # use doc_assert::Variables;
-    /// let mut variables = Variables::new();
-    /// variables.insert_bool("is_adult".to_string(), true);
+    /// # #![allow(unused_mut)]
+    /// use doc_assert::{ColorChoice, DocAssert};
+    /// let mut doc_assert = DocAssert::new().with_color(ColorChoice::Auto);
     /// ```
-    pub fn insert_bool(&mut self, name: String, value: bool) {
-        self.map.insert(name, Value::Bool(value));
+    pub fn with_color(mut self, color: ColorChoice) -> Self {
+        self.color = color;
+        self
     }
 
-    /// Inserts a `null` into the `Variables`.
+    /// Reports each body difference on a single line with its path and the compact values
+    /// instead of the pretty printed values, which is easier to scan for large objects.
     ///
     /// # Examples
     ///
     /// ```
-    /// # use doc_assert::Variables;
-    /// let mut variables = Variables::new();
-    /// variables.insert_null("name".to_string());
+    /// # #![allow(unused_mut)]
+    /// use doc_assert::DocAssert;
+    /// let mut doc_assert = DocAssert::new().with_compact_diff(true);
     /// ```
-    pub fn insert_null(&mut self, name: String) {
-        self.map.insert(name, Value::Null);
+    pub fn with_compact_diff(mut self, compact_diff: bool) -> Self {
+        self.compact_diff = compact_diff;
+        self
     }
 
-    pub(crate) fn obtain_from_response(
-        &mut self,
-        response: &Value,
-        variable_templates: &HashMap<String, Path>,
-    ) -> Result<(), String> {
-        for (name, path) in variable_templates {
-            let value = extract_value(path, response).ok_or_else(|| {
-                format!("variable template {} not found in the response body", name)
-            })?;
+    /// Fails parsing on unknown annotations instead of ignoring them.
+    ///
+    /// Lines shaped like an annotation, `[keyword]: # (...)`, which follow a request are checked
+    /// against the known annotations, so a misspelled `[ignroe]` is reported instead of silently
+    /// not being applied. Disabled by default, since such lines may be regular markdown links.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #![allow(unused_mut)]
+    /// use doc_assert::DocAssert;
+    /// let mut doc_assert = DocAssert::new().with_strict_parsing(true);
+    /// ```
+    pub fn with_strict_parsing(mut self, strict_parsing: bool) -> Self {
+        self.strict_parsing = strict_parsing;
+        self
+    }
 
-            self.map.insert(name.clone(), value);
-        }
+    /// Sets the delay between consecutive requests.
+    ///
+    /// The delay is applied between every two tests regardless of their results,
+    /// which helps with rate limited APIs. It is independent of the delay between retries.
+    /// With a concurrency greater than one, the delay only separates the tests of the same file,
+    /// so the files running at the same time may still send their requests together.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #![allow(unused_mut)]
+    /// use doc_assert::DocAssert;
+    /// use std::time::Duration;
+    /// let mut doc_assert = DocAssert::new().with_request_delay(Duration::from_millis(100));
+    /// ```
+    pub fn with_request_delay(mut self, delay: Duration) -> Self {
+        self.request_delay = Some(delay);
+        self
+    }
 
-        Ok(())
+    /// Sets the directory where the actual response bodies will be written.
+    ///
+    /// The body of each response is written to a file named after the test id,
+    /// regardless of whether the test passed or failed. The directory is created if needed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #![allow(unused_mut)]
+    /// use doc_assert::DocAssert;
+    /// let mut doc_assert = DocAssert::new().with_response_dump_dir("target/responses");
+    /// ```
+    pub fn with_response_dump_dir(mut self, dir: &'a str) -> Self {
+        self.response_dump_dir = Some(dir);
+        self
     }
 
-    fn replace_placeholders(&self, input: &mut String, trim_quotes: bool) -> Result<(), String> {
-        for (name, value) in &self.map {
-            let placeholder = format!("`{}`", name);
-            let value_str = value.to_string();
+    /// Registers a function callable in the placeholders.
+    ///
+    /// A placeholder like `` `sign(`ts`, /blog)` `` calls the function with the arguments
+    /// separated by commas. Each argument is the value of the variable with that name,
+    /// a JSON value or otherwise a string. The returned value replaces the placeholder
+    /// the same way as the value of a variable.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #![allow(unused_mut)]
+    /// use doc_assert::DocAssert;
+    /// use serde_json::Value;
+    ///
+    /// let mut doc_assert = DocAssert::new().register_function("upper", |args: &[Value]| {
+    ///     Value::String(args[0].as_str().unwrap_or_default().to_uppercase())
+    /// });
+    /// ```
+    pub fn register_function<F>(mut self, name: &str, function: F) -> Self
+    where
+        F: Fn(&[Value]) -> Value + Send + Sync + 'static,
+    {
+        self.functions
+            .0
+            .insert(name.to_string(), Arc::new(function));
+        self
+    }
 
-            let value = if trim_quotes {
-                value_str.trim_matches('"')
-            } else {
-                value_str.as_str()
-            };
+    /// Sets an in-process handler the requests will be dispatched to.
+    ///
+    /// Instead of sending the requests over HTTP, each of them is passed to the handler
+    /// and the returned response is asserted. The URL is not required in this case.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #![allow(unused_mut)]
+    /// use doc_assert::{DocAssert, ServiceRequest, ServiceResponse};
+    ///
+    /// let mut doc_assert = DocAssert::new().with_service(|request: ServiceRequest| async move {
+    ///     match request.uri() {
+    ///         "/health" => ServiceResponse::new(200).with_body("{\"status\": \"up\"}"),
+    ///         _ => ServiceResponse::new(404),
+    ///     }
+    /// });
+    /// ```
+    pub fn with_service<F, Fut>(mut self, service: F) -> Self
+    where
+        F: Fn(ServiceRequest) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ServiceResponse> + Send + 'static,
+    {
+        self.service = Some(Box::new(move |request| Box::pin(service(request))));
+        self
+    }
 
-            *input = input.replace(&placeholder, value);
-        }
+    /// Sets the user agent the requests will be sent with.
+    ///
+    /// Defaults to `doc-assert/<version>`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #![allow(unused_mut)]
+    /// use doc_assert::DocAssert;
+    /// let mut doc_assert = DocAssert::new().with_user_agent("my-docs-checker/1.0");
+    /// ```
+    pub fn with_user_agent(mut self, user_agent: &'a str) -> Self {
+        self.user_agent = Some(user_agent);
+        self
+    }
 
-        if input.contains('`') {
-            return Err(format!("unresolved variable placeholders in {}", input));
-        }
+    /// Sets the path prepended to the URI of every request sent to the URL, so the documented
+    /// URIs can omit a common prefix like `/api/v1`.
+    ///
+    /// The parts are joined with single slashes whether or not the path starts or ends with one.
+    /// Absolute URIs starting with `http://` or `https://` are requested as they are.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #![allow(unused_mut)]
+    /// use doc_assert::DocAssert;
+    /// let mut doc_assert = DocAssert::new().with_base_path("/api/v1");
+    /// ```
+    pub fn with_base_path(mut self, base_path: &'a str) -> Self {
+        self.base_path = Some(base_path);
+        self
+    }
 
-        Ok(())
+    /// Sets the default time to wait for each response, covering sending the request and
+    /// reading the whole response.
+    ///
+    /// The `[timeout]` annotation of a request overrides it. Without any timeout the requests
+    /// wait as long as it takes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #![allow(unused_mut)]
+    /// use doc_assert::DocAssert;
+    /// use std::time::Duration;
+    /// let mut doc_assert = DocAssert::new().with_timeout(Duration::from_secs(10));
+    /// ```
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
     }
 
-    pub(crate) fn replace_request_placeholders(&self, input: &mut Request) -> Result<(), String> {
-        self.replace_placeholders(&mut input.uri, true)?;
+    /// Sets the client certificate the requests authenticate with, for APIs requiring mutual TLS.
+    ///
+    /// The certificate and its private key are read from a PKCS #12 archive protected with the
+    /// password when the tests run, failing the run if it can't be loaded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #![allow(unused_mut)]
+    /// use doc_assert::DocAssert;
+    /// let mut doc_assert = DocAssert::new().with_client_identity("path/to/client.p12", "secret");
+    /// ```
+    pub fn with_client_identity(mut self, pkcs12_path: &'a str, password: &'a str) -> Self {
+        self.client_identity = Some((pkcs12_path, password));
+        self
+    }
 
-        if let Some(body) = &mut input.body {
-            self.replace_placeholders(body, false)?;
-        }
+    /// Sets an additional root certificate in the PEM format the server certificates are
+    /// trusted with, e.g. the one of a self-signed certificate authority.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #![allow(unused_mut)]
+    /// use doc_assert::DocAssert;
+    /// let mut doc_assert = DocAssert::new().with_root_certificate("path/to/ca.pem");
+    /// ```
+    pub fn with_root_certificate(mut self, pem_path: &'a str) -> Self {
+        self.root_certificate = Some(pem_path);
+        self
+    }
 
-        for (_, value) in &mut input.headers.iter_mut() {
-            self.replace_placeholders(value, true)?;
-        }
+    /// Accepts any server certificate, including the self-signed, expired or issued for another
+    /// host ones, e.g. for a staging environment.
+    ///
+    /// This makes the connections vulnerable to man-in-the-middle attacks, so a warning is
+    /// printed to the standard error whenever the tests run with it. Prefer trusting the
+    /// certificate authority with [`DocAssert::with_root_certificate`] where possible.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #![allow(unused_mut)]
+    /// use doc_assert::DocAssert;
+    /// let mut doc_assert = DocAssert::new().danger_accept_invalid_certs(true);
+    /// ```
+    pub fn danger_accept_invalid_certs(mut self, accept_invalid_certs: bool) -> Self {
+        self.accept_invalid_certs = accept_invalid_certs;
+        self
+    }
 
-        Ok(())
+    /// Sets the proxy all the requests are sent through, e.g. `http://proxy.example.com:3128`.
+    ///
+    /// Without it the proxies set in the `HTTP_PROXY` and `HTTPS_PROXY` environment variables are
+    /// used. The hosts listed in the `NO_PROXY` environment variable are reached directly either way.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #![allow(unused_mut)]
+    /// use doc_assert::DocAssert;
+    /// let mut doc_assert = DocAssert::new().with_proxy("http://proxy.example.com:3128");
+    /// ```
+    pub fn with_proxy(mut self, proxy: &'a str) -> Self {
+        self.proxy = Some(proxy);
+        self
     }
 
-    pub(crate) fn replace_response_placeholders(&self, input: &mut Response) -> Result<(), String> {
-        if let Some(body) = &mut input.body {
-            self.replace_placeholders(body, false)?;
-        }
+    /// Sets how the redirects returned by the API are handled.
+    ///
+    /// Up to 10 redirects are followed by default, so only the final response is asserted.
+    /// With [`RedirectPolicy::None`] the redirect response itself is asserted, including
+    /// its `Location` header. The `[no-redirect]` annotation disables following redirects
+    /// for a single request.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #![allow(unused_mut)]
+    /// use doc_assert::{DocAssert, RedirectPolicy};
+    /// let mut doc_assert = DocAssert::new().with_redirect_policy(RedirectPolicy::None);
+    /// ```
+    pub fn with_redirect_policy(mut self, redirect_policy: RedirectPolicy) -> Self {
+        self.redirect_policy = redirect_policy;
+        self
+    }
 
-        for (_, value) in &mut input.headers.iter_mut() {
-            self.replace_placeholders(value, true)?;
-        }
+    /// Sets the number of documentation files tested concurrently.
+    ///
+    /// The test cases of a single file always run in order, so the variables captured in one
+    /// of them can be used by the following ones. With a concurrency greater than one, each file
+    /// starts with its own copy of the variables, so variables captured in one file are not
+    /// available in the others, and such files have to run sequentially. The results are
+    /// reported in the order of the files regardless of when they finished.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #![allow(unused_mut)]
+    /// use doc_assert::DocAssert;
+    /// let mut doc_assert = DocAssert::new().with_concurrency(4);
+    /// ```
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = Some(concurrency);
+        self
+    }
 
-        Ok(())
+    /// Sets the bearer token sent in the `Authorization` header of each request.
+    ///
+    /// The header is not overridden if the documentation sets it for the request, so a token
+    /// captured from a login request can still be sent instead. The token can contain variables.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #![allow(unused_mut)]
+    /// use doc_assert::DocAssert;
+    /// let mut doc_assert = DocAssert::new().with_bearer_token("abcd");
+    /// ```
+    pub fn with_bearer_token(mut self, token: &str) -> Self {
+        self.authorization = Some(format!("Bearer {}", token));
+        self
     }
-}
 
-fn extract_value(path: &Path, value: &Value) -> Option<Value> {
-    match path {
-        Path::Root => None,
-        Path::Keys(keys) => {
-            let mut current = value;
-            for key in keys {
-                match key {
-                    Key::Field(field) => current = current.get(field)?,
-                    Key::Idx(index) => current = current.get(index)?,
-                    _ => return None,
-                }
-            }
-            Some(current.clone())
-        }
+    /// Sets the credentials sent with the basic authentication scheme in the `Authorization`
+    /// header of each request.
+    ///
+    /// Like with [`DocAssert::with_bearer_token`], the header set in the documentation takes
+    /// precedence.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #![allow(unused_mut)]
+    /// use doc_assert::DocAssert;
+    /// let mut doc_assert = DocAssert::new().with_basic_auth("admin", "secret");
+    /// ```
+    pub fn with_basic_auth(mut self, user: &str, password: &str) -> Self {
+        let credentials =
+            base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", user, password));
+        self.authorization = Some(format!("Basic {}", credentials));
+        self
+    }
+
+    /// Sets the header each request is sent with carrying a unique identifier.
+    ///
+    /// A new UUID is generated for every test and recorded in its [`TestResult`],
+    /// so the test can be correlated with the server logs. The header is not overridden
+    /// if the documentation sets it for the request.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #![allow(unused_mut)]
+    /// use doc_assert::DocAssert;
+    /// let mut doc_assert = DocAssert::new().with_correlation_header("X-Correlation-Id");
+    /// ```
+    pub fn with_correlation_header(mut self, header: &'a str) -> Self {
+        self.correlation_header = Some(header);
+        self
+    }
+
+    /// Sets the seed of the generated values.
+    ///
+    /// Values like the identifiers sent in the correlation header are random by default.
+    /// With a seed they are the same in every run, so failures can be reproduced.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #![allow(unused_mut)]
+    /// use doc_assert::DocAssert;
+    /// let mut doc_assert = DocAssert::new().with_random_seed(42);
+    /// ```
+    pub fn with_random_seed(mut self, seed: u64) -> Self {
+        self.random_seed = Some(seed);
+        self
+    }
+
+    /// Sets the base body the documented response bodies are merged into.
+    ///
+    /// Fields shared by many responses, like a common envelope, can be declared once
+    /// and the documentation only states the specific ones. Documented JSON objects are merged
+    /// into the base recursively, their fields overriding the base ones. Other bodies are compared as they are.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #![allow(unused_mut)]
+    /// use doc_assert::DocAssert;
+    /// use serde_json::json;
+    ///
+    /// let mut doc_assert = DocAssert::new()
+    ///     .with_base_response(json!({"status": "ok", "errors": [], "data": null}));
+    /// ```
+    pub fn with_base_response(mut self, body: Value) -> Self {
+        self.base_response = Some(body);
+        self
+    }
+
+    /// Registers a check of the values at the path in every response.
+    ///
+    /// The path is a JSONPath like `$.items[*].price`. The check is called with each actual value
+    /// the path resolves to, responses without any value there are not checked. An error returned
+    /// by the check fails the test with its message. It allows asserting invariants which cannot
+    /// be documented with a literal example, like a total computed from other fields.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #![allow(unused_mut)]
+    /// use doc_assert::DocAssert;
+    ///
+    /// let mut doc_assert = DocAssert::new().assert_path("$.id", |id| match id.as_u64() {
+    ///     Some(id) if id > 0 => Ok(()),
+    ///     _ => Err(format!("expected a positive id, got {}", id)),
+    /// });
+    /// ```
+    pub fn assert_path<F>(mut self, path: &str, assertion: F) -> Self
+    where
+        F: Fn(&Value) -> Result<(), String> + Send + Sync + 'static,
+    {
+        self.path_assertions.push(PathAssertion {
+            path: path.to_string(),
+            assertion: Arc::new(assertion),
+        });
+        self
+    }
+
+    /// Sets the path to the OpenAPI specification the responses will be validated against.
+    ///
+    /// When a documented request matches an operation of the specification, the actual response
+    /// is validated against the schema of the operation's response in addition to the comparison
+    /// with the documented body. The specification can be in the JSON or YAML format.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #![allow(unused_mut)]
+    /// use doc_assert::DocAssert;
+    /// let mut doc_assert = DocAssert::new().with_openapi("path/to/openapi.yaml");
+    /// ```
+    #[cfg(feature = "openapi")]
+    pub fn with_openapi(mut self, spec_path: &'a str) -> Self {
+        self.openapi_path = Some(spec_path);
+        self
+    }
+
+    /// Execute the assertions
+    ///
+    /// The assertions will be executed and a report will be returned.
+    /// Fails with a configuration error if no documentation file was added.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #![allow(unused_mut)]
+    /// use doc_assert::DocAssert;
+    /// async fn test() {
+    ///     let mut doc_assert = DocAssert::new()
+    ///         .with_url("http://localhost:8080")
+    ///         .with_doc_path("path/to/README.md");
+    ///     match doc_assert.assert().await {
+    ///         Ok(report) => {
+    ///             // handle success
+    ///         }
+    ///         Err(err) => {
+    ///             // handle error
+    ///         }
+    ///     };
+    /// }
+    /// ```
+    pub async fn assert(mut self) -> Result<Report, AssertionError> {
+        if self.doc_paths.is_empty() {
+            return Err(AssertionError::ConfigurationError(
+                "no documentation to test, add the files with DocAssert::with_doc_path".to_string(),
+            ));
+        }
+
+        let url = match (self.url.take(), &self.service) {
+            (Some(url), _) => url,
+            (None, Some(_)) => "",
+            (None, None) => panic!("URL is required"),
+        };
+
+        let mut variables = self.variables.clone();
+        variables.functions = self.functions.clone();
+        if let Some(seed) = self.random_seed {
+            variables.generator = Generator::seeded(seed);
+        }
+        if let Some(environment) = self.environment {
+            let environment_variables =
+                self.environment_variables.get(environment).ok_or_else(|| {
+                    AssertionError::ConfigurationError(format!(
+                        "no variables defined for environment {}",
+                        environment
+                    ))
+                })?;
+            variables.extend(environment_variables);
+        }
+
+        if self.accept_invalid_certs {
+            eprintln!(
+                "WARNING: TLS certificates are not verified, the connections are insecure. \
+                 Never accept invalid certificates outside of test environments."
+            );
+        }
+
+        let report = self.run(url, variables.clone()).await?;
+        let flipped = match self.check_determinism {
+            true => {
+                // unless seeded, the rerun generates other values, so it can expose the tests
+                // which depend on them
+                if self.random_seed.is_none() {
+                    variables.generator = Generator::default();
+                }
+                // the observer follows the first run only, the rerun is reported as flipped tests
+                let observer = self.observer.take();
+                let rerun = self.run(url, variables).await;
+                self.observer = observer;
+                report.flipped(&rerun?)
+            }
+            false => vec![],
+        };
+
+        self.notify(TestEvent::Finished {
+            passed: report.total_count() - report.failed_count(),
+            failed: report.failed_count(),
+        });
+        if !flipped.is_empty() {
+            return Err(AssertionError::NondeterminismError(flipped));
+        }
+
+        if report.failed_count() == 0 {
+            Ok(report)
+        } else {
+            Err(AssertionError::TestSuiteError(report))
+        }
+    }
+
+    /// Runs the tests like [`DocAssert::assert`], calling the observer as each test starts
+    /// and completes and once all of them finished.
+    ///
+    /// The observer is called synchronously as the tests progress, so it can drive a progress bar
+    /// or log the results as they come without waiting for the report. With a concurrency above
+    /// one the events of the documents running at the same time interleave, while the events of
+    /// a single document keep their order. The rerun of [`DocAssert::with_determinism_check`] sends no
+    /// events. [`TestEvent::Finished`] is only sent once the tests ran, so not when the
+    /// configuration is invalid or a document can't be parsed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use doc_assert::{DocAssert, TestEvent};
+    ///
+    /// async fn test() {
+    ///     let result = DocAssert::new()
+    ///         .with_url("http://localhost:8080")
+    ///         .with_doc_path("path/to/README.md")
+    ///         .assert_with_observer(|event| match event {
+    ///             TestEvent::Failed { id, error } => eprintln!("{} failed: {}", id, error),
+    ///             event => eprintln!("{:?}", event),
+    ///         })
+    ///         .await;
+    /// }
+    /// ```
+    pub async fn assert_with_observer<F>(mut self, observer: F) -> Result<Report, AssertionError>
+    where
+        F: Fn(TestEvent) + Send + Sync + 'static,
+    {
+        self.observer = Some(Arc::new(observer));
+        self.assert().await
+    }
+
+    /// Parses the documentation and checks it can be run, without sending any request.
+    ///
+    /// Besides the parsing errors, like the invalid paths and patterns of the annotations, the
+    /// placeholders which resolve neither to a variable set before the run or captured by an
+    /// earlier response, nor to a function call, are reported. The environment placeholders like
+    /// `${env.API_TOKEN}` are only resolved when the tests run, so they are not checked. The
+    /// parsing stops at the first error of a file, while the placeholders of every request and
+    /// response are checked, and all the errors found are returned together, each with its file
+    /// and line. With a concurrency greater than one, each file is checked with its own copy of
+    /// the variables, as when it runs. On success the number of the validated test cases
+    /// is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use doc_assert::{AssertionError, DocAssert};
+    ///
+    /// fn validate() {
+    ///     match DocAssert::new().with_doc_path("path/to/README.md").validate() {
+    ///         Ok(count) => println!("{} test cases are valid", count),
+    ///         Err(AssertionError::ValidationError(errors)) => {
+    ///             errors.iter().for_each(|err| eprintln!("{}", err))
+    ///         }
+    ///         Err(_) => {}
+    ///     }
+    /// }
+    /// ```
+    pub fn validate(&self) -> Result<usize, AssertionError> {
+        if self.doc_paths.is_empty() {
+            return Err(AssertionError::ConfigurationError(
+                "no documentation to validate, add the files with DocAssert::with_doc_path"
+                    .to_string(),
+            ));
+        }
+
+        let mut known = self.variables.map.keys().cloned().collect::<HashSet<_>>();
+        if let Some(environment) = self.environment {
+            let environment_variables =
+                self.environment_variables.get(environment).ok_or_else(|| {
+                    AssertionError::ConfigurationError(format!(
+                        "no variables defined for environment {}",
+                        environment
+                    ))
+                })?;
+            known.extend(environment_variables.map.keys().cloned());
+        }
+
+        let initial = known.clone();
+        let (mut count, mut errors) = (0, vec![]);
+        for doc_path in self.selected_doc_paths() {
+            if matches!(self.concurrency, Some(concurrency) if concurrency > 1) {
+                known = initial.clone();
+            }
+            let test_cases = match parser::parse(doc_path.to_string(), self.strict_parsing) {
+                Ok(test_cases) => test_cases,
+                Err(err) => {
+                    errors.push(err);
+                    continue;
+                }
+            };
+            for tc in test_cases {
+                count += 1;
+                if let Err(message) = self.check_placeholders(&tc.request, &known) {
+                    errors.push(ParseError::new(doc_path, tc.request.line_number, message));
+                }
+                let response = &tc.response;
+                let texts = response.body.iter().chain(response.headers.values());
+                if let Err(message) = self.check_text_placeholders(texts, &known) {
+                    errors.push(ParseError::new(doc_path, response.line_number, message));
+                }
+
+                known.extend(response.variables.keys().cloned());
+                known.extend(response.header_variables.keys().cloned());
+                known.extend(response.cookie_variables.keys().cloned());
+                known.extend(response.etag.iter().map(|etag| etag.variable.clone()));
+            }
+        }
+
+        match errors.is_empty() {
+            true => Ok(count),
+            false => Err(AssertionError::ValidationError(errors)),
+        }
+    }
+
+    /// Fails on the first placeholder of the request which can't be resolved before the run.
+    fn check_placeholders(&self, request: &Request, known: &HashSet<String>) -> Result<(), String> {
+        if let Some(variable) = request.patch_of.as_ref().filter(|v| !known.contains(*v)) {
+            return Err(format!("unknown variable {} to patch", variable));
+        }
+
+        let multipart = request
+            .multipart
+            .iter()
+            .flatten()
+            .filter_map(|field| match field {
+                MultipartField::Text { value, .. } => Some(value),
+                MultipartField::File { .. } => None,
+            });
+        let texts = std::iter::once(&request.uri)
+            .chain(request.body.iter())
+            .chain(request.headers.values())
+            .chain(request.form.iter().flatten().map(|(_, value)| value))
+            .chain(multipart);
+        self.check_text_placeholders(texts, known)
+    }
+
+    fn check_text_placeholders<'t>(
+        &self,
+        texts: impl Iterator<Item = &'t String>,
+        known: &HashSet<String>,
+    ) -> Result<(), String> {
+        let re = regex::Regex::new(r"`(?<name>[\w-]+)(?:\((?<args>[^`()]*)\))?`").unwrap();
+        let env_re = regex::Regex::new(ENV_PLACEHOLDER).unwrap();
+        let mut generator = Generator::seeded(0);
+        for text in texts {
+            let text = env_re.replace_all(text, "");
+            for caps in re.captures_iter(&text) {
+                let name = &caps["name"];
+                let Some(args) = caps.name("args") else {
+                    if !known.contains(name) {
+                        return Err(format!("unresolved variable placeholder {}", &caps[0]));
+                    }
+                    continue;
+                };
+                if self.functions.0.contains_key(name) {
+                    continue;
+                }
+                let args = args
+                    .as_str()
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|arg| !arg.is_empty())
+                    .collect::<Vec<_>>();
+                generator
+                    .generate(name, &args)
+                    .map_err(|err| format!("invalid call {}: {}", &caps[0], err))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parses the documentation file and returns the tests it declares, without running them.
+    ///
+    /// The tests are returned in the order they run, the setup ones first and the teardown ones
+    /// last. Unknown annotations fail the parsing when set with [`DocAssert::with_strict_parsing`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use doc_assert::DocAssert;
+    ///
+    /// fn endpoints() {
+    ///     if let Ok(tests) = DocAssert::new().parse_only("path/to/README.md") {
+    ///         for test in tests {
+    ///             println!("{} {} -> {}", test.method(), test.uri(), test.code());
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub fn parse_only(&self, doc_path: &str) -> Result<Vec<DocumentedTest>, ParseError> {
+        let test_cases = parser::parse(doc_path.to_string(), self.strict_parsing)?;
+        Ok(test_cases
+            .iter()
+            .map(|tc| DocumentedTest::new(doc_path, tc))
+            .collect())
+    }
+
+    fn notify(&self, event: TestEvent) {
+        if let Some(observer) = &self.observer {
+            observer(event);
+        }
+    }
+
+    /// Returns the documentation files to run, only the changed ones if the changed paths are set.
+    fn selected_doc_paths(&self) -> Vec<&'a str> {
+        let normalize = |path: &str| {
+            std::path::Path::new(path)
+                .components()
+                .filter(|component| component != &std::path::Component::CurDir)
+                .collect::<std::path::PathBuf>()
+        };
+
+        match &self.changed_paths {
+            Some(changed_paths) => {
+                let changed_paths = changed_paths
+                    .iter()
+                    .map(|p| normalize(p))
+                    .collect::<Vec<_>>();
+                self.doc_paths
+                    .iter()
+                    .filter(|doc_path| changed_paths.contains(&normalize(doc_path)))
+                    .copied()
+                    .collect()
+            }
+            None => self.doc_paths.clone(),
+        }
+    }
+
+    async fn run(&self, url: &str, mut variables: Variables) -> Result<Report, AssertionError> {
+        let context = RunContext {
+            url,
+            http_options: HttpOptions {
+                user_agent: self.user_agent,
+                base_path: self.base_path,
+                timeout: self.timeout,
+                redirect_policy: self.redirect_policy,
+                identity: self
+                    .client_identity
+                    .map(|(path, password)| load_identity(path, password))
+                    .transpose()
+                    .map_err(AssertionError::ConfigurationError)?,
+                root_certificate: self
+                    .root_certificate
+                    .map(load_certificate)
+                    .transpose()
+                    .map_err(AssertionError::ConfigurationError)?,
+                accept_invalid_certs: self.accept_invalid_certs,
+                proxy: self
+                    .proxy
+                    .map(|proxy| {
+                        reqwest::Proxy::all(proxy)
+                            .map(|p| p.no_proxy(reqwest::NoProxy::from_env()))
+                            .map_err(|e| format!("invalid proxy {}: {}", proxy, e))
+                    })
+                    .transpose()
+                    .map_err(AssertionError::ConfigurationError)?,
+                ..HttpOptions::default()
+            },
+            #[cfg(feature = "openapi")]
+            openapi: self
+                .openapi_path
+                .map(openapi::OpenApi::load)
+                .transpose()
+                .map_err(AssertionError::ConfigurationError)?,
+        };
+
+        let doc_paths = self.selected_doc_paths();
+        let results = match self.concurrency {
+            Some(concurrency) if concurrency > 1 => {
+                // documents run independently, each with its own copy of the variables, and
+                // the futures are collected first to keep the future of the run Send
+                let runs = doc_paths
+                    .into_iter()
+                    .map(|doc_path| self.run_isolated(&context, doc_path, variables.clone()))
+                    .collect::<Vec<_>>();
+                let runs = futures_util::stream::iter(runs)
+                    .buffered(concurrency)
+                    .collect::<Vec<_>>()
+                    .await;
+                let mut results = vec![];
+                for run in runs {
+                    results.extend(run?);
+                }
+                results
+            }
+            _ => {
+                let mut results = vec![];
+                for doc_path in doc_paths {
+                    self.run_doc(&context, doc_path, &mut variables, &mut results)
+                        .await?;
+                }
+                results
+            }
+        };
+
+        Ok(Report {
+            results,
+            textual_markers: self.textual_markers,
+        })
+    }
+
+    /// Runs the test cases of a single document with its own copy of the variables.
+    async fn run_isolated(
+        &self,
+        context: &RunContext<'_>,
+        doc_path: &str,
+        mut variables: Variables,
+    ) -> Result<Vec<TestResult>, AssertionError> {
+        let mut results = vec![];
+        self.run_doc(context, doc_path, &mut variables, &mut results)
+            .await?;
+        Ok(results)
+    }
+
+    /// Runs the test cases of a single document in order, appending their results.
+    async fn run_doc(
+        &self,
+        context: &RunContext<'_>,
+        doc_path: &str,
+        variables: &mut Variables,
+        results: &mut Vec<TestResult>,
+    ) -> Result<(), AssertionError> {
+        let test_cases = parser::parse(doc_path.to_string(), self.strict_parsing)
+            .map_err(AssertionError::ParsingError)?;
+        for mut tc in test_cases {
+            tc.response.no_unexpected_errors = self.no_unexpected_errors;
+            tc.response.continue_on_capture_failure = self.continue_on_capture_failure;
+            tc.response.report_stale_example = self.report_stale_examples;
+            tc.response.base_body = self.base_response.clone();
+            tc.response.diff_format = DiffFormat {
+                color: match self.color {
+                    ColorChoice::Auto => std::io::stdout().is_terminal(),
+                    ColorChoice::Always => true,
+                    ColorChoice::Never => false,
+                },
+                compact: self.compact_diff,
+            };
+            tc.response.path_assertions = self.path_assertions.clone();
+            if let Some(authorization) = &self.authorization {
+                let documented = tc
+                    .request
+                    .headers
+                    .keys()
+                    .any(|name| name.eq_ignore_ascii_case(AUTHORIZATION.as_str()));
+                if !documented {
+                    tc.request
+                        .headers
+                        .insert(AUTHORIZATION.to_string(), authorization.clone());
+                }
+            }
+            let correlation_id = self.correlation_header.map(|header| {
+                let documented = tc
+                    .request
+                    .headers
+                    .iter()
+                    .find(|(name, _)| name.eq_ignore_ascii_case(header));
+                match documented {
+                    Some((_, value)) => value.clone(),
+                    None => {
+                        let id = variables.generator.uuid();
+                        tc.request.headers.insert(header.to_string(), id.clone());
+                        id
+                    }
+                }
+            });
+            if let (Some(delay), false) = (self.request_delay, results.is_empty()) {
+                tokio::time::sleep(delay).await;
+            }
+
+            let (method, uri, line) = (
+                tc.request.http_method.to_string(),
+                tc.request.uri.clone(),
+                tc.request.line_number,
+            );
+            let id = format!("{} {} ({}:{})", method, uri, doc_path, line);
+            #[cfg(feature = "openapi")]
+            let http_method = tc.request.http_method.clone();
+            let target = match &self.service {
+                Some(service) => Target::Service(service),
+                None => Target::Url(context.url, &context.http_options),
+            };
+            self.notify(TestEvent::Started { id: id.clone() });
+            let start = Instant::now();
+            #[cfg_attr(not(feature = "openapi"), allow(unused_variables))]
+            let (status, response_body, notes, error) =
+                match executor::execute(&target, tc, variables).await {
+                    Ok(execution) => (
+                        Some(execution.status),
+                        execution.response_body,
+                        execution.notes,
+                        None,
+                    ),
+                    Err(err) => (None, err.response_body.clone(), vec![], Some(err)),
+                };
+            let duration = start.elapsed();
+            #[cfg(feature = "openapi")]
+            let error = match (&context.openapi, &response_body, status) {
+                (Some(openapi), Some(body), Some(status)) => openapi
+                    .validate(&http_method, &uri, status, body)
+                    .err()
+                    .map(|violations| executor::ExecutionError {
+                        context: Some(format!(
+                            "error validating response from {} {} against the OpenAPI spec",
+                            http_method, uri
+                        )),
+                        failure: Failure::Schema(violations),
+                        response_body: response_body.clone(),
+                    }),
+                _ => error,
+            };
+            if let (Some(dir), Some(body)) = (self.response_dump_dir, &response_body) {
+                dump_response(dir, &id, body).map_err(AssertionError::ConfigurationError)?;
+            }
+            self.notify(match &error {
+                None => TestEvent::Passed { id: id.clone() },
+                Some(err) => TestEvent::Failed {
+                    id: id.clone(),
+                    error: err.to_string(),
+                },
+            });
+            results.push(TestResult {
+                id,
+                method,
+                uri,
+                doc_path: doc_path.to_string(),
+                line,
+                failure: error.as_ref().map(|e| e.to_string()),
+                failure_kind: error.map(|e| e.failure),
+                correlation_id,
+                notes,
+                duration,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Settings shared by the documents of a single run
+struct RunContext<'r> {
+    url: &'r str,
+    http_options: HttpOptions<'r>,
+    #[cfg(feature = "openapi")]
+    openapi: Option<openapi::OpenApi>,
+}
+
+fn dump_response(dir: &str, id: &str, body: &str) -> Result<(), String> {
+    let file_name = id
+        .split(|c: char| !c.is_ascii_alphanumeric() && c != '.' && c != '-')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("_");
+    let path = std::path::Path::new(dir).join(file_name);
+    std::fs::create_dir_all(dir)
+        .and_then(|_| std::fs::write(&path, body))
+        .map_err(|err| format!("error writing response to {}: {}", path.display(), err))
+}
+
+impl<'a> Default for DocAssert<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Report of the assertions
+///
+/// The report contains the total number of tests, the number of failed tests,
+/// a summary of passed and failed tests, and detailed information about
+/// the failed assertions.
+///
+/// # Examples
+///
+/// ```
+/// # #![allow(unused_mut)]
+/// use doc_assert::DocAssert;
+/// use doc_assert::Variables;
+///
+/// async fn test() {
+///     let mut doc_assert = DocAssert::new()
+///         .with_url("http://localhost:8080")
+///         .with_doc_path("path/to/README.md");
+///     match doc_assert.assert().await {
+///         Ok(report) => {
+///             println!("{}", report);
+///         }
+///         Err(err) => {
+///             // handle error
+///         }
+///     };
+/// }
+pub struct Report {
+    /// Results of the executed tests in order of execution
+    results: Vec<TestResult>,
+    /// Whether the results are marked with `PASS` and `FAIL` instead of emoji
+    textual_markers: bool,
+}
+
+impl Report {
+    /// Returns the results of the executed tests in order of execution.
+    pub fn results(&self) -> &[TestResult] {
+        &self.results
+    }
+
+    /// Returns the total number of tests.
+    pub fn total_count(&self) -> usize {
+        self.results.len()
+    }
+
+    /// Returns the number of failed tests.
+    pub fn failed_count(&self) -> usize {
+        self.results.iter().filter(|r| !r.passed()).count()
+    }
+
+    /// Renders the report as Markdown, e.g. for the job summary of GitHub Actions.
+    ///
+    /// Each test is a row of a table with its result and duration, followed by the details
+    /// of the failures in collapsible sections.
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = String::from("| Test | Result | Duration |\n| --- | --- | --- |\n");
+        for result in &self.results {
+            let outcome = match (self.textual_markers, result.passed()) {
+                (true, passed) => self.marker(passed).to_string(),
+                (false, true) => format!("{} passed", self.marker(true)),
+                (false, false) => format!("{} failed", self.marker(false)),
+            };
+            markdown.push_str(
+                format!(
+                    "| {} | {} | {}ms |\n",
+                    result.id.replace('|', "\\|"),
+                    outcome,
+                    result.duration.as_millis()
+                )
+                .as_str(),
+            );
+        }
+
+        markdown.push_str(
+            format!(
+                "\n**{} passed; {} failed**\n",
+                self.total_count() - self.failed_count(),
+                self.failed_count()
+            )
+            .as_str(),
+        );
+
+        for result in &self.results {
+            if let Some(failure) = &result.failure {
+                // the fence is longer than any run of backticks of a response body in the failure
+                let longest = failure
+                    .split(|c| c != '`')
+                    .map(str::len)
+                    .max()
+                    .unwrap_or_default();
+                let fence = "`".repeat(longest.max(2) + 1);
+                markdown.push_str(
+                    format!(
+                        "\n<details>\n<summary>{}</summary>\n\n{}\n{}\n{}\n\n</details>\n",
+                        escape_xml(&result.id),
+                        fence,
+                        failure,
+                        fence
+                    )
+                    .as_str(),
+                );
+            }
+        }
+
+        markdown
+    }
+
+    /// Renders the report as JUnit XML, e.g. for the test reports of GitLab CI.
+    ///
+    /// Each test is a `<testcase>` named after its identifier, and a failed one contains
+    /// a `<failure>` element with the details of the failure, without colors. The characters
+    /// XML doesn't allow, like the control characters of a response body, are replaced.
+    pub fn to_junit_xml(&self) -> String {
+        let duration = self.results.iter().map(|r| r.duration).sum::<Duration>();
+        let mut xml = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <testsuite name=\"doc-assert\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">\n",
+            self.total_count(),
+            self.failed_count(),
+            duration.as_secs_f64()
+        );
+        for result in &self.results {
+            let testcase = format!(
+                "  <testcase name=\"{}\" time=\"{:.3}\"",
+                escape_xml(&result.id),
+                result.duration.as_secs_f64()
+            );
+            match result.failure.as_deref().map(strip_colors) {
+                None => xml.push_str(format!("{}/>\n", testcase).as_str()),
+                Some(failure) => xml.push_str(
+                    format!(
+                        "{}>\n    <failure message=\"{}\">{}</failure>\n  </testcase>\n",
+                        testcase,
+                        escape_xml(failure.lines().next().unwrap_or_default()),
+                        escape_xml(&failure)
+                    )
+                    .as_str(),
+                ),
+            }
+        }
+        xml.push_str("</testsuite>\n");
+
+        xml
+    }
+
+    /// Renders the report as JSON for further processing by scripts.
+    ///
+    /// The report is an object with the numbers of tests and a `results` array describing
+    /// each test with its request, location in the documentation and failure.
+    pub fn to_json(&self) -> String {
+        let outcomes = self
+            .results
+            .iter()
+            .map(|result| TestOutcome {
+                id: &result.id,
+                method: &result.method,
+                uri: &result.uri,
+                doc_path: &result.doc_path,
+                line: result.line,
+                passed: result.passed(),
+                failure: result.failure(),
+                duration_ms: result.duration.as_millis(),
+            })
+            .collect();
+        let report = JsonReport {
+            total: self.total_count(),
+            passed: self.total_count() - self.failed_count(),
+            failed: self.failed_count(),
+            results: outcomes,
+        };
+
+        serde_json::to_string_pretty(&report).expect("report is serializable")
+    }
+
+    /// Returns the marker of the result of a test.
+    fn marker(&self, passed: bool) -> &'static str {
+        match (self.textual_markers, passed) {
+            (true, true) => "PASS",
+            (true, false) => "FAIL",
+            (false, true) => "✅",
+            (false, false) => "❌",
+        }
+    }
+
+    /// Returns the ids of the tests which passed in one report and failed in the other,
+    /// followed by the ids of the tests found in one report only.
+    fn flipped(&self, other: &Report) -> Vec<String> {
+        let outcomes = |report: &Report| {
+            report
+                .results
+                .iter()
+                .map(|result| (result.id.clone(), result.passed()))
+                .collect::<HashMap<_, _>>()
+        };
+        let (own, others) = (outcomes(self), outcomes(other));
+        let changed = self
+            .results
+            .iter()
+            .filter(|result| others.get(&result.id) != Some(&result.passed()));
+        let added = other
+            .results
+            .iter()
+            .filter(|result| !own.contains_key(&result.id));
+        changed
+            .chain(added)
+            .map(|result| result.id.clone())
+            .collect()
+    }
+}
+
+/// Report serialized by [`Report::to_json`]
+#[derive(Serialize)]
+struct JsonReport<'r> {
+    total: usize,
+    passed: usize,
+    failed: usize,
+    results: Vec<TestOutcome<'r>>,
+}
+
+/// Result of a single test serialized by [`Report::to_json`]
+#[derive(Serialize)]
+struct TestOutcome<'r> {
+    id: &'r str,
+    method: &'r str,
+    uri: &'r str,
+    doc_path: &'r str,
+    line: usize,
+    passed: bool,
+    failure: Option<&'r str>,
+    duration_ms: u128,
+}
+
+/// Escapes the characters which can't appear in XML or HTML text and attribute values and replaces
+/// the ones XML 1.0 forbids, like the control characters of a response body, with `\u{fffd}`.
+fn escape_xml(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '\t' | '\n' | '\r' => c,
+            '\u{0}'..='\u{1f}' | '\u{fffe}' | '\u{ffff}' => '\u{fffd}',
+            c => c,
+        })
+        .collect::<String>()
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Removes the ANSI color codes of the differences colored with [`ColorChoice::Always`].
+fn strip_colors(text: &str) -> String {
+    regex::Regex::new(r"\x1b\[[0-9;]*m")
+        .unwrap()
+        .replace_all(text, "")
+        .to_string()
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let total_count = self.total_count();
+        let failed_count = self.failed_count();
+
+        let mut summary = String::new();
+        let mut failures = String::new();
+        let mut notes = String::new();
+        for result in &self.results {
+            for note in &result.notes {
+                notes.push_str(format!("{}: {}\n", result.id, note).as_str());
+            }
+            match &result.failure {
+                None => summary.push_str(format!("{} {}\n", result.id, self.marker(true)).as_str()),
+                Some(err) => {
+                    summary.push_str(format!("{} {}\n", result.id, self.marker(false)).as_str());
+                    failures.push_str(format!("-------------\n{}: {}\n", result.id, err).as_str());
+                    if let Some(correlation_id) = &result.correlation_id {
+                        failures.push_str(format!("correlation id: {}\n", correlation_id).as_str());
+                    }
+                }
+            }
+        }
+
+        if !notes.is_empty() {
+            summary.push_str(format!("\nnotes:\n{}", notes).as_str());
+        }
+
+        if failed_count > 0 {
+            write!(
+                f,
+                "{} tests\n{}\nfailures:\n{}\ntest result: FAILED. {} passed; {} failed",
+                total_count,
+                summary,
+                failures,
+                total_count - failed_count,
+                failed_count
+            )
+        } else {
+            write!(
+                f,
+                "{} tests\n{}\ntest result: PASSED. {} passed; 0 failed",
+                total_count, summary, total_count
+            )
+        }
+    }
+}
+
+/// How the redirects returned by the API are handled, set with [`DocAssert::with_redirect_policy`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RedirectPolicy {
+    /// Follows up to 10 redirects
+    #[default]
+    Follow,
+    /// Follows up to the given number of redirects
+    Limited(usize),
+    /// Returns the redirect responses instead of following them
+    None,
+}
+
+/// Progress of the tests reported to the observer of [`DocAssert::assert_with_observer`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TestEvent {
+    /// The test is about to send its request
+    Started {
+        /// Identifier of the test, the same as in the report
+        id: String,
+    },
+    /// The test passed
+    Passed {
+        /// Identifier of the test, the same as in the report
+        id: String,
+    },
+    /// The test failed
+    Failed {
+        /// Identifier of the test, the same as in the report
+        id: String,
+        /// Description of the failure
+        error: String,
+    },
+    /// All the tests completed, not sent when the run stops on a configuration or parsing error
+    Finished {
+        /// Number of passed tests
+        passed: usize,
+        /// Number of failed tests
+        failed: usize,
+    },
+}
+
+/// When the body differences are colored, set with [`DocAssert::with_color`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    /// Colors the differences if the standard output is a terminal
+    Auto,
+    /// Always colors the differences
+    Always,
+    /// Never colors the differences
+    #[default]
+    Never,
+}
+
+/// Aspect of the test which failed
+///
+/// The `Display` implementation provides a human readable description of the failure.
+/// More aspects may be added, so a match on the failure needs a wildcard arm.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum Failure {
+    /// The request could not be prepared or executed
+    Request(String),
+    /// The response status code differs from the expected one
+    Status {
+        /// Expected status code
+        expected: u16,
+        /// Actual status code
+        actual: u16,
+    },
+    /// The response status code is outside of the expected class or range
+    StatusRange {
+        /// Expected class or range of status codes, e.g. `2xx` or `200-299`
+        expected: String,
+        /// Actual status code
+        actual: u16,
+    },
+    /// An expected header is missing or has an unexpected value
+    Header(String),
+    /// The response body differs from the expected one, contains every difference found
+    Body(Vec<String>),
+    /// The response body does not match the schema, contains every violation found
+    Schema(Vec<String>),
+    /// Any other assertion on the response failed
+    Response(String),
+}
+
+impl From<String> for Failure {
+    fn from(err: String) -> Self {
+        Failure::Response(err)
+    }
+}
+
+impl Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Failure::Request(err) | Failure::Header(err) | Failure::Response(err) => {
+                write!(f, "{}", err)
+            }
+            Failure::Status { expected, actual } => {
+                write!(f, "expected response code {}, got {}", expected, actual)
+            }
+            Failure::StatusRange { expected, actual } => {
+                write!(f, "expected response code {}, got {}", expected, actual)
+            }
+            Failure::Body(differences) => write!(
+                f,
+                "expected response differs from actual {}",
+                differences.join("\n")
+            ),
+            Failure::Schema(violations) => write!(
+                f,
+                "response does not match the schema {}",
+                violations.join("\n")
+            ),
+        }
+    }
+}
+
+/// Result of a single test
+///
+/// A test is a single request defined in the documentation
+/// together with its expected response.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TestResult {
+    /// Identifier of the test consisting of the HTTP method, URI, doc path and line number
+    id: String,
+    /// HTTP method of the request
+    method: String,
+    /// URI of the request
+    uri: String,
+    /// Path of the documentation the test is defined in
+    doc_path: String,
+    /// Line number of the request in the documentation
+    line: usize,
+    /// Detailed information about the failed assertion
+    failure: Option<String>,
+    /// Aspect of the test which failed
+    failure_kind: Option<Failure>,
+    /// Identifier sent in the correlation header
+    correlation_id: Option<String>,
+    /// Informational notes which don't fail the test
+    notes: Vec<String>,
+    /// Time it took to execute the test including the retries
+    duration: Duration,
+}
+
+impl TestResult {
+    /// Returns the identifier of the test.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Returns the HTTP method of the request.
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    /// Returns the URI of the request.
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+
+    /// Returns the path of the documentation the test is defined in.
+    pub fn doc_path(&self) -> &str {
+        &self.doc_path
+    }
+
+    /// Returns the line number of the request in the documentation.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Returns `true` if the test passed.
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+
+    /// Returns detailed information about the failed assertion.
+    pub fn failure(&self) -> Option<&str> {
+        self.failure.as_deref()
+    }
+
+    /// Returns the aspect of the test which failed.
+    pub fn failure_kind(&self) -> Option<&Failure> {
+        self.failure_kind.as_ref()
+    }
+
+    /// Returns the identifier sent in the header set with [`DocAssert::with_correlation_header`].
+    pub fn correlation_id(&self) -> Option<&str> {
+        self.correlation_id.as_deref()
+    }
+
+    /// Returns the informational notes, e.g. about an example which is stale in the ignored fields
+    /// when the run is configured with [`DocAssert::with_stale_example_report`].
+    pub fn notes(&self) -> &[String] {
+        &self.notes
+    }
+
+    /// Returns the time it took to execute the test including the retries.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+/// Test declared in the documentation, as returned by [`DocAssert::parse_only`]
+///
+/// Only the request and the main expectations of the response are exposed, the values are the
+/// documented ones with the placeholders left in place.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocumentedTest {
+    doc_path: String,
+    line: usize,
+    method: String,
+    uri: String,
+    headers: HashMap<String, String>,
+    body: Option<String>,
+    code: String,
+    ignore_paths: Vec<String>,
+}
+
+impl DocumentedTest {
+    fn new(doc_path: &str, tc: &TestCase) -> Self {
+        Self {
+            doc_path: doc_path.to_string(),
+            line: tc.request.line_number,
+            method: tc.request.http_method.to_string(),
+            uri: tc.request.uri.clone(),
+            headers: tc.request.headers.clone(),
+            body: tc.request.body.clone(),
+            code: tc.response.code.to_string(),
+            ignore_paths: tc.response.ignore_paths.clone(),
+        }
+    }
+
+    /// Returns the path of the documentation the test is defined in.
+    pub fn doc_path(&self) -> &str {
+        &self.doc_path
+    }
+
+    /// Returns the line number of the request in the documentation.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Returns the HTTP method of the request.
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    /// Returns the URI of the request, without the base path.
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+
+    /// Returns the headers of the request.
+    pub fn headers(&self) -> &HashMap<String, String> {
+        &self.headers
+    }
+
+    /// Returns the body of the request, if it has a textual one.
+    pub fn body(&self) -> Option<&str> {
+        self.body.as_deref()
+    }
+
+    /// Returns the expected status code of the response as documented, e.g. `200`, or `2xx`
+    /// and `200-299` for any status of a class or range.
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    /// Returns the paths ignored in the response body.
+    pub fn ignore_paths(&self) -> &[String] {
+        &self.ignore_paths
+    }
+}
+
+/// Request dispatched to the in-process handler set with [`DocAssert::with_service`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServiceRequest {
+    method: String,
+    uri: String,
+    headers: HashMap<String, String>,
+    body: Option<Vec<u8>>,
+}
+
+impl ServiceRequest {
+    pub(crate) fn new(
+        method: String,
+        uri: String,
+        headers: HashMap<String, String>,
+        body: Option<Vec<u8>>,
+    ) -> Self {
+        Self {
+            method,
+            uri,
+            headers,
+            body,
+        }
+    }
+
+    /// Returns the HTTP method of the request.
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    /// Returns the URI of the request as defined in the documentation, with variables replaced.
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+
+    /// Returns the value of the header with the given name, ignoring the case of the name.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the body of the request.
+    pub fn body(&self) -> Option<&[u8]> {
+        self.body.as_deref()
+    }
+}
+
+/// Response returned by the in-process handler set with [`DocAssert::with_service`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServiceResponse {
+    pub(crate) status: u16,
+    pub(crate) headers: Vec<(String, String)>,
+    pub(crate) body: String,
+}
+
+impl ServiceResponse {
+    /// Constructs a new response with the given status code and an empty body.
+    pub fn new(status: u16) -> Self {
+        Self {
+            status,
+            headers: vec![],
+            body: String::new(),
+        }
+    }
+
+    /// Adds a header to the response.
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Sets the body of the response.
+    pub fn with_body(mut self, body: &str) -> Self {
+        self.body = body.to_string();
+        self
+    }
+}
+
+/// Error type for DocAssert run
+pub enum AssertionError {
+    /// Invalid configuration of the run
+    ConfigurationError(String),
+    /// Error parsing the documentation file
+    ParsingError(ParseError),
+    /// Error executing tests
+    TestSuiteError(Report),
+    /// Tests which passed in one run and failed in the other
+    NondeterminismError(Vec<String>),
+    /// Errors found validating the documentation without running it
+    ValidationError(Vec<ParseError>),
+}
+
+/// Error in the documentation together with its location
+///
+/// The line is the one of the misplaced or invalid annotation, or the first line
+/// of the invalid code block. Errors concerning the whole file are located at line 0.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    file: String,
+    line: usize,
+    message: String,
+}
+
+impl ParseError {
+    pub(crate) fn new(file: &str, line: usize, message: String) -> Self {
+        Self {
+            file: file.to_string(),
+            line,
+            message,
+        }
+    }
+
+    /// Returns the path of the documentation file.
+    pub fn file(&self) -> &str {
+        &self.file
+    }
+
+    /// Returns the line number of the error, starting from 1, or 0 for errors
+    /// concerning the whole file.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Returns the description of the error.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            0 => write!(f, "{}: {}", self.file, self.message),
+            line => write!(f, "{}:{}: {}", self.file, line, self.message),
+        }
+    }
+}
+
+/// Variables to be used in the request and response bodies.
+///
+/// The variables are used to replace placeholders in the request
+/// and response bodies in case some values need to be shared between requests and responses.
+///
+/// # Examples
+///
+/// Variables can be passed one by one with specified type:
+///
+/// ```
+/// # use doc_assert::Variables;
+/// # use serde_json::Value;
+/// let mut variables = Variables::new();
+/// variables.insert_string("name".to_string(), "John".to_string());
+/// variables.insert_int("age".to_string(), 30);
+/// ```
+///
+/// A `Value` can be passed directly:
+///
+/// ```
+/// # use doc_assert::Variables;
+/// # use serde_json::Value;
+/// let mut variables = Variables::new();
+/// variables.insert_value("name".to_string(), Value::String("John".to_string()));
+/// variables.insert_value("age".to_string(), Value::Number(serde_json::Number::from(30)));
+/// ```
+///
+/// Alternatively, they can be passed as a JSON object:
+///
+/// ```
+/// # use doc_assert::Variables;
+/// # use serde_json::Value;
+/// let json = r#"{"name": "John", "age": 30}"#;
+/// let variables = Variables::from_json(&serde_json::from_str(json).unwrap()).unwrap();
+/// ```
+///
+#[derive(Debug, Clone, Default)]
+pub struct Variables {
+    map: HashMap<String, Value>,
+    functions: Functions,
+    generator: Generator,
+}
+
+impl Variables {
+    /// Constructs a new `Variables`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use doc_assert::Variables;
+    /// let variables = Variables::new();
+    /// ```
+    pub fn new() -> Self {
+        Self {
+            map: HashMap::new(),
+            functions: Functions::default(),
+            generator: Generator::default(),
+        }
+    }
+
+    /// Constructs a new `Variables` from a JSON object.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use doc_assert::Variables;
+    /// # use serde_json::Value;
+    /// let json = r#"{"name": "John", "age": 30}"#;
+    /// let variables = Variables::from_json(&serde_json::from_str(json).unwrap()).unwrap();
+    /// ```
+    pub fn from_json(json: &Value) -> Result<Self, String> {
+        let mut map = HashMap::new();
+
+        if let Value::Object(obj) = json {
+            for (key, value) in obj {
+                map.insert(key.clone(), value.clone());
+            }
+        } else {
+            return Err("variables must be an object".to_string());
+        }
+
+        Ok(Self {
+            map,
+            functions: Functions::default(),
+            generator: Generator::default(),
+        })
+    }
+
+    /// Inserts a `Value` into the `Variables`.
+    ///
+    /// This can be useful when more complex types are needed.
+    /// Since `Variables` is a wrapper around `HashMap` if you insert duplicate
+    /// keys the value will be overwritten.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use doc_assert::Variables;
+    /// # use serde_json::Value;
+    /// let mut variables = Variables::new();
+    /// variables.insert_value("name".to_string(), Value::String("John".to_string()));
+    /// ```
+    pub fn insert_value(&mut self, name: String, value: Value) {
+        self.map.insert(name, value);
+    }
+
+    /// Inserts a `String` into the `Variables`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use doc_assert::Variables;
+    /// let mut variables = Variables::new();
+    /// variables.insert_string("name".to_string(), "John".to_string());
+    /// ```
+    pub fn insert_string(&mut self, name: String, value: String) {
+        self.map.insert(name, Value::String(value));
+    }
+
+    /// Inserts an `i64` into the `Variables`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use doc_assert::Variables;
+    /// let mut variables = Variables::new();
+    /// variables.insert_int("age".to_string(), 30);
+    /// ```
+    pub fn insert_int(&mut self, name: String, value: i64) {
+        self.map
+            .insert(name, Value::Number(serde_json::Number::from(value)));
+    }
+
+    /// Inserts an `f64` into the `Variables`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use doc_assert::Variables;
+    /// let mut variables = Variables::new();
+    /// variables.insert_float("age".to_string(), 30.0);
+    /// ```
+    pub fn insert_float(&mut self, name: String, value: f64) {
+        self.map.insert(
+            name,
+            Value::Number(serde_json::Number::from_f64(value).unwrap()),
+        );
+    }
+
+    /// Inserts a `bool` into the `Variables`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use doc_assert::Variables;
+    /// let mut variables = Variables::new();
+    /// variables.insert_bool("is_adult".to_string(), true);
+    /// ```
+    pub fn insert_bool(&mut self, name: String, value: bool) {
+        self.map.insert(name, Value::Bool(value));
+    }
+
+    /// Inserts a `null` into the `Variables`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use doc_assert::Variables;
+    /// let mut variables = Variables::new();
+    /// variables.insert_null("name".to_string());
+    /// ```
+    pub fn insert_null(&mut self, name: String) {
+        self.map.insert(name, Value::Null);
+    }
+
+    pub(crate) fn extend(&mut self, other: &Variables) {
+        self.map
+            .extend(other.map.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+
+    pub(crate) fn obtain_from_response(
+        &mut self,
+        response: &Value,
+        variable_templates: &HashMap<String, Capture>,
+        continue_on_failure: bool,
+    ) -> Result<(), String> {
+        let mut errors = vec![];
+        for (name, capture) in variable_templates {
+            match capture_value(name, capture, response) {
+                Ok(value) => {
+                    self.map.insert(name.clone(), value);
+                }
+                Err(err) if continue_on_failure => errors.push(err),
+                Err(err) => return Err(err),
+            }
+        }
+
+        if errors.is_empty() {
+            return Ok(());
+        }
+        errors.sort();
+        Err(errors.join(", "))
+    }
+
+    fn replace_placeholders(
+        &mut self,
+        input: &mut String,
+        trim_quotes: bool,
+    ) -> Result<(), String> {
+        replace_env_placeholders(input, false)?;
+        replace_text_placeholders(input, &self.map, trim_quotes);
+        let calls = self.call_functions(input)?;
+        replace_text_placeholders(input, &calls, trim_quotes);
+        self.replace_generated_values(input, trim_quotes)?;
+
+        if input.contains('`') {
+            return Err(format!("unresolved variable placeholders in {}", input));
+        }
+
+        Ok(())
+    }
+
+    /// Evaluates the calls of the registered functions like `` `sign(1700000000, /blog)` ``
+    /// found in the input and returns their results keyed by the call.
+    fn call_functions(&self, input: &str) -> Result<HashMap<String, Value>, String> {
+        let mut calls = HashMap::new();
+        if self.functions.0.is_empty() {
+            return Ok(calls);
+        }
+
+        let re = regex::Regex::new(r"`(?<call>(?<name>[\w-]+)\((?<args>[^`()]*)\))`").unwrap();
+        for caps in re.captures_iter(input) {
+            let Some(function) = self.functions.0.get(&caps["name"]) else {
+                continue;
+            };
+            let args = caps["args"]
+                .split(',')
+                .map(str::trim)
+                .filter(|arg| !arg.is_empty())
+                .map(|arg| match self.map.get(arg) {
+                    Some(value) => value.clone(),
+                    None => serde_json::from_str(arg).unwrap_or(Value::String(arg.to_string())),
+                })
+                .collect::<Vec<_>>();
+            calls.insert(caps["call"].to_string(), function(&args));
+        }
+
+        Ok(calls)
+    }
+
+    /// Replaces each call of a built-in generator, which wasn't overridden by a registered
+    /// function, with a newly generated value.
+    fn replace_generated_values(
+        &mut self,
+        input: &mut String,
+        trim_quotes: bool,
+    ) -> Result<(), String> {
+        let re = regex::Regex::new(r"`(?<name>uuid|now|randomInt)\((?<args>[^`()]*)\)`").unwrap();
+        replace_each(input, &re, |caps| {
+            let args = caps["args"]
+                .split(',')
+                .map(str::trim)
+                .filter(|arg| !arg.is_empty())
+                .collect::<Vec<_>>();
+            let value = self.generator.generate(&caps["name"], &args)?;
+            Ok(text_value(&value, trim_quotes))
+        })
+    }
+
+    pub(crate) fn replace_request_placeholders(
+        &mut self,
+        input: &mut Request,
+    ) -> Result<(), String> {
+        self.replace_placeholders(&mut input.uri, true)?;
+
+        if let Some(body) = &mut input.body {
+            self.replace_placeholders(body, false)?;
+        }
+
+        for field in input.multipart.iter_mut().flatten() {
+            if let MultipartField::Text { value, .. } = field {
+                self.replace_placeholders(value, true)?;
+            }
+        }
+
+        for (_, value) in input.form.iter_mut().flatten() {
+            self.replace_placeholders(value, true)?;
+        }
+
+        for (_, value) in &mut input.headers.iter_mut() {
+            self.replace_placeholders(value, true)?;
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the placeholders in a JSON document. Placeholders inside string literals
+    /// are replaced with the value as string content, the others with the JSON value.
+    fn replace_json_placeholders(&self, input: &mut String) -> Result<(), String> {
+        replace_env_placeholders(input, true)?;
+        let output = replace_json_values(input, &self.map)?;
+        let calls = self.call_functions(&output)?;
+        let output = replace_json_values(&output, &calls)?;
+
+        if output.contains('`') {
+            return Err(format!("unresolved variable placeholders in {}", output));
+        }
+
+        *input = output;
+        Ok(())
+    }
+
+    pub(crate) fn replace_response_placeholders(
+        &mut self,
+        input: &mut Response,
+    ) -> Result<(), String> {
+        match &mut input.body {
+            Some(body) if input.text => self.replace_placeholders(body, true)?,
+            Some(body) => self.replace_json_placeholders(body)?,
+            None => {}
+        }
+
+        for (_, value) in &mut input.headers.iter_mut() {
+            self.replace_placeholders(value, true)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn capture_value(name: &str, capture: &Capture, response: &Value) -> Result<Value, String> {
+    let (Capture::Value(path) | Capture::Length(path)) = capture;
+    let value = extract_value(path, response)
+        .ok_or_else(|| format!("variable template {} not found in the response body", name))?;
+
+    match capture {
+        Capture::Value(_) => Ok(value),
+        Capture::Length(_) => value
+            .as_array()
+            .map(|array| array.len().into())
+            .ok_or_else(|| format!("variable template {} expects an array, got {}", name, value)),
+    }
+}
+
+/// Placeholder of an environment variable, like `${env.API_TOKEN}`
+const ENV_PLACEHOLDER: &str = r"\$\{env\.(?<name>[A-Za-z_][A-Za-z0-9_]*)\}";
+
+/// Replaces the placeholders like `${env.API_TOKEN}` with the values of the environment variables.
+/// In JSON documents the values are escaped to be used inside string literals.
+fn replace_env_placeholders(input: &mut String, json: bool) -> Result<(), String> {
+    if !input.contains("${env.") {
+        return Ok(());
+    }
+
+    let re = regex::Regex::new(ENV_PLACEHOLDER).unwrap();
+    replace_each(input, &re, |caps| {
+        let value = std::env::var(&caps["name"])
+            .map_err(|_| format!("environment variable {} is not set", &caps["name"]))?;
+        if !json {
+            return Ok(value);
+        }
+        let content = serde_json::to_string(&value).map_err(|e| e.to_string())?;
+        Ok(content[1..content.len() - 1].to_string())
+    })
+}
+
+/// Replaces each match of the regular expression with its own replacement.
+fn replace_each(
+    input: &mut String,
+    re: &regex::Regex,
+    mut replacement: impl FnMut(&regex::Captures) -> Result<String, String>,
+) -> Result<(), String> {
+    let mut output = String::with_capacity(input.len());
+    let mut last = 0;
+    for caps in re.captures_iter(input) {
+        let matched = caps.get(0).unwrap();
+        output.push_str(&input[last..matched.start()]);
+        output.push_str(&replacement(&caps)?);
+        last = matched.end();
+    }
+    output.push_str(&input[last..]);
+
+    *input = output;
+    Ok(())
+}
+
+/// Formats the value as text, strings without the quotes if trimmed.
+fn text_value(value: &Value, trim_quotes: bool) -> String {
+    // strings are inserted as they are, so quotes inside them, like in ETags, are kept
+    match value {
+        Value::String(s) if trim_quotes => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// Replaces the placeholders of the given values as plain text.
+fn replace_text_placeholders(
+    input: &mut String,
+    values: &HashMap<String, Value>,
+    trim_quotes: bool,
+) {
+    for (name, value) in values {
+        let placeholder = format!("`{}`", name);
+        *input = input.replace(&placeholder, &text_value(value, trim_quotes));
+    }
+}
+
+/// Replaces the placeholders of the given values in a JSON document.
+fn replace_json_values(input: &str, values: &HashMap<String, Value>) -> Result<String, String> {
+    let mut output = String::with_capacity(input.len());
+    let (mut in_string, mut escaped) = (false, false);
+    let mut chars = input.char_indices();
+    while let Some((idx, c)) = chars.next() {
+        if c == '`' {
+            let rest = &input[idx + 1..];
+            let name = rest.find('`').map(|end| &rest[..end]);
+            if let Some((name, value)) = name.and_then(|name| Some((name, values.get(name)?))) {
+                if in_string {
+                    let content = match value {
+                        Value::String(s) => serde_json::to_string(s),
+                        value => serde_json::to_string(&value.to_string()),
+                    }
+                    .map_err(|e| e.to_string())?;
+                    output.push_str(&content[1..content.len() - 1]);
+                } else {
+                    output.push_str(&value.to_string());
+                }
+                chars.nth(name.chars().count());
+                continue;
+            }
+        }
+
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            _ => {}
+        }
+        output.push(c);
+    }
+
+    Ok(output)
+}
+
+pub(crate) fn extract_value(path: &Path, value: &Value) -> Option<Value> {
+    match path {
+        Path::Root => Some(value.clone()),
+        Path::Keys(keys) => {
+            let mut current = value;
+            for key in keys {
+                match key {
+                    Key::Field(field) => current = current.get(field)?,
+                    Key::Idx(index) => current = current.get(index)?,
+                    _ => return None,
+                }
+            }
+            Some(current.clone())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    use serde_json::{json, Value};
+
+    use crate::{
+        iso8601, AssertionError, DocAssert, Failure, Generator, RedirectPolicy, Report,
+        ServiceRequest, ServiceResponse, TestEvent, TestResult, Variables,
+    };
+
+    #[tokio::test]
+    async fn test_determinism_check() {
+        let mut server = mockito::Server::new();
+        server.mock("GET", "/stable").with_status(200).create();
+        server
+            .mock("GET", "/flaky")
+            .with_status(200)
+            .expect(1)
+            .create();
+        server.mock("GET", "/flaky").with_status(500).create();
+
+        let url = server.url();
+        let result = DocAssert::new()
+            .with_url(url.as_str())
+            .with_doc_path("tests/data/flaky.md")
+            .with_determinism_check(true)
+            .assert()
+            .await;
+
+        match result {
+            Err(AssertionError::NondeterminismError(flipped)) => {
+                assert_eq!(flipped, vec!["GET /flaky (tests/data/flaky.md:11)"]);
+            }
+            _ => panic!("expected nondeterminism error"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_determinism_check_generated_values() {
+        // only the first generated id is found, so the test passes as long as it is replayed
+        let doc_assert = || {
+            let first = Arc::new(Mutex::new(None::<String>));
+            DocAssert::new()
+                .with_doc_path("tests/data/generated.md")
+                .with_determinism_check(true)
+                .with_service(move |request: ServiceRequest| {
+                    let first = first.clone();
+                    async move {
+                        let mut first = first.lock().unwrap();
+                        match first.get_or_insert_with(|| request.uri().to_string()) {
+                            uri if uri == request.uri() => ServiceResponse::new(200),
+                            _ => ServiceResponse::new(404),
+                        }
+                    }
+                })
+        };
+
+        let result = doc_assert().assert().await;
+        let Err(AssertionError::NondeterminismError(flipped)) = result else {
+            panic!("expected the rerun to generate another id");
+        };
+        assert_eq!(
+            flipped,
+            vec!["GET /orders/`uuid()` (tests/data/generated.md:3)"]
+        );
+
+        let result = doc_assert().with_random_seed(42).assert().await;
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_report_flipped() {
+        let result = |id: &str, failure: Option<&str>| TestResult {
+            id: id.to_string(),
+            failure: failure.map(|f| f.to_string()),
+            ..TestResult::default()
+        };
+        let report = |results| Report {
+            results,
+            textual_markers: false,
+        };
+        let first = report(vec![
+            result("GET /a (README.md:3)", None),
+            result("GET /b (README.md:9)", None),
+            result("GET /c (README.md:15)", Some("failed")),
+        ]);
+        let second = report(vec![
+            result("GET /b (README.md:9)", Some("failed")),
+            result("GET /a (README.md:3)", None),
+            result("GET /d (README.md:21)", None),
+        ]);
+        assert_eq!(
+            first.flipped(&second),
+            vec![
+                "GET /b (README.md:9)",
+                "GET /c (README.md:15)",
+                "GET /d (README.md:21)"
+            ]
+        );
+        assert!(first.flipped(&first).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_determinism_check_stable() {
+        let mut server = mockito::Server::new();
+        server.mock("GET", "/stable").with_status(200).create();
+        server.mock("GET", "/flaky").with_status(200).create();
+
+        let url = server.url();
+        let result = DocAssert::new()
+            .with_url(url.as_str())
+            .with_doc_path("tests/data/flaky.md")
+            .with_determinism_check(true)
+            .assert()
+            .await;
+
+        match result {
+            Ok(report) => assert_eq!(report.total_count(), 2),
+            _ => panic!("expected stable test suite to pass"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stages_teardown_runs_on_failure() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("POST", "/session")
+            .with_status(201)
+            .with_body(r#"{"token": "abc"}"#)
+            .create();
+        server.mock("GET", "/items/abc").with_status(500).create();
+        server.mock("GET", "/items").with_status(200).create();
+        let teardown = server
+            .mock("DELETE", "/session/abc")
+            .with_status(204)
+            .expect(1)
+            .create();
+
+        let url = server.url();
+        let result = DocAssert::new()
+            .with_url(url.as_str())
+            .with_doc_path("tests/data/stages.md")
+            .assert()
+            .await;
+
+        teardown.assert();
+        match result {
+            Err(AssertionError::TestSuiteError(report)) => {
+                let outcomes = report
+                    .results()
+                    .iter()
+                    .map(|r| (r.id(), r.passed()))
+                    .collect::<Vec<_>>();
+                assert_eq!(
+                    outcomes,
+                    vec![
+                        ("POST /session (tests/data/stages.md:33)", true),
+                        ("GET /items/`token` (tests/data/stages.md:15)", false),
+                        ("GET /items (tests/data/stages.md:23)", true),
+                        ("DELETE /session/`token` (tests/data/stages.md:5)", true),
+                    ]
+                );
+            }
+            _ => panic!("expected test suite error"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_request_delay() {
+        let mut server = mockito::Server::new();
+        server.mock("GET", "/stable").with_status(200).create();
+        server.mock("GET", "/flaky").with_status(200).create();
+
+        let url = server.url();
+        let start = Instant::now();
+        let result = DocAssert::new()
+            .with_url(url.as_str())
+            .with_doc_path("tests/data/flaky.md")
+            .with_doc_path("tests/data/flaky.md")
+            .with_request_delay(Duration::from_millis(100))
+            .assert()
+            .await;
+
+        assert!(result.is_ok());
+        // four tests with a delay between each of them
+        assert!(start.elapsed() >= Duration::from_millis(300));
+    }
+
+    #[tokio::test]
+    async fn test_environment_variables() {
+        let mut server = mockito::Server::new();
+        let staging = server
+            .mock("GET", "/users/1")
+            .with_status(200)
+            .expect(1)
+            .create();
+        let production = server
+            .mock("GET", "/users/42")
+            .with_status(200)
+            .expect(1)
+            .create();
+
+        let url = server.url();
+        for environment in ["staging", "production"] {
+            let mut staging_variables = Variables::new();
+            staging_variables.insert_int("user_id".to_string(), 1);
+            let mut production_variables = Variables::new();
+            production_variables.insert_int("user_id".to_string(), 42);
+
+            let result = DocAssert::new()
+                .with_url(url.as_str())
+                .with_doc_path("tests/data/environments.md")
+                .with_environment_variables("staging", staging_variables)
+                .with_environment_variables("production", production_variables)
+                .with_environment(environment)
+                .assert()
+                .await;
+
+            assert!(result.is_ok());
+        }
+
+        staging.assert();
+        production.assert();
+
+        let result = DocAssert::new()
+            .with_url(url.as_str())
+            .with_doc_path("tests/data/environments.md")
+            .with_environment("development")
+            .assert()
+            .await;
+
+        assert!(matches!(result, Err(AssertionError::ConfigurationError(_))));
+    }
+
+    #[test]
+    fn test_report_to_markdown() {
+        let result = |id: &str, failure: Option<&str>, millis| TestResult {
+            id: id.to_string(),
+            failure: failure.map(|f| f.to_string()),
+            failure_kind: failure.map(|f| Failure::Response(f.to_string())),
+            duration: Duration::from_millis(millis),
+            ..TestResult::default()
+        };
+        let report = Report {
+            results: vec![
+                result("GET /blog (README.md:3)", None, 12),
+                result(
+                    "GET /blog?tags=a|b (README.md:15)",
+                    Some("expected response code 200, got 500"),
+                    40,
+                ),
+            ],
+            textual_markers: false,
+        };
+
+        assert_eq!(
+            report.to_markdown(),
+            "| Test | Result | Duration |
+| --- | --- | --- |
+| GET /blog (README.md:3) | ✅ passed | 12ms |
+| GET /blog?tags=a\\|b (README.md:15) | ❌ failed | 40ms |
+
+**1 passed; 1 failed**
+
+<details>
+<summary>GET /blog?tags=a|b (README.md:15)</summary>
+
+```
+expected response code 200, got 500
+```
+
+</details>
+"
+        );
+
+        let report = Report {
+            results: vec![result(
+                "GET /search?q=<b>&lang=en (README.md:21)",
+                Some("expected response differs from actual\n```\n<missing> \"title\""),
+                5,
+            )],
+            textual_markers: false,
+        };
+        assert!(report.to_markdown().ends_with(
+            "<details>
+<summary>GET /search?q=&lt;b&gt;&amp;lang=en (README.md:21)</summary>
+
+````
+expected response differs from actual
+```
+<missing> \"title\"
+````
+
+</details>
+"
+        ));
+    }
+
+    #[test]
+    fn test_report_to_junit_xml() {
+        let result = |id: &str, failure: Option<&str>, millis| TestResult {
+            id: id.to_string(),
+            failure: failure.map(|f| f.to_string()),
+            failure_kind: failure.map(|f| Failure::Response(f.to_string())),
+            duration: Duration::from_millis(millis),
+            ..TestResult::default()
+        };
+        let report = Report {
+            results: vec![
+                result("GET /blog (README.md:3)", None, 12),
+                result(
+                    "GET /blog?tags=a&b (README.md:15)",
+                    Some("expected response differs from actual\n<missing> \"title\""),
+                    1040,
+                ),
+            ],
+            textual_markers: false,
+        };
+
+        assert_eq!(
+            report.to_junit_xml(),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuite name="doc-assert" tests="2" failures="1" time="1.052">
+  <testcase name="GET /blog (README.md:3)" time="0.012"/>
+  <testcase name="GET /blog?tags=a&amp;b (README.md:15)" time="1.040">
+    <failure message="expected response differs from actual">expected response differs from actual
+&lt;missing&gt; &quot;title&quot;</failure>
+  </testcase>
+</testsuite>
+"#
+        );
+
+        // the colors and the control characters of the body are not valid XML
+        let report = Report {
+            results: vec![result(
+                "GET /blog (README.md:3)",
+                Some("json atoms are not equal: actual \u{1b}[31m\"a\u{0}\tb\"\u{1b}[0m"),
+                5,
+            )],
+            textual_markers: false,
+        };
+        assert!(report.to_junit_xml().contains(
+            "<failure message=\"json atoms are not equal: actual &quot;a\u{fffd}\tb&quot;\">"
+        ));
+    }
+
+    #[test]
+    fn test_report_to_json() {
+        let report = Report {
+            results: vec![
+                TestResult {
+                    id: "GET /blog (README.md:3)".to_string(),
+                    method: "GET".to_string(),
+                    uri: "/blog".to_string(),
+                    doc_path: "README.md".to_string(),
+                    line: 3,
+                    duration: Duration::from_millis(12),
+                    ..TestResult::default()
+                },
+                TestResult {
+                    id: "POST /blog (README.md:9)".to_string(),
+                    method: "POST".to_string(),
+                    uri: "/blog".to_string(),
+                    doc_path: "README.md".to_string(),
+                    line: 9,
+                    failure: Some("expected response code 201, got 400".to_string()),
+                    duration: Duration::from_millis(40),
+                    ..TestResult::default()
+                },
+            ],
+            textual_markers: false,
+        };
+
+        let json: Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(
+            json,
+            json!({
+                "total": 2,
+                "passed": 1,
+                "failed": 1,
+                "results": [
+                    {
+                        "id": "GET /blog (README.md:3)",
+                        "method": "GET",
+                        "uri": "/blog",
+                        "doc_path": "README.md",
+                        "line": 3,
+                        "passed": true,
+                        "failure": null,
+                        "duration_ms": 12
+                    },
+                    {
+                        "id": "POST /blog (README.md:9)",
+                        "method": "POST",
+                        "uri": "/blog",
+                        "doc_path": "README.md",
+                        "line": 9,
+                        "passed": false,
+                        "failure": "expected response code 201, got 400",
+                        "duration_ms": 40
+                    }
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn test_report_textual_markers() {
+        let result = |id: &str, failure: Option<&str>| TestResult {
+            id: id.to_string(),
+            failure: failure.map(|f| f.to_string()),
+            failure_kind: failure.map(|f| Failure::Response(f.to_string())),
+            duration: Duration::from_millis(5),
+            ..TestResult::default()
+        };
+        let report = Report {
+            results: vec![
+                result("GET /blog (README.md:3)", None),
+                result(
+                    "POST /blog (README.md:9)",
+                    Some("expected response code 201, got 400"),
+                ),
+            ],
+            textual_markers: true,
+        };
+
+        let text = report.to_string();
+        assert!(text.contains("GET /blog (README.md:3) PASS\n"));
+        assert!(text.contains("POST /blog (README.md:9) FAIL\n"));
+        let markdown = report.to_markdown();
+        assert!(markdown.contains("| GET /blog (README.md:3) | PASS | 5ms |"));
+        assert!(markdown.contains("| POST /blog (README.md:9) | FAIL | 5ms |"));
+        for output in [text, markdown] {
+            assert!(!output.contains('✅') && !output.contains('❌'));
+        }
+    }
+
+    #[test]
+    fn test_report_large_suite() {
+        let results = (0..300)
+            .map(|i| TestResult {
+                id: format!("GET /blog/{} (README.md:{})", i, i * 10 + 3),
+                failure: (i % 30 == 0).then(|| "expected response code 200, got 404".to_string()),
+                duration: Duration::from_millis(5),
+                ..TestResult::default()
+            })
+            .collect();
+        let report = Report {
+            results,
+            textual_markers: false,
+        };
+
+        assert_eq!(report.total_count(), 300);
+        assert_eq!(report.failed_count(), 10);
+        let text = report.to_string();
+        assert!(text.starts_with("300 tests\n"));
+        assert!(text.ends_with("test result: FAILED. 290 passed; 10 failed"));
+        assert!(report
+            .to_junit_xml()
+            .contains(r#"<testsuite name="doc-assert" tests="300" failures="10""#));
+        let json = serde_json::from_str::<Value>(&report.to_json()).unwrap();
+        assert_eq!(json["passed"], 290);
+        assert_eq!(json["failed"], 10);
+    }
+
+    #[tokio::test]
+    async fn test_no_doc_paths() {
+        let result = DocAssert::new()
+            .with_url("http://localhost:8080")
+            .assert()
+            .await;
+
+        let Err(AssertionError::ConfigurationError(err)) = result else {
+            panic!("expected a configuration error without any documentation");
+        };
+        assert!(err.contains("with_doc_path"));
+    }
+
+    #[tokio::test]
+    async fn test_etag() {
+        let doc_assert = |bumps_version: bool| {
+            let version = Arc::new(AtomicUsize::new(1));
+            DocAssert::new()
+                .with_doc_path("tests/data/etag.md")
+                .with_service(move |request: ServiceRequest| {
+                    let version = version.clone();
+                    async move {
+                        if request.method() == "PUT" {
+                            if bumps_version {
+                                version.fetch_add(1, Ordering::SeqCst);
+                            }
+                            return ServiceResponse::new(204);
+                        }
+                        let current = version.load(Ordering::SeqCst);
+                        let etag = format!("\"v{}\"", current);
+                        if request.header("If-None-Match") == Some(etag.as_str()) {
+                            return ServiceResponse::new(304).with_header("ETag", &etag);
+                        }
+                        let title = if current > 1 {
+                            "My Updated Blog"
+                        } else {
+                            "My First Blog"
+                        };
+                        ServiceResponse::new(200)
+                            .with_header("Content-Type", "application/json")
+                            .with_header("ETag", &etag)
+                            .with_body(&format!(r#"{{"title": "{}"}}"#, title))
+                    }
+                })
+        };
+
+        let result = doc_assert(true).assert().await;
+        assert!(result.is_ok());
+
+        let result = doc_assert(false).assert().await;
+        let Err(AssertionError::TestSuiteError(report)) = result else {
+            panic!("expected the unchanged ETag to fail the test");
+        };
+        assert_eq!(report.failed_count(), 1);
+        assert_eq!(
+            report.results()[3].failure_kind(),
+            Some(&Failure::Header(
+                "expected ETag to change from \"v1\", got \"v1\"".to_string()
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_changed_paths() {
+        let doc_assert = || {
+            DocAssert::new()
+                .with_doc_paths(vec!["tests/data/blog.md", "tests/data/text.md"])
+                .with_service(|request: ServiceRequest| async move {
+                    match request.uri() {
+                        "/health" => ServiceResponse::new(200)
+                            .with_header("Content-Type", "text/plain")
+                            .with_body("OK"),
+                        "/motd" => ServiceResponse::new(200)
+                            .with_header("Content-Type", "text/plain")
+                            .with_body(
+                                "Welcome to the blog\r\n  - posts are moderated\r\n  - be nice\r\n",
+                            ),
+                        _ => ServiceResponse::new(500),
+                    }
+                })
+        };
+
+        let Ok(report) = doc_assert()
+            .with_changed_paths(vec!["./tests/data/text.md", "src/lib.rs"])
+            .assert()
+            .await
+        else {
+            panic!("expected the changed documentation to pass");
+        };
+        let ids = report
+            .results()
+            .iter()
+            .map(|result| result.id())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ids,
+            vec![
+                "GET /health (tests/data/text.md:3)",
+                "GET /motd (tests/data/text.md:16)"
+            ]
+        );
+
+        let Ok(report) = doc_assert()
+            .with_changed_paths(vec!["src/lib.rs"])
+            .assert()
+            .await
+        else {
+            panic!("expected no documentation to run");
+        };
+        assert_eq!(report.total_count(), 0);
+
+        let result = doc_assert().assert().await;
+        let Err(AssertionError::TestSuiteError(report)) = result else {
+            panic!("expected the blog tests to fail");
+        };
+        assert_eq!(report.total_count(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_parsing_error() {
+        let result = DocAssert::new()
+            .with_url("http://localhost:8080")
+            .with_doc_path("tests/data/misplaced.md")
+            .assert()
+            .await;
+
+        let Err(AssertionError::ParsingError(err)) = result else {
+            panic!("expected a parsing error");
+        };
+        assert_eq!(err.file(), "tests/data/misplaced.md");
+        assert_eq!(err.line(), 7);
+    }
+
+    #[tokio::test]
+    async fn test_response_dump_dir() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/stable")
+            .with_status(200)
+            .with_body("{\"status\": \"stable\"}")
+            .create();
+        server
+            .mock("GET", "/flaky")
+            .with_status(200)
+            .with_body("{\"status\": \"flaky\"}")
+            .create();
+
+        let dir = std::env::temp_dir().join("doc-assert-test-response-dump-dir");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let url = server.url();
+        let result = DocAssert::new()
+            .with_url(url.as_str())
+            .with_doc_path("tests/data/flaky.md")
+            .with_response_dump_dir(dir.to_str().unwrap())
+            .assert()
+            .await;
+
+        assert!(result.is_ok());
+        assert_eq!(
+            "{\"status\": \"stable\"}",
+            std::fs::read_to_string(dir.join("GET_stable_tests_data_flaky.md_3")).unwrap()
+        );
+        assert_eq!(
+            "{\"status\": \"flaky\"}",
+            std::fs::read_to_string(dir.join("GET_flaky_tests_data_flaky.md_11")).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_service() {
+        let blogs = Arc::new(Mutex::new(Vec::<Value>::new()));
+
+        let result = DocAssert::new()
+            .with_doc_path("tests/data/blog.md")
+            .with_service(move |request: ServiceRequest| {
+                let blogs = blogs.clone();
+                async move {
+                    let mut blogs = blogs.lock().unwrap();
+                    let response = match (request.method(), request.uri()) {
+                        ("POST", "/blog") => {
+                            let mut blog: Value =
+                                serde_json::from_slice(request.body().unwrap()).unwrap();
+                            blog["id"] = json!(blogs.len() + 1);
+                            blogs.push(blog.clone());
+                            ServiceResponse::new(201).with_body(&blog.to_string())
+                        }
+                        ("GET", uri) => {
+                            let id = uri.trim_start_matches("/blog/").parse::<usize>().unwrap();
+                            match blogs.get(id - 1) {
+                                Some(blog) => {
+                                    ServiceResponse::new(200).with_body(&blog.to_string())
+                                }
+                                None => return ServiceResponse::new(404),
+                            }
+                        }
+                        _ => return ServiceResponse::new(405),
+                    };
+                    response.with_header("Content-Type", "application/json")
+                }
+            })
+            .assert()
+            .await;
+
+        let Ok(report) = result else {
+            panic!("expected the tests to pass");
+        };
+        assert_eq!(report.total_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_correlation_header() {
+        let mut server = mockito::Server::new();
+        let uuid = mockito::Matcher::Regex("^[0-9a-f]{8}-[0-9a-f]{4}-4[0-9a-f]{3}-".to_string());
+        server
+            .mock("GET", "/stable")
+            .match_header("X-Correlation-Id", uuid.clone())
+            .with_status(200)
+            .create();
+        server
+            .mock("GET", "/flaky")
+            .match_header("X-Correlation-Id", uuid)
+            .with_status(500)
+            .create();
+
+        let url = server.url();
+        let result = DocAssert::new()
+            .with_url(url.as_str())
+            .with_doc_path("tests/data/flaky.md")
+            .with_correlation_header("X-Correlation-Id")
+            .assert()
+            .await;
+
+        let Err(AssertionError::TestSuiteError(report)) = result else {
+            panic!("expected the flaky test to fail");
+        };
+        let ids = report
+            .results()
+            .iter()
+            .map(|r| r.correlation_id().unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(ids.len(), 2);
+        assert_ne!(ids[0], ids[1]);
+        assert!(report.results()[0].passed());
+        assert!(report
+            .to_string()
+            .contains(format!("correlation id: {}", ids[1]).as_str()));
+
+        let result = DocAssert::new()
+            .with_url(url.as_str())
+            .with_doc_path("tests/data/flaky.md")
+            .assert()
+            .await;
+        let Err(AssertionError::TestSuiteError(report)) = result else {
+            panic!("expected the tests without the correlation header to fail");
+        };
+        assert!(report
+            .results()
+            .iter()
+            .all(|r| r.correlation_id().is_none()));
+    }
+
+    #[tokio::test]
+    async fn test_register_function() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/signed")
+            .match_header("X-Signature", "1700000000:/signed")
+            .with_status(200)
+            .create();
+
+        let mut variables = Variables::new();
+        variables.insert_int("ts".to_string(), 1700000000);
+
+        let url = server.url();
+        let result = DocAssert::new()
+            .with_url(url.as_str())
+            .with_doc_path("tests/data/functions.md")
+            .with_variables(variables)
+            .register_function("sign", |args: &[Value]| {
+                let args = args
+                    .iter()
+                    .map(|arg| arg.as_str().map_or(arg.to_string(), str::to_string))
+                    .collect::<Vec<_>>();
+                Value::String(args.join(":"))
+            })
+            .assert()
+            .await;
+        assert!(result.is_ok());
+
+        let result = DocAssert::new()
+            .with_url(url.as_str())
+            .with_doc_path("tests/data/functions.md")
+            .with_variables(Variables::new())
+            .assert()
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_env_placeholders() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/profile")
+            .match_header("authorization", "Bearer abc\"def")
+            .with_status(200)
+            .with_header("Content-Type", "application/json")
+            .with_body(r#"{"token": "abc\"def"}"#)
+            .create();
+
+        let url = server.url();
+        let doc_assert = || {
+            DocAssert::new()
+                .with_url(url.as_str())
+                .with_doc_path("tests/data/env.md")
+        };
+
+        std::env::set_var("DOC_ASSERT_API_TOKEN", "abc\"def");
+        let result = doc_assert().assert().await;
+        std::env::remove_var("DOC_ASSERT_API_TOKEN");
+        assert!(result.is_ok());
+
+        let Err(AssertionError::TestSuiteError(report)) = doc_assert().assert().await else {
+            panic!("expected the unset environment variable to fail the test");
+        };
+        assert_eq!(
+            report.results()[0].failure_kind(),
+            Some(&Failure::Request(
+                "environment variable DOC_ASSERT_API_TOKEN is not set".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_generated_values() {
+        let mut variables = Variables::new();
+        variables.generator = Generator::seeded(7);
+
+        let mut input = "`uuid()` `uuid()` `randomInt(1, 3)` `now()` `now(epoch)`".to_string();
+        variables.replace_placeholders(&mut input, true).unwrap();
+        let values = input.split(' ').collect::<Vec<_>>();
+        assert_eq!(values[0].len(), 36);
+        assert_ne!(values[0], values[1]);
+        assert!(["1", "2", "3"].contains(&values[2]));
+        assert!(regex::Regex::new(r"^\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}Z$")
+            .unwrap()
+            .is_match(values[3]));
+        assert!(values[4].parse::<u64>().unwrap() > 1_700_000_000);
+
+        let mut body = r#"{"key": `uuid()`}"#.to_string();
+        variables.replace_placeholders(&mut body, false).unwrap();
+        assert!(serde_json::from_str::<Value>(&body).unwrap()["key"].is_string());
+
+        let mut input = "`randomInt(3, 1)`".to_string();
+        assert!(variables.replace_placeholders(&mut input, true).is_err());
+        let mut input = "`now(millis)`".to_string();
+        assert!(variables.replace_placeholders(&mut input, true).is_err());
+
+        assert_eq!(
+            iso8601(Duration::from_secs(1_700_000_000)),
+            "2023-11-14T22:13:20Z"
+        );
+        assert_eq!(
+            iso8601(Duration::from_secs(951_782_400)),
+            "2000-02-29T00:00:00Z"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_random_seed() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", mockito::Matcher::Any)
+            .with_status(200)
+            .create_async()
+            .await;
+
+        let url = server.url();
+        let correlation_ids = |seed: Option<u64>| {
+            let mut doc_assert = DocAssert::new()
+                .with_url(url.as_str())
+                .with_doc_path("tests/data/flaky.md")
+                .with_correlation_header("X-Correlation-Id");
+            if let Some(seed) = seed {
+                doc_assert = doc_assert.with_random_seed(seed);
+            }
+            async move {
+                let Ok(report) = doc_assert.assert().await else {
+                    panic!("expected the tests to pass");
+                };
+                report
+                    .results()
+                    .iter()
+                    .map(|r| r.correlation_id().unwrap().to_string())
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        let ids = correlation_ids(Some(42)).await;
+        assert_eq!(ids, correlation_ids(Some(42)).await);
+        assert_ne!(ids[0], ids[1]);
+        assert_ne!(ids, correlation_ids(Some(7)).await);
+        assert_ne!(ids, correlation_ids(None).await);
+    }
+
+    #[tokio::test]
+    async fn test_assert_path() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/orders/1")
+            .with_status(200)
+            .with_header("Content-Type", "application/json")
+            .with_body(
+                r#"{"id": 1, "items": [{"name": "book", "price": 12}, {"name": "pen", "price": 3}], "total": 16}"#,
+            )
+            .create_async()
+            .await;
+
+        let total_is_sum = |order: &Value| {
+            let sum = order["items"].as_array().map(|items| {
+                items
+                    .iter()
+                    .filter_map(|item| item["price"].as_u64())
+                    .sum::<u64>()
+            });
+            match (order["total"].as_u64(), sum) {
+                (Some(total), Some(sum)) if total == sum => Ok(()),
+                (total, sum) => Err(format!(
+                    "total {:?} is not the sum {:?} of the prices",
+                    total, sum
+                )),
+            }
+        };
+        let url = server.url();
+        let result = DocAssert::new()
+            .with_url(url.as_str())
+            .with_doc_path("tests/data/orders.md")
+            .assert_path("$", total_is_sum)
+            .assert()
+            .await;
+        let Err(AssertionError::TestSuiteError(report)) = result else {
+            panic!("expected the inconsistent total to fail the test");
+        };
+        assert_eq!(
+            report.results()[0].failure_kind(),
+            Some(&Failure::Response(
+                "assertion on the value at path $ failed: total Some(16) is not the sum Some(15) of the prices"
+                    .to_string()
+            ))
+        );
+
+        // paths resolving to no value are not checked
+        let result = DocAssert::new()
+            .with_url(url.as_str())
+            .with_doc_path("tests/data/orders.md")
+            .assert_path("$.items[*].price", |price| match price.as_u64() {
+                Some(price) if price > 0 => Ok(()),
+                _ => Err(format!("expected a positive price, got {}", price)),
+            })
+            .assert_path("$.discount", |_| Err("unexpected discount".to_string()))
+            .assert()
+            .await;
+        assert!(result.is_ok());
+        mock.expect(2).assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_base_response() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/blog/1")
+            .with_status(200)
+            .with_header("Content-Type", "application/json")
+            .with_body(
+                r#"{"status": "ok", "errors": [], "data": {"id": 1, "title": "My First Blog"}}"#,
+            )
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/blog/2")
+            .with_status(404)
+            .with_header("Content-Type", "application/json")
+            .with_body(r#"{"status": "error", "errors": ["blog not found"], "data": null}"#)
+            .create_async()
+            .await;
+
+        let url = server.url();
+        let base = json!({"status": "ok", "errors": [], "data": null});
+        let result = DocAssert::new()
+            .with_url(url.as_str())
+            .with_doc_path("tests/data/envelope.md")
+            .with_base_response(base.clone())
+            .assert()
+            .await;
+        assert!(result.is_ok());
+
+        let result = DocAssert::new()
+            .with_url(url.as_str())
+            .with_doc_path("tests/data/envelope.md")
+            .assert()
+            .await;
+        let Err(AssertionError::TestSuiteError(report)) = result else {
+            panic!("expected the tests without the base response to fail");
+        };
+        assert_eq!(report.failed_count(), 2);
+
+        let result = DocAssert::new()
+            .with_url(url.as_str())
+            .with_doc_path("tests/data/envelope.md")
+            .with_base_response(json!({"status": "ok", "errors": [], "data": {"id": 2}}))
+            .assert()
+            .await;
+        let Err(AssertionError::TestSuiteError(report)) = result else {
+            panic!("expected the base data to be compared unless documented");
+        };
+        // the documented data overrides the base one only in the first response
+        assert!(report.results()[0].passed());
+        assert!(report.results()[1].failure().unwrap().contains(".data"));
+    }
+
+    #[tokio::test]
+    async fn test_length_capture() {
+        let mut server = mockito::Server::new_async().await;
+        let url = server.url();
+
+        for (total, passes) in [(4, true), (5, false)] {
+            server.reset();
+            for (page, body) in [
+                ("1", r#"{"items": [{"id": 1}, {"id": 2}, {"id": 3}]}"#),
+                ("2", r#"{"items": [{"id": 4}]}"#),
+            ] {
+                server
+                    .mock("GET", "/blog")
+                    .match_query(mockito::Matcher::UrlEncoded(
+                        "page".to_string(),
+                        page.to_string(),
+                    ))
+                    .with_status(200)
+                    .with_body(body)
+                    .create();
+            }
+            server
+                .mock("GET", "/blog/stats")
+                .with_status(200)
+                .with_body(json!({ "total": total }).to_string())
+                .create();
+
+            let result = DocAssert::new()
+                .with_url(url.as_str())
+                .with_doc_path("tests/data/pagination.md")
+                .assert()
+                .await;
+            match result {
+                Ok(_) => assert!(passes),
+                Err(AssertionError::TestSuiteError(report)) => {
+                    assert!(!passes);
+                    assert!(report.results()[2]
+                        .failure()
+                        .unwrap()
+                        .contains("expected expression evaluated to 4"));
+                }
+                Err(_) => panic!("expected the suite to run"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_continue_on_capture_failure() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/blog")
+            .with_status(201)
+            .with_body(r#"{"id": 1, "title": "Hello"}"#)
+            .create();
+        server
+            .mock("GET", "/blog/1")
+            .with_status(200)
+            .with_body(r#"{"id": 1, "title": "Hello"}"#)
+            .create();
+        server
+            .mock("GET", "/health")
+            .with_status(200)
+            .with_body(r#"{"status": "ok"}"#)
+            .create();
+
+        let url = server.url();
+        for continue_on_capture_failure in [false, true] {
+            let result = DocAssert::new()
+                .with_url(url.as_str())
+                .with_doc_path("tests/data/captures.md")
+                .with_continue_on_capture_failure(continue_on_capture_failure)
+                .assert()
+                .await;
+            let Err(AssertionError::TestSuiteError(report)) = result else {
+                panic!("expected the suite to fail");
+            };
+
+            let results = report.results();
+            assert_eq!(results.len(), 4);
+            assert!(results[0]
+                .failure()
+                .unwrap()
+                .contains("variable template revision not found in the response body"));
+            assert!(results[2].passed());
+            assert!(results[3]
+                .failure()
+                .unwrap()
+                .contains("unresolved variable placeholders"));
+            if continue_on_capture_failure {
+                assert!(results[1].passed());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stale_example_report() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/blog/1")
+            .with_status(200)
+            .with_body(r#"{"id": 1, "title": "My First Blog", "date_upd": "2024-03-01T00:00:00Z"}"#)
+            .create();
+        server
+            .mock("GET", "/blog/2")
+            .with_status(200)
+            .with_body(
+                r#"{"id": 2, "title": "My Second Blog", "date_upd": "2024-02-01T00:00:00Z"}"#,
+            )
+            .create();
+
+        let url = server.url();
+        for report_stale_examples in [false, true] {
+            let result = DocAssert::new()
+                .with_url(url.as_str())
+                .with_doc_path("tests/data/stale.md")
+                .with_stale_example_report(report_stale_examples)
+                .assert()
+                .await;
+            let Ok(report) = result else {
+                panic!("expected the suite to pass");
+            };
+
+            let results = report.results();
+            assert!(results[1].notes().is_empty());
+            if report_stale_examples {
+                assert_eq!(
+                    results[0].notes(),
+                    ["example is stale in ignored fields: $.date_upd"]
+                );
+                assert!(report.to_string().contains(
+                    "notes:\nGET /blog/1 (tests/data/stale.md:3): example is stale in ignored fields: $.date_upd"
+                ));
+            } else {
+                assert!(results[0].notes().is_empty());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_patch_of() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/blog/1")
+            .with_status(200)
+            .with_body(r#"{"id": 1, "title": "Draft", "tags": ["rust"]}"#)
+            .create();
+        server
+            .mock("PATCH", "/blog/1")
+            .match_body(mockito::Matcher::Json(
+                json!({"id": 1, "title": "Published", "tags": ["rust", "api"]}),
+            ))
+            .with_status(200)
+            .with_body(r#"{"id": 1, "title": "Published", "tags": ["rust", "api"]}"#)
+            .create();
+
+        let url = server.url();
+        let result = DocAssert::new()
+            .with_url(url.as_str())
+            .with_doc_path("tests/data/patch.md")
+            .assert()
+            .await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_base_path() {
+        let mut server = mockito::Server::new();
+        let stable = server
+            .mock("GET", "/api/v1/stable")
+            .with_status(200)
+            .create();
+        let flaky = server
+            .mock("GET", "/api/v1/flaky")
+            .with_status(200)
+            .create();
+
+        let url = format!("{}/", server.url());
+        let result = DocAssert::new()
+            .with_url(url.as_str())
+            .with_doc_path("tests/data/flaky.md")
+            .with_base_path("/api/v1/")
+            .assert()
+            .await;
+        assert!(result.is_ok());
+        stable.assert();
+        flaky.assert();
+    }
+
+    #[tokio::test]
+    async fn test_client_certificates() {
+        let url = mutual_tls_server();
+        let doc_assert = || {
+            DocAssert::new()
+                .with_url(url.as_str())
+                .with_doc_path("tests/data/flaky.md")
+        };
+        let result = doc_assert()
+            .with_client_identity("tests/data/tls/client.p12", "secret")
+            .with_root_certificate("tests/data/tls/ca.pem")
+            .assert()
+            .await;
+        assert!(result.is_ok());
+
+        // the server requires the client certificate
+        let result = doc_assert()
+            .with_root_certificate("tests/data/tls/ca.pem")
+            .assert()
+            .await;
+        let Err(AssertionError::TestSuiteError(report)) = result else {
+            panic!("expected the connection without a client certificate to be rejected");
+        };
+        assert_eq!(report.failed_count(), 2);
+
+        // and its certificate is only trusted with the root certificate
+        let result = doc_assert()
+            .with_client_identity("tests/data/tls/client.p12", "secret")
+            .assert()
+            .await;
+        let Err(AssertionError::TestSuiteError(report)) = result else {
+            panic!("expected the server certificate to be rejected");
+        };
+        assert_eq!(report.failed_count(), 2);
+
+        let result = doc_assert()
+            .with_client_identity("tests/data/tls/client.p12", "wrong")
+            .assert()
+            .await;
+        let Err(AssertionError::ConfigurationError(err)) = result else {
+            panic!("expected a configuration error");
+        };
+        assert!(err.starts_with("invalid client certificate tests/data/tls/client.p12"));
+
+        let result = doc_assert()
+            .with_root_certificate("tests/data/tls/missing.pem")
+            .assert()
+            .await;
+        let Err(AssertionError::ConfigurationError(err)) = result else {
+            panic!("expected a configuration error");
+        };
+        assert!(err.starts_with("cannot read root certificate tests/data/tls/missing.pem"));
+    }
+
+    #[tokio::test]
+    async fn test_proxy() {
+        let mut server = mockito::Server::new();
+        let stable = server
+            .mock("GET", "/stable")
+            .match_header("host", "api.doc-assert.invalid")
+            .with_status(200)
+            .create();
+        let flaky = server.mock("GET", "/flaky").with_status(200).create();
+
+        let proxy = server.url();
+        let result = DocAssert::new()
+            .with_url("http://api.doc-assert.invalid")
+            .with_doc_path("tests/data/flaky.md")
+            .with_proxy(proxy.as_str())
+            .assert()
+            .await;
+        assert!(result.is_ok());
+        stable.assert();
+        flaky.assert();
+
+        let result = DocAssert::new()
+            .with_url("http://api.doc-assert.invalid")
+            .with_doc_path("tests/data/flaky.md")
+            .with_proxy("not a proxy")
+            .assert()
+            .await;
+        let Err(AssertionError::ConfigurationError(err)) = result else {
+            panic!("expected a configuration error");
+        };
+        assert!(err.starts_with("invalid proxy not a proxy"));
+    }
+
+    /// Serves empty `200 OK` responses over TLS with a certificate signed by `ca.pem`, only to the
+    /// clients presenting the certificate of `client.p12`.
+    fn mutual_tls_server() -> String {
+        use openssl::pkcs12::Pkcs12;
+        use openssl::ssl::{SslAcceptor, SslMethod, SslVerifyMode};
+        use std::io::{Read, Write};
+
+        let load = |path: &str| {
+            Pkcs12::from_der(&std::fs::read(path).unwrap())
+                .unwrap()
+                .parse2("secret")
+                .unwrap()
+        };
+        let (server, client) = (
+            load("tests/data/tls/server.p12"),
+            load("tests/data/tls/client.p12"),
+        );
+        let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+        acceptor.set_private_key(&server.pkey.unwrap()).unwrap();
+        acceptor.set_certificate(&server.cert.unwrap()).unwrap();
+        // the client certificate is self-signed, so it is trusted as it is
+        acceptor
+            .cert_store_mut()
+            .add_cert(client.cert.unwrap())
+            .unwrap();
+        acceptor.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
+        let acceptor = acceptor.build();
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("https://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let Ok(mut stream) = acceptor.accept(stream) else {
+                    continue;
+                };
+                let mut buffer = [0; 1024];
+                let _ = stream.read(&mut buffer);
+                let response = "HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+                let _ = stream.write_all(response.as_bytes());
+                let _ = stream.shutdown();
+            }
+        });
+        url
+    }
+
+    /// Serves empty `200 OK` responses over TLS with a self-signed certificate.
+    async fn self_signed_server() -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let der = std::fs::read("tests/data/tls/client.p12").unwrap();
+        let identity = tokio_native_tls::native_tls::Identity::from_pkcs12(&der, "secret").unwrap();
+        let acceptor = tokio_native_tls::TlsAcceptor::from(
+            tokio_native_tls::native_tls::TlsAcceptor::new(identity).unwrap(),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("https://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let Ok(mut stream) = acceptor.accept(stream).await else {
+                    continue;
+                };
+                let mut buffer = [0; 1024];
+                let _ = stream.read(&mut buffer).await;
+                let response = "HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+                let _ = stream.write_all(response.as_bytes()).await;
+                let _ = stream.shutdown().await;
+            }
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn test_accept_invalid_certs() {
+        let url = self_signed_server().await;
+        let doc_assert = || {
+            DocAssert::new()
+                .with_url(url.as_str())
+                .with_doc_path("tests/data/flaky.md")
+        };
+
+        let result = doc_assert().assert().await;
+        let Err(AssertionError::TestSuiteError(report)) = result else {
+            panic!("expected the self-signed certificate to be rejected");
+        };
+        assert_eq!(report.failed_count(), 2);
+
+        let result = doc_assert()
+            .danger_accept_invalid_certs(true)
+            .assert()
+            .await;
+        assert!(result.is_ok());
+    }
+
+    #[cfg(feature = "tls-inspect")]
+    #[tokio::test]
+    async fn test_tls_inspect() {
+        let url = self_signed_server().await;
+        let result = DocAssert::new()
+            .with_url(url.as_str())
+            .with_doc_path("tests/data/tls.md")
+            .danger_accept_invalid_certs(true)
+            .assert()
+            .await;
+        let Err(AssertionError::TestSuiteError(report)) = result else {
+            panic!("expected the common name of the second test not to match");
+        };
+        assert_eq!(report.failed_count(), 1);
+        assert_eq!(report.results()[0].failure(), None);
+        assert_eq!(
+            report.results()[1].failure_kind(),
+            Some(&Failure::Response(
+                "expected TLS certificate with common name example.com, got doc-assert-client"
+                    .to_string()
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_user_agent() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/stable")
+            .match_header(
+                "user-agent",
+                concat!("doc-assert/", env!("CARGO_PKG_VERSION")),
+            )
+            .with_status(200)
+            .create();
+        server
+            .mock("GET", "/flaky")
+            .match_header(
+                "user-agent",
+                concat!("doc-assert/", env!("CARGO_PKG_VERSION")),
+            )
+            .with_status(200)
+            .create();
+
+        let url = server.url();
+        let result = DocAssert::new()
+            .with_url(url.as_str())
+            .with_doc_path("tests/data/flaky.md")
+            .assert()
+            .await;
+        assert!(result.is_ok());
+
+        server.reset();
+        server
+            .mock("GET", "/stable")
+            .match_header("user-agent", "my-docs-checker/1.0")
+            .with_status(200)
+            .create();
+        server
+            .mock("GET", "/flaky")
+            .match_header("user-agent", "my-docs-checker/1.0")
+            .with_status(200)
+            .create();
+
+        let result = DocAssert::new()
+            .with_url(url.as_str())
+            .with_doc_path("tests/data/flaky.md")
+            .with_user_agent("my-docs-checker/1.0")
+            .assert()
+            .await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_redirect_policy() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/old-blog/1")
+            .with_status(301)
+            .with_header("Location", "/blog/1")
+            .create();
+        server
+            .mock("GET", "/blog/1")
+            .with_status(200)
+            .with_header("Content-Type", "application/json")
+            .with_body(r#"{"title": "My First Blog"}"#)
+            .create();
+
+        let url = server.url();
+        let result = DocAssert::new()
+            .with_url(url.as_str())
+            .with_doc_path("tests/data/redirect.md")
+            .assert()
+            .await;
+        assert!(result.is_ok());
+
+        let result = DocAssert::new()
+            .with_url(url.as_str())
+            .with_doc_path("tests/data/redirect.md")
+            .with_redirect_policy(RedirectPolicy::None)
+            .assert()
+            .await;
+        let Err(AssertionError::TestSuiteError(report)) = result else {
+            panic!("expected the redirect to be asserted instead of followed");
+        };
+        assert_eq!(report.failed_count(), 1);
+        assert_eq!(
+            report.results()[1].failure_kind(),
+            Some(&Failure::Status {
+                expected: 200,
+                actual: 301
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_assert_with_observer() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("POST", "/login")
+            .with_status(200)
+            .with_header("Content-Type", "application/json")
+            .with_body(r#"{"token": "user-token"}"#)
+            .create();
+        server.mock("GET", "/blog/1").with_status(200).create();
+        server.mock("GET", "/me").with_status(401).create();
+
+        let events = Arc::new(Mutex::new(vec![]));
+        let observed = events.clone();
+        let url = server.url();
+        let result = DocAssert::new()
+            .with_url(url.as_str())
+            .with_doc_path("tests/data/auth.md")
+            .assert_with_observer(move |event| observed.lock().unwrap().push(event))
+            .await;
+        assert!(matches!(result, Err(AssertionError::TestSuiteError(_))));
+
+        let events = events.lock().unwrap().clone();
+        assert_eq!(events.len(), 7);
+        assert_eq!(
+            events[..4],
+            [
+                TestEvent::Started {
+                    id: "POST /login (tests/data/auth.md:3)".to_string()
+                },
+                TestEvent::Passed {
+                    id: "POST /login (tests/data/auth.md:3)".to_string()
+                },
+                TestEvent::Started {
+                    id: "GET /blog/1 (tests/data/auth.md:21)".to_string()
+                },
+                TestEvent::Passed {
+                    id: "GET /blog/1 (tests/data/auth.md:21)".to_string()
+                },
+            ]
+        );
+        let TestEvent::Failed { id, error } = &events[5] else {
+            panic!("expected the last test to fail, got {:?}", events[5]);
+        };
+        assert_eq!(id, "GET /me (tests/data/auth.md:29)");
+        assert!(
+            error.ends_with("expected response code 200, got 401"),
+            "{}",
+            error
+        );
+        assert_eq!(
+            events[6],
+            TestEvent::Finished {
+                passed: 2,
+                failed: 1
+            }
+        );
+
+        // the rerun checking the determinism isn't observed
+        let events = Arc::new(Mutex::new(vec![]));
+        let observed = events.clone();
+        let result = DocAssert::new()
+            .with_url(url.as_str())
+            .with_doc_path("tests/data/auth.md")
+            .with_determinism_check(true)
+            .assert_with_observer(move |event| observed.lock().unwrap().push(event))
+            .await;
+        assert!(matches!(result, Err(AssertionError::TestSuiteError(_))));
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 7);
+        assert!(matches!(events[6], TestEvent::Finished { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_form_body() {
+        let mut server = mockito::Server::new();
+        let login = server
+            .mock("POST", "/login")
+            .match_header("content-type", "application/x-www-form-urlencoded")
+            .match_body("username=admin&password=p%40ss+word%26more&redirect=http%3A%2F%2Flocalhost%3A8080%2Fhome%3Ftab%3D1")
+            .with_status(200)
+            .create();
+
+        let mut variables = Variables::new();
+        variables.insert_string("user".to_string(), "admin".to_string());
+        let url = server.url();
+        let result = DocAssert::new()
+            .with_url(url.as_str())
+            .with_doc_path("tests/data/form.md")
+            .with_variables(variables)
+            .assert()
+            .await;
+        assert!(result.is_ok());
+        login.assert();
+    }
+
+    #[test]
+    fn test_parse_only() {
+        let tests = DocAssert::new()
+            .parse_only("tests/data/cookies.md")
+            .unwrap();
+        assert_eq!(tests.len(), 2);
+        assert_eq!(tests[0].method(), "POST");
+        assert_eq!(tests[0].uri(), "/login");
+        assert_eq!(tests[0].code(), "204");
+        assert_eq!(tests[1].doc_path(), "tests/data/cookies.md");
+        assert_eq!(tests[1].line(), 14);
+        assert_eq!(
+            tests[1].headers().get("Cookie").map(String::as_str),
+            Some("session=`sid`")
+        );
+        assert_eq!(tests[1].body(), None);
+
+        let tests = DocAssert::new().parse_only("tests/data/stale.md").unwrap();
+        assert_eq!(tests[0].ignore_paths(), ["$.date_upd".to_string()]);
+
+        let err = DocAssert::new()
+            .parse_only("tests/data/unanswered.md")
+            .unwrap_err();
+        assert_eq!(err.file(), "tests/data/unanswered.md");
+    }
+
+    #[test]
+    fn test_validate() {
+        let count = DocAssert::new()
+            .with_doc_path("tests/data/cookies.md")
+            .validate();
+        assert!(matches!(count, Ok(2)));
+
+        let mut variables = Variables::new();
+        variables.insert_int("postId".to_string(), 1);
+        let count = DocAssert::new()
+            .with_doc_paths(vec!["tests/data/form.md", "tests/data/validate.md"])
+            .with_variables(variables)
+            .validate();
+        let Err(AssertionError::ValidationError(errors)) = count else {
+            panic!("expected validation errors");
+        };
+        let errors = errors.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            vec![
+                "tests/data/form.md:3: unresolved variable placeholder `user`".to_string(),
+                "tests/data/validate.md:29: invalid call `randomInt(10)`: invalid arguments of randomInt: (10)".to_string(),
+            ]
+        );
+
+        let result = DocAssert::new()
+            .with_doc_paths(vec!["tests/data/validate.md", "tests/data/unanswered.md"])
+            .validate();
+        let Err(AssertionError::ValidationError(errors)) = result else {
+            panic!("expected validation errors");
+        };
+        assert_eq!(errors.len(), 3);
+        assert_eq!(
+            errors[0].message(),
+            "unresolved variable placeholder `postId`"
+        );
+        assert_eq!(errors[2].file(), "tests/data/unanswered.md");
+
+        // the environment variables are read when the tests run
+        let count = DocAssert::new()
+            .with_doc_path("tests/data/env.md")
+            .validate();
+        assert!(matches!(count, Ok(1)));
+
+        // the files running concurrently don't share the captured variables
+        let doc_assert =
+            || DocAssert::new().with_doc_paths(vec!["tests/data/auth.md", "tests/data/shared.md"]);
+        assert!(matches!(doc_assert().validate(), Ok(4)));
+        let result = doc_assert().with_concurrency(2).validate();
+        let Err(AssertionError::ValidationError(errors)) = result else {
+            panic!("expected validation errors");
+        };
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            vec!["tests/data/shared.md:3: unresolved variable placeholder `token`".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_status_ranges() {
+        let mut server = mockito::Server::new();
+        server.mock("POST", "/jobs").with_status(202).create();
+        server.mock("GET", "/jobs/1").with_status(404).create();
+
+        let url = server.url();
+        let result = DocAssert::new()
+            .with_url(url.as_str())
+            .with_doc_path("tests/data/ranges.md")
+            .assert()
+            .await;
+        let Err(AssertionError::TestSuiteError(report)) = result else {
+            panic!("expected the second test to fail");
+        };
+        assert!(report.results()[0].passed());
+        assert_eq!(
+            report.results()[1].failure_kind(),
+            Some(&Failure::StatusRange {
+                expected: "200-299".to_string(),
+                actual: 404,
+            })
+        );
+        assert!(report.results()[1]
+            .failure()
+            .unwrap()
+            .ends_with("expected response code 200-299, got 404"));
+    }
+
+    #[tokio::test]
+    async fn test_cookies() {
+        let mut server = mockito::Server::new();
+        let login = server
+            .mock("POST", "/login")
+            .with_status(204)
+            .with_header(
+                "Set-Cookie",
+                "session=abc123; Path=/; HttpOnly; Secure; SameSite=Strict",
+            )
+            .create();
+        let me = server
+            .mock("GET", "/me")
+            .match_header("cookie", "session=abc123")
+            .with_status(204)
+            .create();
+
+        let url = server.url();
+        let result = DocAssert::new()
+            .with_url(url.as_str())
+            .with_doc_path("tests/data/cookies.md")
+            .assert()
+            .await;
+        assert!(result.is_ok());
+        login.assert();
+        me.assert();
+    }
+
+    #[tokio::test]
+    async fn test_authorization() {
+        let mut server = mockito::Server::new();
+        let login = server
+            .mock("POST", "/login")
+            .match_header("authorization", "Basic YWRtaW46c2VjcmV0")
+            .with_status(200)
+            .with_header("Content-Type", "application/json")
+            .with_body(r#"{"token": "user-token"}"#)
+            .create();
+        let blog = server
+            .mock("GET", "/blog/1")
+            .match_header("authorization", "Basic YWRtaW46c2VjcmV0")
+            .with_status(200)
+            .create();
+        let me = server
+            .mock("GET", "/me")
+            .match_header("authorization", "Bearer user-token")
+            .with_status(200)
+            .create();
+
+        let url = server.url();
+        let result = DocAssert::new()
+            .with_url(url.as_str())
+            .with_doc_path("tests/data/auth.md")
+            .with_basic_auth("admin", "secret")
+            .assert()
+            .await;
+        assert!(result.is_ok());
+        login.assert();
+        blog.assert();
+        me.assert();
+
+        server.reset();
+        server
+            .mock("POST", "/login")
+            .match_header("authorization", "Bearer service-token")
+            .with_status(200)
+            .with_header("Content-Type", "application/json")
+            .with_body(r#"{"token": "user-token"}"#)
+            .create();
+        server
+            .mock("GET", "/blog/1")
+            .match_header("authorization", "Bearer service-token")
+            .with_status(200)
+            .create();
+        server
+            .mock("GET", "/me")
+            .match_header("authorization", "Bearer user-token")
+            .with_status(200)
+            .create();
+
+        let result = DocAssert::new()
+            .with_url(url.as_str())
+            .with_doc_path("tests/data/auth.md")
+            .with_bearer_token("service-token")
+            .assert()
+            .await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_concurrency() {
+        let doc_assert = || {
+            let in_flight = Arc::new(AtomicUsize::new(0));
+            let max_in_flight = Arc::new(AtomicUsize::new(0));
+            let doc_assert = DocAssert::new()
+                .with_doc_path("tests/data/stages.md")
+                .with_doc_path("tests/data/flaky.md")
+                .with_service({
+                    let max_in_flight = max_in_flight.clone();
+                    move |request: ServiceRequest| {
+                        let (in_flight, max_in_flight) = (in_flight.clone(), max_in_flight.clone());
+                        async move {
+                            let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                            max_in_flight.fetch_max(current, Ordering::SeqCst);
+                            tokio::time::sleep(Duration::from_millis(50)).await;
+                            in_flight.fetch_sub(1, Ordering::SeqCst);
+                            match (request.method(), request.uri()) {
+                                ("POST", "/session") => ServiceResponse::new(201)
+                                    .with_header("Content-Type", "application/json")
+                                    .with_body(r#"{"token": "abc"}"#),
+                                ("DELETE", _) => ServiceResponse::new(204),
+                                _ => ServiceResponse::new(200),
+                            }
+                        }
+                    }
+                });
+            (doc_assert, max_in_flight)
+        };
+
+        let (sequential, max_in_flight) = doc_assert();
+        let Ok(sequential) = sequential.assert().await else {
+            panic!("expected the documents to pass when run sequentially");
+        };
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 1);
+
+        let (concurrent, max_in_flight) = doc_assert();
+        let Ok(concurrent) = concurrent.with_concurrency(2).assert().await else {
+            panic!("expected the documents to pass when run concurrently");
+        };
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 2);
+
+        let ids = |report: &Report| {
+            report
+                .results()
+                .iter()
+                .map(|r| r.id().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(&concurrent), ids(&sequential));
     }
 }
@@ -14,12 +14,16 @@
 use std::convert::From;
 use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::Duration;
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use serde_json::Value;
 
 use doc_assert::AssertionError;
+use doc_assert::ColorChoice;
 use doc_assert::DocAssert;
+use doc_assert::RedirectPolicy;
+use doc_assert::Report;
 use doc_assert::Variables;
 
 #[doc(hidden)]
@@ -53,6 +57,42 @@ impl FromStr for JSONVars {
     }
 }
 
+#[doc(hidden)]
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+enum ReportFormat {
+    /// Plain text summary
+    #[default]
+    Text,
+    /// Markdown table, e.g. for the GitHub Actions job summary
+    Markdown,
+    /// JUnit XML, e.g. for the GitLab CI test reports
+    Junit,
+    /// JSON document for further processing by scripts
+    Json,
+}
+
+#[doc(hidden)]
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+enum Color {
+    /// Color the differences when printing to a terminal
+    #[default]
+    Auto,
+    /// Always color the differences
+    Always,
+    /// Never color the differences
+    Never,
+}
+
+#[doc(hidden)]
+fn render(report: &Report, format: ReportFormat) -> String {
+    match format {
+        ReportFormat::Text => report.to_string(),
+        ReportFormat::Markdown => report.to_markdown(),
+        ReportFormat::Junit => report.to_junit_xml(),
+        ReportFormat::Json => report.to_json(),
+    }
+}
+
 #[doc(hidden)]
 #[macro_export]
 macro_rules! handle_error {
@@ -86,12 +126,135 @@ struct Cli {
     files: Vec<PathBuf>,
 
     /// URL to test against
-    #[clap(short, long, required = true)]
-    url: String,
+    #[clap(short, long, required_unless_present = "dry_run")]
+    url: Option<String>,
+
+    /// Only validate the documentation, without sending any request
+    #[clap(long)]
+    dry_run: bool,
 
     /// Variables to be used in the assertions in the JSON object format
     #[clap(short, long)]
     variables: Option<JSONVars>,
+
+    /// Run the test suite twice and fail if any test changed its outcome
+    #[clap(long)]
+    check_determinism: bool,
+
+    /// Fail on any undocumented 5xx response, even if a retry would pass
+    #[clap(long)]
+    no_unexpected_errors: bool,
+
+    /// Keep capturing the other variables of a response when one of its captures fails
+    #[clap(long)]
+    continue_on_capture_failure: bool,
+
+    /// Note the examples whose body differs from the actual one only in the ignored fields
+    #[clap(long)]
+    report_stale_examples: bool,
+
+    /// Delay between consecutive requests in milliseconds
+    #[clap(long)]
+    delay: Option<u64>,
+
+    /// Number of documentation files tested concurrently, each with its own copy of the variables
+    #[clap(long)]
+    concurrency: Option<usize>,
+
+    /// Default time to wait for each response in milliseconds
+    #[clap(long)]
+    timeout: Option<u64>,
+
+    /// Assert redirect responses instead of following them
+    #[clap(long)]
+    no_follow_redirects: bool,
+
+    /// Environment whose variables should be used
+    #[clap(long)]
+    env: Option<String>,
+
+    /// Variables of each environment in the JSON object format, e.g. {"staging": {"user_id": 1}}
+    #[clap(long)]
+    env_variables: Option<JSONVars>,
+
+    /// Directory where the body of each actual response will be written
+    #[clap(long)]
+    dump_responses: Option<PathBuf>,
+
+    /// User agent the requests are sent with
+    #[clap(long)]
+    user_agent: Option<String>,
+
+    /// Path prepended to the URI of every request, e.g. /api/v1
+    #[clap(long)]
+    base_path: Option<String>,
+
+    /// Client certificate and private key in a PKCS #12 archive, for servers requiring mutual TLS
+    #[clap(long)]
+    client_cert: Option<PathBuf>,
+
+    /// Password of the client certificate archive
+    #[clap(long, default_value = "", requires = "client_cert")]
+    client_cert_password: String,
+
+    /// Additional root certificate in the PEM format, e.g. of a self-signed certificate authority
+    #[clap(long)]
+    ca_cert: Option<PathBuf>,
+
+    /// Accept invalid TLS certificates, e.g. self-signed ones. Insecure, for test environments only
+    #[clap(long)]
+    insecure: bool,
+
+    /// Proxy the requests are sent through, by default the one of the HTTP_PROXY and HTTPS_PROXY
+    /// environment variables
+    #[clap(long)]
+    proxy: Option<String>,
+
+    /// Bearer token sent in the Authorization header of the requests which don't set it
+    #[clap(long)]
+    bearer: Option<String>,
+
+    /// Header carrying a unique identifier of each request, e.g. X-Correlation-Id
+    #[clap(long)]
+    correlation_header: Option<String>,
+
+    /// Seed of the generated values, makes them reproducible between runs
+    #[clap(long)]
+    seed: Option<u64>,
+
+    /// Base body in the JSON format the documented response bodies are merged into
+    #[clap(long)]
+    base_response: Option<JSONVars>,
+
+    /// Format of the printed report
+    #[clap(long, value_enum, default_value_t)]
+    report_format: ReportFormat,
+
+    /// Mark the results in the report with PASS and FAIL instead of emoji
+    #[clap(long)]
+    no_emoji: bool,
+
+    /// When to color the actual and expected values of the differences in the text report
+    #[clap(long, value_enum, default_value_t)]
+    color: Color,
+
+    /// Print each difference on a single line with its path and values
+    #[clap(long)]
+    compact_diff: bool,
+
+    /// Fail on unknown annotations, e.g. a misspelled [ignroe], instead of ignoring them
+    #[clap(long)]
+    strict: bool,
+
+    /// File listing the changed files one per line, e.g. the output of git diff --name-only,
+    /// only the documentation files among them are tested
+    #[clap(long)]
+    changed_files: Option<PathBuf>,
+
+    /// OpenAPI specification the responses are validated against
+    #[cfg(feature = "openapi")]
+    #[clap(long)]
+    openapi: Option<PathBuf>,
 }
 
 #[doc(hidden)]
@@ -121,9 +284,152 @@ async fn main() {
         None => Variables::new(),
     };
 
+    let mut environment_variables = vec![];
+    if let Some(vars) = &cli.env_variables {
+        let Value::Object(environments) = &vars.0 else {
+            handle_error!(
+                Code::INVALID_ARGUMENT,
+                "Error: Environment variables must be a JSON object"
+            );
+        };
+
+        for (environment, vars) in environments {
+            match Variables::from_json(vars) {
+                Ok(vars) => environment_variables.push((environment.as_str(), vars)),
+                Err(e) => {
+                    handle_error!(Code::INVALID_ARGUMENT, "Error: {}: {}", environment, e);
+                }
+            }
+        }
+    }
+
+    let changed_files = match &cli.changed_files {
+        Some(path) => match std::fs::read_to_string(path) {
+            Ok(changed_files) => Some(changed_files),
+            Err(e) => {
+                handle_error!(
+                    Code::INVALID_ARGUMENT,
+                    "error: Invalid changed files {}: {}",
+                    path.display(),
+                    e
+                );
+            }
+        },
+        None => None,
+    };
+
     let mut doc_assert = DocAssert::new()
-        .with_url(cli.url.as_str())
-        .with_variables(variables);
+        .with_variables(variables)
+        .with_determinism_check(cli.check_determinism)
+        .with_no_unexpected_errors(cli.no_unexpected_errors)
+        .with_continue_on_capture_failure(cli.continue_on_capture_failure)
+        .with_stale_example_report(cli.report_stale_examples)
+        .with_textual_markers(cli.no_emoji)
+        .with_color(match (cli.report_format, cli.color) {
+            // escape codes would corrupt the other formats
+            (ReportFormat::Text, Color::Auto) => ColorChoice::Auto,
+            (ReportFormat::Text, Color::Always) => ColorChoice::Always,
+            _ => ColorChoice::Never,
+        })
+        .with_compact_diff(cli.compact_diff)
+        .with_strict_parsing(cli.strict)
+        .danger_accept_invalid_certs(cli.insecure);
+
+    if let Some(url) = &cli.url {
+        doc_assert = doc_assert.with_url(url.as_str());
+    }
+
+    for (environment, vars) in environment_variables {
+        doc_assert = doc_assert.with_environment_variables(environment, vars);
+    }
+
+    if let Some(environment) = &cli.env {
+        doc_assert = doc_assert.with_environment(environment.as_str());
+    }
+
+    if let Some(delay) = cli.delay {
+        doc_assert = doc_assert.with_request_delay(Duration::from_millis(delay));
+    }
+
+    if let Some(concurrency) = cli.concurrency {
+        doc_assert = doc_assert.with_concurrency(concurrency);
+    }
+
+    if let Some(timeout) = cli.timeout {
+        doc_assert = doc_assert.with_timeout(Duration::from_millis(timeout));
+    }
+
+    if cli.no_follow_redirects {
+        doc_assert = doc_assert.with_redirect_policy(RedirectPolicy::None);
+    }
+
+    if let Some(dir) = &cli.dump_responses {
+        let Some(dir) = dir.to_str() else {
+            handle_error!(Code::INVALID_ARGUMENT, "error: Invalid directory path");
+        };
+
+        doc_assert = doc_assert.with_response_dump_dir(dir);
+    }
+
+    if let Some(user_agent) = &cli.user_agent {
+        doc_assert = doc_assert.with_user_agent(user_agent.as_str());
+    }
+
+    if let Some(base_path) = &cli.base_path {
+        doc_assert = doc_assert.with_base_path(base_path.as_str());
+    }
+
+    if let Some(client_cert) = &cli.client_cert {
+        let Some(client_cert) = client_cert.to_str() else {
+            handle_error!(
+                Code::INVALID_ARGUMENT,
+                "error: Invalid client certificate path"
+            );
+        };
+
+        doc_assert =
+            doc_assert.with_client_identity(client_cert, cli.client_cert_password.as_str());
+    }
+
+    if let Some(ca_cert) = &cli.ca_cert {
+        let Some(ca_cert) = ca_cert.to_str() else {
+            handle_error!(
+                Code::INVALID_ARGUMENT,
+                "error: Invalid root certificate path"
+            );
+        };
+
+        doc_assert = doc_assert.with_root_certificate(ca_cert);
+    }
+
+    if let Some(proxy) = &cli.proxy {
+        doc_assert = doc_assert.with_proxy(proxy.as_str());
+    }
+
+    if let Some(token) = &cli.bearer {
+        doc_assert = doc_assert.with_bearer_token(token.as_str());
+    }
+
+    if let Some(header) = &cli.correlation_header {
+        doc_assert = doc_assert.with_correlation_header(header.as_str());
+    }
+
+    if let Some(seed) = cli.seed {
+        doc_assert = doc_assert.with_random_seed(seed);
+    }
+
+    if let Some(base_response) = cli.base_response {
+        doc_assert = doc_assert.with_base_response(base_response.0);
+    }
+
+    #[cfg(feature = "openapi")]
+    if let Some(spec_path) = &cli.openapi {
+        let Some(spec_path) = spec_path.to_str() else {
+            handle_error!(Code::INVALID_ARGUMENT, "error: Invalid OpenAPI spec path");
+        };
+
+        doc_assert = doc_assert.with_openapi(spec_path);
+    }
 
     for file in cli.files.iter() {
         let Some(file) = file.to_str() else {
@@ -133,19 +439,67 @@ async fn main() {
         doc_assert = doc_assert.with_doc_path(file);
     }
 
+    if let Some(changed_files) = &changed_files {
+        let changed_paths = changed_files
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect();
+        doc_assert = doc_assert.with_changed_paths(changed_paths);
+    }
+
+    if cli.dry_run {
+        match doc_assert.validate() {
+            Ok(count) => {
+                println!("{} test cases are valid", count);
+                std::process::exit(Code::SUCCESS);
+            }
+            Err(AssertionError::ValidationError(errors)) => {
+                for err in errors.iter() {
+                    eprintln!("{}", err);
+                }
+                std::process::exit(Code::DOC_PARSING_ERROR);
+            }
+            Err(AssertionError::ConfigurationError(err)) => {
+                handle_error!(Code::INVALID_ARGUMENT, "Error: {}", err);
+            }
+            Err(_) => {
+                handle_error!(Code::INTERNAL_ERROR, "Error: unexpected validation result");
+            }
+        }
+    }
+
     let result = doc_assert.assert().await;
 
     match result {
         Ok(report) => {
-            println!("{}", report);
+            println!("{}", render(&report, cli.report_format));
             std::process::exit(Code::SUCCESS);
         }
         Err(err) => match err {
+            AssertionError::ConfigurationError(err) => {
+                handle_error!(Code::INVALID_ARGUMENT, "Error: {}", err);
+            }
             AssertionError::ParsingError(err) => {
                 handle_error!(Code::DOC_PARSING_ERROR, "Error parsing file: {}", err);
             }
             AssertionError::TestSuiteError(report) => {
-                handle_error!(Code::DOC_ASSERTION_ERROR, "{}", report);
+                handle_error!(
+                    Code::DOC_ASSERTION_ERROR,
+                    "{}",
+                    render(&report, cli.report_format)
+                );
+            }
+            AssertionError::NondeterminismError(flipped) => {
+                handle_error!(
+                    Code::DOC_ASSERTION_ERROR,
+                    "Error: tests changed their outcome between runs:\n{}",
+                    flipped.join("\n")
+                );
+            }
+            AssertionError::ValidationError(errors) => {
+                let errors = errors.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+                handle_error!(Code::DOC_PARSING_ERROR, "{}", errors.join("\n"));
             }
         },
     }
@@ -0,0 +1,180 @@
+// Copyright 2024 The DocAssert Authors
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fs;
+
+use jsonschema::JSONSchema;
+use serde_json::{json, Value};
+
+use crate::domain::HttpMethod;
+use crate::executor::parse_actual_body;
+
+/// OpenAPI specification the actual responses are validated against
+#[derive(Debug)]
+pub(crate) struct OpenApi {
+    spec: Value,
+}
+
+impl OpenApi {
+    /// Loads the specification in the JSON or YAML format.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("error reading OpenAPI spec {}: {}", path, e))?;
+        let spec = match serde_json::from_str(&content) {
+            Ok(spec) => spec,
+            Err(_) => serde_yaml::from_str(&content)
+                .map_err(|e| format!("error parsing OpenAPI spec {}: {}", path, e))?,
+        };
+
+        Ok(Self { spec })
+    }
+
+    /// Validates the response body against the schema of the operation matching the request.
+    ///
+    /// Responses of requests without a matching operation or schema are considered valid.
+    pub fn validate(
+        &self,
+        http_method: &HttpMethod,
+        uri: &str,
+        status: u16,
+        body: &str,
+    ) -> Result<(), Vec<String>> {
+        let Some(schema) = self.response_schema(http_method, uri, status) else {
+            return Ok(());
+        };
+
+        // components are embedded so that the references like `#/components/schemas/Blog` resolve
+        let schema = json!({
+            "allOf": [schema],
+            "components": self.spec.get("components").cloned().unwrap_or(Value::Null),
+        });
+        let compiled = JSONSchema::compile(&schema)
+            .map_err(|e| vec![format!("invalid schema of {} {}: {}", http_method, uri, e)])?;
+
+        let instance = parse_actual_body(body).map_err(|e| vec![e])?;
+        compiled.validate(&instance).map_err(|errors| {
+            errors
+                .map(|error| {
+                    let instance_path = error.instance_path.to_string();
+                    let instance_path = if instance_path.is_empty() {
+                        "/".to_string()
+                    } else {
+                        instance_path
+                    };
+                    format!("{}: {}", instance_path, error)
+                })
+                .collect::<Vec<_>>()
+        })
+    }
+
+    fn response_schema(&self, http_method: &HttpMethod, uri: &str, status: u16) -> Option<&Value> {
+        let path = uri.split(['?', '#']).next().unwrap_or_default();
+        let (_, path_item) = self
+            .spec
+            .get("paths")?
+            .as_object()?
+            .iter()
+            .find(|(template, _)| matches_template(template, path))?;
+        let responses = path_item
+            .get(http_method.to_string().to_lowercase())?
+            .get("responses")?;
+
+        let status = status.to_string();
+        let status_range = format!("{}XX", &status[..1]);
+        let response = responses
+            .get(&status)
+            .or_else(|| responses.get(&status_range))
+            .or_else(|| responses.get(status_range.to_lowercase()))
+            .or_else(|| responses.get("default"))?;
+        let response = self.resolve(response)?;
+
+        let content = response.get("content")?.as_object()?;
+        content
+            .iter()
+            .find(|(media_type, _)| media_type.contains("json"))
+            .and_then(|(_, media_type)| media_type.get("schema"))
+    }
+
+    /// Follows a local reference like `#/components/responses/NotFound`.
+    fn resolve<'a>(&'a self, value: &'a Value) -> Option<&'a Value> {
+        match value.get("$ref").and_then(|r| r.as_str()) {
+            Some(reference) => self.spec.pointer(reference.strip_prefix('#')?),
+            None => Some(value),
+        }
+    }
+}
+
+/// Checks if the path matches the path template like `/blog/{id}`.
+fn matches_template(template: &str, path: &str) -> bool {
+    let template_segments = template.trim_end_matches('/').split('/');
+    let path_segments = path.trim_end_matches('/').split('/');
+    if template_segments.clone().count() != path_segments.clone().count() {
+        return false;
+    }
+
+    template_segments
+        .zip(path_segments)
+        .all(|(expected, actual)| {
+            (expected.starts_with('{') && expected.ends_with('}') && !actual.is_empty())
+                || expected == actual
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_template() {
+        assert!(matches_template("/blog", "/blog"));
+        assert!(matches_template("/blog/{id}", "/blog/1"));
+        assert!(matches_template("/blog/{id}/", "/blog/`id`"));
+        assert!(!matches_template("/blog/{id}", "/blog"));
+        assert!(!matches_template("/blog/{id}", "/blog/1/comments"));
+        assert!(!matches_template("/blog/{id}", "/user/1"));
+    }
+
+    #[test]
+    fn test_validate() {
+        let openapi = OpenApi::load("tests/data/openapi.yaml").unwrap();
+
+        let valid = r#"{"id": 1, "title": "My First Blog", "body": "Blog content"}"#;
+        assert_eq!(
+            Ok(()),
+            openapi.validate(&HttpMethod::Get, "/blog/1", 200, valid)
+        );
+
+        let invalid = r#"{"id": "1", "body": "Blog content"}"#;
+        let violations = openapi
+            .validate(&HttpMethod::Get, "/blog/1", 200, invalid)
+            .unwrap_err();
+        assert_eq!(violations.len(), 2);
+        assert!(violations.iter().any(|v| v.starts_with("/id: ")));
+        assert!(violations
+            .iter()
+            .any(|v| v.starts_with("/: ") && v.contains("title")));
+
+        let not_found = r#"{"message": 404}"#;
+        let violations = openapi
+            .validate(&HttpMethod::Get, "/blog/2", 404, not_found)
+            .unwrap_err();
+        assert_eq!(violations.len(), 1);
+        assert!(violations[0].starts_with("/message: "));
+
+        // operations missing from the spec are not validated
+        assert_eq!(
+            Ok(()),
+            openapi.validate(&HttpMethod::Delete, "/blog/1", 200, invalid)
+        );
+    }
+}
@@ -14,94 +14,193 @@
 use std::collections::HashMap;
 use std::fs;
 use std::iter::Enumerate;
+use std::ops::Range;
 use std::str::{FromStr, Lines};
+use std::time::Duration;
 
+use base64::Engine;
 use regex::Regex;
+use serde_json::Value;
 
-use crate::domain::{HttpMethod, Request, Response, RetryPolicy, TestCase};
-use crate::json_diff::path::{JSONPath, Path, JSON_PATH_REGEX};
+use crate::domain::{
+    Capture, Comparison, CookieCheck, CountMatching, Each, Etag, EtagExpectation, ExpectedStatus,
+    HttpMethod, MultipartField, PatternMatch, Poll, RateLimit, Request, Response, RetryOn,
+    RetryPolicy, Stage, Stream, TestCase, TlsCheck, Transform, TypeCheck,
+};
+use crate::json_diff::path::{JSONPath, Path, JSON_PATH_REGEX, JSON_POINTER_REGEX};
+use crate::json_diff::{DiffFormat, NumericMode};
+use crate::ParseError;
 
 const DOC_ASSERT_REQUEST: &str = "```docassertrequest";
 const DOC_ASSERT_RESPONSE: &str = "```docassertresponse";
 const IGNORE_PREFIX: &str = "[ignore]";
 const IGNORE_ORDER_PREFIX: &str = "[ignore-order]";
+const EXACT_LENGTH_PREFIX: &str = "[exact-length]";
+const SET_EQUAL_PREFIX: &str = "[set-equal]";
+const CONTAINS_PREFIX: &str = "[contains]";
+const KEY_ORDER_PREFIX: &str = "[key-order]";
+const PARSE_JSON_PREFIX: &str = "[parse-json]";
+const ABSENT_PREFIX: &str = "[absent]";
 const VARIABLE_PREFIX: &str = "[let ";
 const RETRY_PREFIX: &str = "[retry]";
+const TIMEOUT_PREFIX: &str = "[timeout]";
+const PATCH_OF_PREFIX: &str = "[patch-of]";
+const NO_REDIRECT_PREFIX: &str = "[no-redirect]";
+const BEFORE_ALL_PREFIX: &str = "[before-all]";
+const AFTER_ALL_PREFIX: &str = "[after-all]";
+const COUNT_MATCHING_PREFIX: &str = "[count-matching]";
+const EACH_PREFIX: &str = "[each]";
+const TRANSFORM_PREFIX: &str = "[transform]";
+const MATCH_PREFIX: &str = "[match]";
+const TYPE_PREFIX: &str = "[type]";
+const NUMERIC_PREFIX: &str = "[numeric]";
+const TOLERANCE_PREFIX: &str = "[tolerance]";
+const COOKIE_CLEARED_PREFIX: &str = "[cookie-cleared]";
+const COOKIE_PREFIX: &str = "[cookie]";
+const TLS_PREFIX: &str = "[tls]";
+const RATE_LIMIT_PREFIX: &str = "[rate-limit]";
+const ETAG_PREFIX: &str = "[etag]";
+const POLL_PREFIX: &str = "[poll]";
+const REPEAT_PREFIX: &str = "[repeat]";
+const P95_PREFIX: &str = "[p95]";
+const MAX_TIME_PREFIX: &str = "[max-time]";
+const STREAM_PREFIX: &str = "[stream]";
+const TEXT_PREFIX: &str = "[text]";
+const COMPRESSED_PREFIX: &str = "[compressed]";
+const PROBLEM_PREFIX: &str = "[problem]";
+const SCHEMA_PREFIX: &str = "[schema]";
+const BYTES_BASE64_PREFIX: &str = "@bytes:base64,";
+const FILE_BYTES_PREFIX: &str = "@file-bytes:";
+const FILE_PART_PREFIX: &str = "@file:";
+const MULTIPART_CONTENT_TYPE: &str = "multipart/form-data";
+const FORM_CONTENT_TYPE: &str = "application/x-www-form-urlencoded";
+const COMMENT_PREFIXES: [&str; 2] = ["#", "//"];
 
-pub(crate) fn parse(path: String) -> Result<Vec<TestCase>, String> {
-    let (mut requests, mut responses) = (vec![], vec![]);
-    let binding = fs::read_to_string(path).map_err(|e| e.to_string())?;
-    let mut lines = binding.lines().enumerate();
-    while let Some((mut line_no, line)) = lines.next() {
-        line_no += 1;
-        if line.starts_with(DOC_ASSERT_REQUEST) {
-            let request = get_request(line_no, get_code(&mut lines)).map_err(|err| {
-                format!(
-                    "parsing error of a request code block starting at line {}: {}",
-                    line_no, err
-                )
-            })?;
-            requests.push(request);
-        }
-
-        if line.starts_with(DOC_ASSERT_RESPONSE) {
-            let response = get_response(line_no, get_code(&mut lines)).map_err(|err| {
-                format!(
-                    "parsing error of a response code block starting at line {}: {}",
-                    line_no, err
-                )
-            })?;
-            responses.push(response);
-        }
-
-        if line.starts_with(IGNORE_PREFIX) {
-            if responses.is_empty() || responses.len() != requests.len() {
-                return Err(format!("misplaced ignore at line {}: {}", line_no, line));
-            }
-            let l = responses.len();
-            responses[l - 1].ignore_paths.push(get_ignore_path(line)?);
-        }
+const ANNOTATION_PREFIXES: [&str; 36] = [
+    IGNORE_PREFIX,
+    IGNORE_ORDER_PREFIX,
+    EXACT_LENGTH_PREFIX,
+    SET_EQUAL_PREFIX,
+    CONTAINS_PREFIX,
+    KEY_ORDER_PREFIX,
+    PARSE_JSON_PREFIX,
+    ABSENT_PREFIX,
+    VARIABLE_PREFIX,
+    RETRY_PREFIX,
+    TIMEOUT_PREFIX,
+    PATCH_OF_PREFIX,
+    NO_REDIRECT_PREFIX,
+    BEFORE_ALL_PREFIX,
+    AFTER_ALL_PREFIX,
+    COUNT_MATCHING_PREFIX,
+    EACH_PREFIX,
+    TRANSFORM_PREFIX,
+    MATCH_PREFIX,
+    TYPE_PREFIX,
+    NUMERIC_PREFIX,
+    TOLERANCE_PREFIX,
+    COOKIE_CLEARED_PREFIX,
+    COOKIE_PREFIX,
+    TLS_PREFIX,
+    RATE_LIMIT_PREFIX,
+    ETAG_PREFIX,
+    POLL_PREFIX,
+    REPEAT_PREFIX,
+    P95_PREFIX,
+    MAX_TIME_PREFIX,
+    STREAM_PREFIX,
+    TEXT_PREFIX,
+    COMPRESSED_PREFIX,
+    PROBLEM_PREFIX,
+    SCHEMA_PREFIX,
+];
 
-        if line.starts_with(IGNORE_ORDER_PREFIX) {
-            if responses.is_empty() || responses.len() != requests.len() {
-                return Err(format!(
-                    "misplaced ignore-order at line {}: {}",
-                    line_no, line
-                ));
-            }
-            let l = responses.len();
-            responses[l - 1].ignore_orders.push(get_ignore_path(line)?);
-        }
+/// Kind of an element recognized in the documentation
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlockKind {
+    /// Code block with a request
+    Request,
+    /// Code block with an expected response
+    Response,
+    /// Annotation line with the given name, e.g. `ignore` or `let`
+    Annotation(String),
+}
 
-        if line.starts_with(VARIABLE_PREFIX) {
-            if responses.is_empty() || responses.len() != requests.len() {
-                return Err(format!("misplaced variable at line {}: {}", line_no, line));
+/// Lists the requests, responses and annotations found in the documentation
+/// together with the lines they span.
+///
+/// Lines are numbered from 1 and the ranges are half-open, so a code block spanning
+/// lines 3 to 5 including the fences is returned as `3..6`. The elements are only located,
+/// their content is not validated.
+///
+/// # Examples
+///
+/// ```
+/// use doc_assert::{parse_with_spans, BlockKind};
+///
+/// let content = "```docassertrequest\nGET /blog\n```\n```docassertresponse\nHTTP 200\n```\n[retry]: # (3,100)";
+/// assert_eq!(
+///     parse_with_spans(content),
+///     vec![
+///         (BlockKind::Request, 1..4),
+///         (BlockKind::Response, 4..7),
+///         (BlockKind::Annotation("retry".to_string()), 7..8),
+///     ]
+/// );
+/// ```
+pub fn parse_with_spans(content: &str) -> Vec<(BlockKind, Range<usize>)> {
+    let mut spans = vec![];
+    let mut lines = content.lines().enumerate();
+    while let Some((idx, line)) = lines.next() {
+        let line_no = idx + 1;
+        let kind = if line.starts_with(DOC_ASSERT_REQUEST) {
+            BlockKind::Request
+        } else if line.starts_with(DOC_ASSERT_RESPONSE) {
+            BlockKind::Response
+        } else {
+            if let Some(prefix) = ANNOTATION_PREFIXES.iter().find(|p| line.starts_with(*p)) {
+                let name = prefix.trim_matches(['[', ']', ' ']).to_string();
+                spans.push((BlockKind::Annotation(name), line_no..line_no + 1));
             }
-            let (name, path) = get_variable_template(line)?;
+            continue;
+        };
 
-            let l = responses.len();
-            responses[l - 1].variables.insert(name, path);
-        }
+        // unterminated code blocks span until the end of the documentation
+        let end = lines
+            .by_ref()
+            .find(|(_, line)| line.starts_with("```"))
+            .map(|(idx, _)| idx + 2)
+            .unwrap_or(content.lines().count() + 1);
+        spans.push((kind, line_no..end));
+    }
 
-        if line.starts_with(RETRY_PREFIX) {
-            if responses.is_empty() || responses.len() != requests.len() {
-                return Err(format!("misplaced retry at line {}: {}", line_no, line));
-            }
-            let retry_policy = get_retry_policy(line)?;
+    spans
+}
 
-            let l = responses.len();
-            responses[l - 1].retries = retry_policy;
+/// Parses the test cases of the documentation. In the strict mode lines shaped like
+/// an annotation which follow a request but are not any of the known ones are errors.
+pub(crate) fn parse(path: String, strict: bool) -> Result<Vec<TestCase>, ParseError> {
+    let (mut requests, mut responses) = (vec![], vec![]);
+    let binding =
+        fs::read_to_string(&path).map_err(|e| ParseError::new(&path, 0, e.to_string()))?;
+    let mut lines = binding.lines().enumerate();
+    while let Some((mut line_no, line)) = lines.next() {
+        line_no += 1;
+        if strict && !requests.is_empty() {
+            check_annotation(line).map_err(|message| ParseError::new(&path, line_no, message))?;
         }
+        parse_line(line_no, line, &mut lines, &mut requests, &mut responses)
+            .map_err(|message| ParseError::new(&path, line_no, message))?;
     }
-    if requests.len() != responses.len() {
-        return Err(format!(
-            "there is {} requests and {} responses but you need equal number of both",
-            requests.len(),
-            responses.len()
+    if let Some(request) = requests.get(responses.len()) {
+        return Err(ParseError::new(
+            &path,
+            request.line_number,
+            "the request has no response below it".to_string(),
         ));
     }
 
-    let test_cases = requests
+    let mut test_cases = requests
         .iter()
         .zip(responses.iter())
         .map(|(req, resp)| TestCase {
@@ -110,15 +209,315 @@ pub(crate) fn parse(path: String) -> Result<Vec<TestCase>, String> {
         })
         .collect::<Vec<TestCase>>();
 
+    // setup runs before and teardown after the rest of the document,
+    // the order within each stage is preserved
+    test_cases.sort_by_key(|tc| tc.response.stage);
+
     Ok(test_cases)
 }
 
+/// Fails if the line has the shape of an annotation, `[keyword]: #`, but the keyword is unknown.
+/// Other reference definitions like the `[//]: #` comments are allowed.
+fn check_annotation(line: &str) -> Result<(), String> {
+    let re = Regex::new(r"^\[(?<keyword>[a-zA-Z][\w\s-]*)\]:\s*#").unwrap();
+    let Some(caps) = re.captures(line) else {
+        return Ok(());
+    };
+    if ANNOTATION_PREFIXES.iter().any(|p| line.starts_with(*p)) {
+        return Ok(());
+    }
+
+    Err(format!(
+        "unknown annotation [{}]: {}",
+        &caps["keyword"], line
+    ))
+}
+
+/// Parses a line of the documentation, reading the whole code block if the line starts one.
+fn parse_line(
+    line_no: usize,
+    line: &str,
+    lines: &mut Enumerate<Lines>,
+    requests: &mut Vec<Request>,
+    responses: &mut Vec<Response>,
+) -> Result<(), String> {
+    if line.starts_with(DOC_ASSERT_REQUEST) {
+        let request = get_request(line_no, get_code(lines))
+            .map_err(|err| format!("parsing error of a request code block: {}", err))?;
+        requests.push(request);
+    }
+
+    if line.starts_with(DOC_ASSERT_RESPONSE) {
+        if responses.len() == requests.len() {
+            return Err("the response has no request above it".to_string());
+        }
+        let response = get_response(line_no, get_code(lines))
+            .map_err(|err| format!("parsing error of a response code block: {}", err))?;
+        responses.push(response);
+    }
+
+    if line.starts_with(IGNORE_PREFIX) {
+        let i = annotated_case("ignore", line, requests, responses)?;
+        responses[i].ignore_paths.push(get_ignore_path(line)?);
+    }
+
+    if line.starts_with(IGNORE_ORDER_PREFIX) {
+        let i = annotated_case("ignore-order", line, requests, responses)?;
+        responses[i].ignore_orders.push(get_ignore_path(line)?);
+    }
+
+    if line.starts_with(EXACT_LENGTH_PREFIX) {
+        let i = annotated_case("exact-length", line, requests, responses)?;
+        responses[i].exact_lengths.push(get_ignore_path(line)?);
+    }
+
+    if line.starts_with(SET_EQUAL_PREFIX) {
+        let i = annotated_case("set-equal", line, requests, responses)?;
+        responses[i].set_equals.push(get_ignore_path(line)?);
+    }
+
+    if line.starts_with(CONTAINS_PREFIX) {
+        let i = annotated_case("contains", line, requests, responses)?;
+        responses[i].contains.push(get_ignore_path(line)?);
+    }
+
+    if line.starts_with(KEY_ORDER_PREFIX) {
+        let i = annotated_case("key-order", line, requests, responses)?;
+        responses[i].key_orders.extend(get_key_order_paths(line)?);
+    }
+
+    if line.starts_with(PARSE_JSON_PREFIX) {
+        let i = annotated_case("parse-json", line, requests, responses)?;
+        responses[i].parse_json.push(get_ignore_path(line)?);
+    }
+
+    if line.starts_with(ABSENT_PREFIX) {
+        let i = annotated_case("absent", line, requests, responses)?;
+        responses[i].absent_paths.push(get_ignore_path(line)?);
+    }
+
+    if line.starts_with(VARIABLE_PREFIX) {
+        let i = annotated_case("variable", line, requests, responses)?;
+        if line.contains(" from header]") {
+            let (name, header) = get_header_variable_template(line)?;
+            responses[i].header_variables.insert(name, header);
+        } else if line.contains(" from cookie]") {
+            let (name, cookie) = get_cookie_variable_template(line)?;
+            responses[i].cookie_variables.insert(name, cookie);
+        } else {
+            let (name, path) = get_variable_template(line)?;
+            responses[i].variables.insert(name, path);
+        }
+    }
+
+    if line.starts_with(RETRY_PREFIX) {
+        let i = annotated_case("retry", line, requests, responses)?;
+        let retry_policy = get_retry_policy(line)?;
+
+        responses[i].retries = retry_policy;
+    }
+
+    if line.starts_with(TIMEOUT_PREFIX) {
+        let i = annotated_case("timeout", line, requests, responses)?;
+        let timeout = get_timeout(line)?;
+
+        requests[i].timeout = Some(timeout);
+    }
+
+    if line.starts_with(PATCH_OF_PREFIX) {
+        let i = annotated_case("patch-of", line, requests, responses)?;
+        let name = get_patch_of(line)?;
+
+        requests[i].patch_of = Some(name);
+    }
+
+    if line.starts_with(NO_REDIRECT_PREFIX) {
+        let i = annotated_case("no-redirect", line, requests, responses)?;
+
+        requests[i].no_redirect = true;
+    }
+
+    if line.starts_with(BEFORE_ALL_PREFIX) || line.starts_with(AFTER_ALL_PREFIX) {
+        let i = annotated_case("stage", line, requests, responses)?;
+
+        responses[i].stage = if line.starts_with(BEFORE_ALL_PREFIX) {
+            Stage::BeforeAll
+        } else {
+            Stage::AfterAll
+        };
+    }
+
+    if line.starts_with(TEXT_PREFIX) {
+        let i = annotated_case("text", line, requests, responses)?;
+
+        responses[i].text = true;
+    }
+
+    if line.starts_with(COMPRESSED_PREFIX) {
+        let i = annotated_case("compressed", line, requests, responses)?;
+
+        responses[i].compressed = true;
+    }
+
+    if line.starts_with(PROBLEM_PREFIX) {
+        let i = annotated_case("problem", line, requests, responses)?;
+
+        responses[i].problem = true;
+    }
+
+    if line.starts_with(SCHEMA_PREFIX) {
+        let i = annotated_case("schema", line, requests, responses)?;
+        let schema = get_schema(line)?;
+
+        responses[i].schema = Some(schema);
+    }
+
+    if line.starts_with(COUNT_MATCHING_PREFIX) {
+        let i = annotated_case("count-matching", line, requests, responses)?;
+        let count_matching = get_count_matching(line)?;
+
+        responses[i].count_matchings.push(count_matching);
+    }
+
+    if line.starts_with(EACH_PREFIX) {
+        let i = annotated_case("each", line, requests, responses)?;
+        let each = get_each(line)?;
+
+        responses[i].each.push(each);
+    }
+
+    if line.starts_with(TRANSFORM_PREFIX) {
+        let i = annotated_case("transform", line, requests, responses)?;
+        let transform = get_transform(line)?;
+
+        responses[i].transforms.push(transform);
+    }
+
+    if line.starts_with(MATCH_PREFIX) {
+        let i = annotated_case("match", line, requests, responses)?;
+        let pattern_match = get_pattern_match(line)?;
+
+        responses[i].patterns.push(pattern_match);
+    }
+
+    if line.starts_with(TYPE_PREFIX) {
+        let i = annotated_case("type", line, requests, responses)?;
+        let type_check = get_type_check(line)?;
+
+        responses[i].type_checks.push(type_check);
+    }
+
+    if line.starts_with(NUMERIC_PREFIX) {
+        let i = annotated_case("numeric", line, requests, responses)?;
+        let numeric_mode = get_numeric_mode(line)?;
+
+        responses[i].numeric_mode = Some(numeric_mode);
+    }
+
+    if line.starts_with(TOLERANCE_PREFIX) {
+        let i = annotated_case("tolerance", line, requests, responses)?;
+        let tolerance = get_tolerance(line)?;
+
+        responses[i].numeric_mode = Some(NumericMode::Tolerance(tolerance));
+    }
+
+    if line.starts_with(COOKIE_CLEARED_PREFIX) {
+        let i = annotated_case("cookie-cleared", line, requests, responses)?;
+        let name = get_cookie_name(line)?;
+
+        responses[i].cleared_cookies.push(name);
+    }
+
+    if line.starts_with(COOKIE_PREFIX) {
+        let i = annotated_case("cookie", line, requests, responses)?;
+        let cookie = get_cookie_check(line)?;
+
+        responses[i].cookies.push(cookie);
+    }
+    if line.starts_with(TLS_PREFIX) {
+        let i = annotated_case("tls", line, requests, responses)?;
+        let tls_check = get_tls_check(line)?;
+
+        responses[i].tls_checks.push(tls_check);
+    }
+    if line.starts_with(RATE_LIMIT_PREFIX) {
+        let i = annotated_case("rate-limit", line, requests, responses)?;
+        let rate_limit = get_rate_limit(line)?;
+
+        responses[i].rate_limit = Some(rate_limit);
+    }
+    if line.starts_with(ETAG_PREFIX) {
+        let i = annotated_case("etag", line, requests, responses)?;
+        let etag = get_etag(line)?;
+
+        responses[i].etag = Some(etag);
+    }
+    if line.starts_with(POLL_PREFIX) {
+        let i = annotated_case("poll", line, requests, responses)?;
+        let poll = get_poll(line)?;
+
+        responses[i].poll = Some(poll);
+    }
+    if line.starts_with(REPEAT_PREFIX) {
+        let i = annotated_case("repeat", line, requests, responses)?;
+        let repeat = get_repeat(line)?;
+
+        responses[i].repeat = Some(repeat);
+    }
+    if line.starts_with(P95_PREFIX) {
+        let i = annotated_case("p95", line, requests, responses)?;
+        let threshold = get_p95(line)?;
+
+        responses[i].p95 = Some(threshold);
+    }
+    if line.starts_with(MAX_TIME_PREFIX) {
+        let i = annotated_case("max-time", line, requests, responses)?;
+        let max_time = get_max_time(line)?;
+
+        responses[i].max_time = Some(max_time);
+    }
+    if line.starts_with(STREAM_PREFIX) {
+        let i = annotated_case("stream", line, requests, responses)?;
+        let stream = get_stream(line)?;
+
+        responses[i].stream = Some(stream);
+    }
+
+    Ok(())
+}
+
+/// Returns the index of the test case the annotation applies to, the one of the nearest
+/// response above it. Fails if there is no response yet or the last request is still waiting for one.
+fn annotated_case(
+    name: &str,
+    line: &str,
+    requests: &[Request],
+    responses: &[Response],
+) -> Result<usize, String> {
+    match requests.get(responses.len()) {
+        _ if responses.is_empty() => Err(format!(
+            "misplaced {}: {} appears before any response",
+            name, line
+        )),
+        Some(request) => Err(format!(
+            "misplaced {}: {} appears between the request at line {} and its response",
+            name, line, request.line_number
+        )),
+        None => Ok(responses.len() - 1),
+    }
+}
+
+/// Reads the code block until its closing fence, skipping the comments before the request or
+/// status line. The comments of the rest of the block are told apart from the body later.
 fn get_code(lines: &mut Enumerate<Lines>) -> String {
     let mut buff = String::new();
     while let Some(line) = lines.next() {
         if line.1.starts_with("```") {
             break;
         }
+        if buff.is_empty() && is_comment_line(line.1) {
+            continue;
+        }
         buff.push_str(format!("{}\n", line.1).as_str());
     }
     buff
@@ -133,15 +532,37 @@ fn get_ignore_path(line: &str) -> Result<String, String> {
     path.remove(0);
     path.pop();
 
-    if let Err(e) = path.jsonpath() {
+    if let Err(e) = Path::parse(&path) {
         return Err(format!("invalid ignore path {}", e));
     }
 
     Ok(path)
 }
 
+fn get_key_order_paths(line: &str) -> Result<Vec<String>, String> {
+    let re = Regex::new(r"^\[key-order\]:\s#\s\((?<paths>[^()]+)\)").unwrap();
+
+    let caps = re
+        .captures(line)
+        .ok_or(format!("invalid key-order paths: {}", line))?;
+
+    caps["paths"]
+        .split(',')
+        .map(|path| {
+            let path = path.trim();
+            if let Err(e) = Path::parse(path) {
+                return Err(format!("invalid key-order path {}", e));
+            }
+            Ok(path.to_string())
+        })
+        .collect()
+}
+
 fn get_retry_policy(line: &str) -> Result<RetryPolicy, String> {
-    let re = Regex::new(r"^\[retry\]:\s#\s\((?<max_retries>\d+),\s*(?<delay>\d+)\)").unwrap();
+    let re = Regex::new(
+        r"^\[retry\]:\s#\s\((?<max_retries>\d+),\s*(?<delay>\d+)(?<statuses>(?:,\s*\d{3})*)(?:,\s*on=(?<on>[\w-]+))?\)",
+    )
+    .unwrap();
 
     let caps = re
         .captures(line)
@@ -161,162 +582,1765 @@ fn get_retry_policy(line: &str) -> Result<RetryPolicy, String> {
         .parse::<u64>()
         .map_err(|e| format!("invalid delay: {}", e))?;
 
-    Ok(RetryPolicy { max_retries, delay })
+    let on = match caps.name("on") {
+        Some(on) => on.as_str().parse::<RetryOn>()?,
+        None => RetryOn::default(),
+    };
+
+    let statuses = caps
+        .name("statuses")
+        .map(|statuses| {
+            statuses
+                .as_str()
+                .split(',')
+                .map(str::trim)
+                .filter(|status| !status.is_empty())
+                .map(|status| {
+                    status
+                        .parse::<u16>()
+                        .map_err(|e| format!("invalid retry status: {}", e))
+                })
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()?
+        .unwrap_or_default();
+
+    Ok(RetryPolicy {
+        max_retries,
+        delay,
+        on,
+        statuses,
+    })
 }
 
-fn get_variable_template(line: &str) -> Result<(String, Path), String> {
-    let re =
-        Regex::new(format!(r"^\[let\s(?<var>\w+)\]:\s#\s\((?<value>{JSON_PATH_REGEX})\)").as_str())
-            .unwrap();
+fn get_count_matching(line: &str) -> Result<CountMatching, String> {
+    let re = Regex::new(
+        format!(
+            r"^\[count-matching\]:\s#\s\((?<path>{JSON_PATH_REGEX})\s+(?<fragment>.+)\s+@(?<comparison>\w+):(?<count>\d+)\)"
+        )
+        .as_str(),
+    )
+    .unwrap();
 
     let caps = re
         .captures(line)
-        .ok_or(format!("invalid variable template: {}", line))?;
+        .ok_or(format!("invalid count-matching properties: {}", line))?;
 
-    let name = caps
-        .name("var")
-        .ok_or(format!("invalid variable template: {}", line))?;
+    let path = caps["path"].to_string();
+    if let Err(e) = path.jsonpath() {
+        return Err(format!("invalid count-matching path {}", e));
+    }
 
-    let value = caps
-        .name("value")
-        .ok_or(format!("invalid variable template: {}", line))?;
+    let fragment = serde_json::from_str(&caps["fragment"])
+        .map_err(|e| format!("invalid count-matching fragment: {}", e))?;
 
-    match value.as_str().jsonpath() {
-        Ok(p) => Ok((name.as_str().to_owned(), p)),
-        Err(e) => Err(format!("invalid variable template: {}: {}", line, e)),
-    }
+    let comparison = Comparison::from_str(&caps["comparison"])?;
+
+    let count = caps["count"]
+        .parse::<usize>()
+        .map_err(|e| format!("invalid count: {}", e))?;
+
+    Ok(CountMatching {
+        path,
+        fragment,
+        comparison,
+        count,
+    })
 }
 
-fn get_request(code_block_line_no: usize, code: String) -> Result<Request, String> {
-    let mut lines = code.lines();
+fn get_each(line: &str) -> Result<Each, String> {
+    let re = Regex::new(
+        format!(r"^\[each\]:\s#\s\((?<path>{JSON_PATH_REGEX})\s+(?<fragment>.+)\)").as_str(),
+    )
+    .unwrap();
 
-    // Parse HTTP method and URL
-    let parts = lines
-        .next()
-        .ok_or("expected HTTP method and URI".to_string())
-        .map(|line| line.split_whitespace().collect::<Vec<&str>>())?;
-    if parts.len() != 2 {
-        return Err(format!(
-            "invalid HTTP method or URI: \"{}\"",
-            parts.join(" ")
-        ));
+    let caps = re
+        .captures(line)
+        .ok_or(format!("invalid each properties: {}", line))?;
+
+    let path = caps["path"].to_string();
+    if let Err(e) = path.jsonpath() {
+        return Err(format!("invalid each path {}", e));
     }
 
-    let (headers, body) = get_headers_and_body(lines)?;
+    let fragment = serde_json::from_str(&caps["fragment"])
+        .map_err(|e| format!("invalid each fragment: {}", e))?;
 
-    Ok(Request {
-        http_method: HttpMethod::from_str(parts[0])?,
-        uri: parts[1].to_string(),
-        headers,
-        body,
-        line_number: code_block_line_no,
+    Ok(Each { path, fragment })
+}
+
+fn get_transform(line: &str) -> Result<Transform, String> {
+    let re = Regex::new(
+        format!(r"^\[transform\]:\s#\s\((?<path>{JSON_PATH_REGEX})\s+(?<transformation>[\w:]+)\)")
+            .as_str(),
+    )
+    .unwrap();
+
+    let caps = re
+        .captures(line)
+        .ok_or(format!("invalid transform properties: {}", line))?;
+
+    let path = caps["path"].to_string();
+    if let Err(e) = path.jsonpath() {
+        return Err(format!("invalid transform path {}", e));
+    }
+
+    Ok(Transform {
+        path,
+        transformation: caps["transformation"].parse()?,
     })
 }
 
-fn get_response(code_block_line_no: usize, code: String) -> Result<Response, String> {
-    let mut lines = code.lines();
+fn get_pattern_match(line: &str) -> Result<PatternMatch, String> {
+    let re = Regex::new(
+        format!(r"^\[match\]:\s#\s\((?<path>{JSON_PATH_REGEX}),\s*(?<pattern>.+)\)\s*$").as_str(),
+    )
+    .unwrap();
 
-    // Parse HTTP method and URL
-    let parts = lines
-        .next()
-        .ok_or("response code line not found".to_string())
-        .map(|line| line.split_whitespace().collect::<Vec<&str>>())?;
-    if parts.len() != 2 {
-        return Err(format!("invalid response code line {}", parts.join(" ")));
+    let caps = re
+        .captures(line)
+        .ok_or(format!("invalid match properties: {}", line))?;
+
+    let path = caps["path"].to_string();
+    if let Err(e) = path.jsonpath() {
+        return Err(format!("invalid match path {}", e));
     }
-    let http_code = parts[1]
-        .parse::<u16>()
-        .map_err(|err| format!("invalid HTTP code: {}", err))?;
-    if !(100..=599).contains(&http_code) {
-        return Err(format!("HTTP code {} outside of valid range", http_code));
+    let pattern = caps["pattern"].to_string();
+    if let Err(e) = Regex::new(&pattern) {
+        return Err(format!("invalid match pattern {}: {}", pattern, e));
     }
 
-    let (headers, body) = get_headers_and_body(lines)?;
+    Ok(PatternMatch { path, pattern })
+}
 
-    Ok(Response {
-        code: http_code,
-        headers,
-        ignore_paths: vec![],
-        ignore_orders: vec![],
-        body,
-        line_number: code_block_line_no,
-        variables: HashMap::new(),
-        retries: RetryPolicy::default(),
+fn get_type_check(line: &str) -> Result<TypeCheck, String> {
+    let re = Regex::new(
+        format!(r"^\[type\]:\s#\s\((?<path>{JSON_PATH_REGEX}),\s*(?<type>\w+)\)").as_str(),
+    )
+    .unwrap();
+
+    let caps = re
+        .captures(line)
+        .ok_or(format!("invalid type properties: {}", line))?;
+
+    let path = caps["path"].to_string();
+    if let Err(e) = path.jsonpath() {
+        return Err(format!("invalid type path {}", e));
+    }
+
+    Ok(TypeCheck {
+        path,
+        json_type: caps["type"].parse()?,
     })
 }
 
-fn get_headers_and_body(
-    mut lines: Lines,
-) -> Result<(HashMap<String, String>, Option<String>), String> {
-    let mut headers = HashMap::new();
-    let mut body = String::new();
-    for line in &mut lines {
-        if body.is_empty() && line.contains(':') && !line.contains('{') {
-            let header_parts = line.split(':').map(|s| s.trim()).collect::<Vec<&str>>();
-            if header_parts.len() != 2 {
-                return Err(format!("invalid header line {}", line));
-            }
-            headers.insert(header_parts[0].to_string(), header_parts[1].to_string());
-            continue;
-        }
-        body.push_str(line.trim());
+fn get_numeric_mode(line: &str) -> Result<NumericMode, String> {
+    let re =
+        Regex::new(r"^\[numeric\]:\s#\s\((?<mode>\w+)(?:\s+(?<tolerance>[^\s)]+))?\)").unwrap();
+
+    let caps = re
+        .captures(line)
+        .ok_or(format!("invalid numeric properties: {}", line))?;
+
+    match (&caps["mode"], caps.name("tolerance")) {
+        ("strict", None) => Ok(NumericMode::Strict),
+        ("float", None) => Ok(NumericMode::AssumeFloat),
+        ("tolerance", Some(tolerance)) => tolerance
+            .as_str()
+            .parse::<f64>()
+            .ok()
+            .filter(|tolerance| *tolerance >= 0.0)
+            .map(NumericMode::Tolerance)
+            .ok_or(format!("invalid numeric tolerance: {}", tolerance.as_str())),
+        ("sigfig", Some(figures)) => figures
+            .as_str()
+            .parse::<u32>()
+            .ok()
+            .filter(|figures| (1..=17).contains(figures))
+            .map(NumericMode::SignificantFigures)
+            .ok_or(format!(
+                "invalid number of significant figures: {}",
+                figures.as_str()
+            )),
+        _ => Err(format!("invalid numeric mode: {}", line)),
     }
-    let body = if body.is_empty() { None } else { Some(body) };
-    Ok((headers, body))
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::{domain::RetryPolicy, parser::parse};
+/// Parses the shorthand of `[numeric]: # (tolerance 0.01)`.
+fn get_tolerance(line: &str) -> Result<f64, String> {
+    let re = Regex::new(r"^\[tolerance\]:\s#\s\((?<tolerance>[^\s)]+)\)").unwrap();
 
-    #[test]
-    fn test_parse() {
-        let result = parse("tests/data/README.md".to_string());
-        assert!(result.is_ok());
-        let test_cases = result.unwrap();
-        assert_eq!(test_cases.len(), 2);
-        // request
-        assert_eq!(
-            test_cases[0].request.http_method,
-            crate::domain::HttpMethod::Post
-        );
-        assert_eq!(test_cases[0].request.uri, "/api/user");
-        assert_eq!(
-            test_cases[0].request.headers.get("Content-Type").unwrap(),
-            "application/json"
-        );
-        assert_eq!(
+    let caps = re
+        .captures(line)
+        .ok_or(format!("invalid tolerance properties: {}", line))?;
+
+    caps["tolerance"]
+        .parse::<f64>()
+        .ok()
+        .filter(|tolerance| *tolerance >= 0.0)
+        .ok_or(format!("invalid numeric tolerance: {}", &caps["tolerance"]))
+}
+
+fn get_cookie_name(line: &str) -> Result<String, String> {
+    let re = Regex::new(r"^\[[\w-]+\]:\s#\s\((?<name>[^\s;,=()]+)\)").unwrap();
+
+    let caps = re
+        .captures(line)
+        .ok_or(format!("invalid cookie name: {}", line))?;
+
+    Ok(caps["name"].to_string())
+}
+
+fn get_cookie_check(line: &str) -> Result<CookieCheck, String> {
+    let re = Regex::new(
+        r"^\[cookie\]:\s#\s\((?<name>[^\s;,=()]+)(?<attributes>(?:,\s*[\w-]+(?:=[^\s,()]+)?)*)\)",
+    )
+    .unwrap();
+
+    let caps = re
+        .captures(line)
+        .ok_or(format!("invalid cookie properties: {}", line))?;
+
+    Ok(CookieCheck {
+        name: caps["name"].to_string(),
+        attributes: caps["attributes"]
+            .split(',')
+            .map(str::trim)
+            .filter(|attribute| !attribute.is_empty())
+            .map(str::to_string)
+            .collect(),
+    })
+}
+
+fn get_tls_check(line: &str) -> Result<TlsCheck, String> {
+    let re = Regex::new(r"^\[tls\]:\s#\s\((?<check>[^\s()]+)\)").unwrap();
+
+    let caps = re
+        .captures(line)
+        .ok_or(format!("invalid TLS check: {}", line))?;
+
+    TlsCheck::from_str(&caps["check"])
+}
+
+fn get_rate_limit(line: &str) -> Result<RateLimit, String> {
+    let re = Regex::new(r"^\[rate-limit\]:\s#\s\((?<calls>\d+),\s*(?<header>[\w-]+)\)").unwrap();
+
+    let caps = re
+        .captures(line)
+        .ok_or(format!("invalid rate-limit properties: {}", line))?;
+
+    let calls = caps["calls"]
+        .parse::<u64>()
+        .map_err(|e| format!("invalid number of calls: {}", e))?;
+
+    Ok(RateLimit {
+        calls,
+        header: caps["header"].to_string(),
+    })
+}
+
+fn get_etag(line: &str) -> Result<Etag, String> {
+    let re =
+        Regex::new(r"^\[etag\]:\s#\s\((?<variable>\w+)(\s+(?<expectation>changed|unchanged))?\)")
+            .unwrap();
+
+    let caps = re
+        .captures(line)
+        .ok_or(format!("invalid etag properties: {}", line))?;
+
+    let expectation = match caps.name("expectation").map(|m| m.as_str()) {
+        Some("changed") => EtagExpectation::Changed,
+        Some(_) => EtagExpectation::Unchanged,
+        None => EtagExpectation::Capture,
+    };
+
+    Ok(Etag {
+        variable: caps["variable"].to_string(),
+        expectation,
+    })
+}
+
+fn get_poll(line: &str) -> Result<Poll, String> {
+    let re = Regex::new(
+        format!(
+            r"^\[poll\]:\s#\s\((?<timeout>\d+(ms|s)?),\s*(?<interval>\d+(ms|s)?),\s*(?<path>{JSON_PATH_REGEX})\s*==\s*(?<value>.+)\)"
+        )
+        .as_str(),
+    )
+    .unwrap();
+
+    let caps = re
+        .captures(line)
+        .ok_or(format!("invalid poll properties: {}", line))?;
+
+    let path = caps["path"].to_string();
+    if let Err(e) = path.jsonpath() {
+        return Err(format!("invalid poll path {}", e));
+    }
+
+    // values which are not valid JSON, like `ready`, are compared as strings
+    let value = caps["value"].trim();
+    let value = serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()));
+
+    Ok(Poll {
+        timeout: get_millis(&caps["timeout"])?,
+        interval: get_millis(&caps["interval"])?,
+        path,
+        value,
+    })
+}
+
+fn get_repeat(line: &str) -> Result<u64, String> {
+    let re = Regex::new(r"^\[repeat\]:\s#\s\((?<count>\d+)\)").unwrap();
+
+    let caps = re
+        .captures(line)
+        .ok_or(format!("invalid repeat properties: {}", line))?;
+
+    caps["count"]
+        .parse::<u64>()
+        .ok()
+        .filter(|count| *count > 0)
+        .ok_or(format!("invalid number of repeats: {}", &caps["count"]))
+}
+
+fn get_patch_of(line: &str) -> Result<String, String> {
+    let re = Regex::new(r"^\[patch-of\]:\s#\s\((?<var>\w+)\)").unwrap();
+
+    let caps = re
+        .captures(line)
+        .ok_or(format!("invalid patch-of properties: {}", line))?;
+
+    Ok(caps["var"].to_string())
+}
+
+fn get_schema(line: &str) -> Result<String, String> {
+    let re = Regex::new(r"^\[schema\]:\s#\s\((?<path>[^()]+)\)").unwrap();
+
+    let caps = re
+        .captures(line)
+        .ok_or(format!("invalid schema properties: {}", line))?;
+
+    Ok(caps["path"].trim().to_string())
+}
+
+fn get_timeout(line: &str) -> Result<Duration, String> {
+    let re = Regex::new(r"^\[timeout\]:\s#\s\((?<timeout>\d+(ms|s)?)\)").unwrap();
+
+    let caps = re
+        .captures(line)
+        .ok_or(format!("invalid timeout properties: {}", line))?;
+
+    let millis = get_millis(&caps["timeout"])?;
+    if millis == 0 {
+        return Err(format!("invalid timeout: {}", &caps["timeout"]));
+    }
+
+    Ok(Duration::from_millis(millis))
+}
+
+fn get_p95(line: &str) -> Result<u64, String> {
+    let re = Regex::new(r"^\[p95\]:\s#\s\((?<threshold>\d+(ms|s)?)\)").unwrap();
+
+    let caps = re
+        .captures(line)
+        .ok_or(format!("invalid p95 properties: {}", line))?;
+
+    get_millis(&caps["threshold"])
+}
+
+fn get_max_time(line: &str) -> Result<u64, String> {
+    let re = Regex::new(r"^\[max-time\]:\s#\s\((?<max_time>\d+(ms|s)?)\)").unwrap();
+
+    let caps = re
+        .captures(line)
+        .ok_or(format!("invalid max-time properties: {}", line))?;
+
+    get_millis(&caps["max_time"])
+}
+
+fn get_stream(line: &str) -> Result<Stream, String> {
+    let re = Regex::new(r"^\[stream\]:\s#\s\((?<properties>[^)]+)\)").unwrap();
+
+    let caps = re
+        .captures(line)
+        .ok_or(format!("invalid stream properties: {}", line))?;
+
+    let mut stream = Stream::default();
+    for property in caps["properties"].split(',').map(str::trim) {
+        match property.split_once('=') {
+            Some(("first-byte", value)) => stream.first_byte = Some(get_millis(value)?),
+            Some(("within", value)) => stream.within = Some(get_millis(value)?),
+            Some(("min-chunks", value)) => {
+                let min_chunks = value
+                    .parse::<u64>()
+                    .map_err(|e| format!("invalid number of chunks: {}", e))?;
+                stream.min_chunks = Some(min_chunks);
+            }
+            _ => return Err(format!("invalid stream property: {}", property)),
+        }
+    }
+
+    Ok(stream)
+}
+
+fn get_millis(duration: &str) -> Result<u64, String> {
+    let (value, multiplier) = match duration.strip_suffix("ms") {
+        Some(value) => (value, 1),
+        None => match duration.strip_suffix('s') {
+            Some(value) => (value, 1000),
+            None => (duration, 1),
+        },
+    };
+
+    value
+        .parse::<u64>()
+        .map(|value| value * multiplier)
+        .map_err(|e| format!("invalid duration {}: {}", duration, e))
+}
+
+fn get_variable_template(line: &str) -> Result<(String, Capture), String> {
+    let re = Regex::new(
+        format!(r"^\[let\s(?<var>\w+)\]:\s#\s\((?<length>length\s+)?(?<value>{JSON_PATH_REGEX}|{JSON_POINTER_REGEX})\)")
+            .as_str(),
+    )
+    .unwrap();
+
+    let caps = re
+        .captures(line)
+        .ok_or(format!("invalid variable template: {}", line))?;
+
+    let name = caps
+        .name("var")
+        .ok_or(format!("invalid variable template: {}", line))?;
+
+    let value = caps
+        .name("value")
+        .ok_or(format!("invalid variable template: {}", line))?;
+
+    match Path::parse(value.as_str()) {
+        Ok(p) if caps.name("length").is_some() => {
+            Ok((name.as_str().to_owned(), Capture::Length(p)))
+        }
+        Ok(p) => Ok((name.as_str().to_owned(), Capture::Value(p))),
+        Err(e) => Err(format!("invalid variable template: {}: {}", line, e)),
+    }
+}
+
+fn get_header_variable_template(line: &str) -> Result<(String, String), String> {
+    let re = Regex::new(r"^\[let\s(?<var>\w+)\sfrom\sheader\]:\s#\s\((?<header>[\w-]+)\)").unwrap();
+
+    let caps = re
+        .captures(line)
+        .ok_or(format!("invalid header variable template: {}", line))?;
+
+    Ok((caps["var"].to_string(), caps["header"].to_string()))
+}
+
+fn get_cookie_variable_template(line: &str) -> Result<(String, String), String> {
+    let re =
+        Regex::new(r"^\[let\s(?<var>\w+)\sfrom\scookie\]:\s#\s\((?<cookie>[^\s;,=()]+)\)").unwrap();
+
+    let caps = re
+        .captures(line)
+        .ok_or(format!("invalid cookie variable template: {}", line))?;
+
+    Ok((caps["var"].to_string(), caps["cookie"].to_string()))
+}
+
+fn get_request(code_block_line_no: usize, code: String) -> Result<Request, String> {
+    let mut lines = code.lines();
+
+    // Parse HTTP method and URL
+    let parts = lines
+        .next()
+        .ok_or("expected HTTP method and URI".to_string())
+        .map(|line| line.split_whitespace().collect::<Vec<&str>>())?;
+    if parts.len() != 2 {
+        return Err(format!(
+            "invalid HTTP method or URI: \"{}\"",
+            parts.join(" ")
+        ));
+    }
+
+    let (headers, body) = get_headers_and_body(lines.clone())?;
+    let is_multipart = headers.iter().any(|(key, value)| {
+        key.eq_ignore_ascii_case("content-type") && value.starts_with(MULTIPART_CONTENT_TYPE)
+    });
+    let multipart = if is_multipart {
+        Some(get_multipart_fields(lines)?)
+    } else {
+        None
+    };
+    let is_form = headers.iter().any(|(key, value)| {
+        key.eq_ignore_ascii_case("content-type") && value.starts_with(FORM_CONTENT_TYPE)
+    });
+    let form = match &body {
+        Some(body) if is_form => get_form_fields(body)?,
+        _ => None,
+    };
+    let raw_body = body.as_deref().map(get_raw_body).transpose()?.flatten();
+    let body = if raw_body.is_some() || multipart.is_some() || form.is_some() {
+        None
+    } else {
+        body
+    };
+
+    Ok(Request {
+        http_method: HttpMethod::from_str(parts[0])?,
+        uri: parts[1].to_string(),
+        headers,
+        body,
+        raw_body,
+        multipart,
+        form,
+        timeout: None,
+        patch_of: None,
+        no_redirect: false,
+        line_number: code_block_line_no,
+    })
+}
+
+fn get_raw_body(body: &str) -> Result<Option<Vec<u8>>, String> {
+    if let Some(data) = body.strip_prefix(BYTES_BASE64_PREFIX) {
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(data)
+            .map_err(|e| format!("invalid base64 body: {}", e))?;
+        return Ok(Some(bytes));
+    }
+
+    if let Some(path) = body.strip_prefix(FILE_BYTES_PREFIX) {
+        let bytes = fs::read(path).map_err(|e| format!("invalid body file {}: {}", path, e))?;
+        return Ok(Some(bytes));
+    }
+
+    Ok(None)
+}
+
+/// Parses the fields of a multipart body, one per line, either `name=value`
+/// for text fields or `name=@file:path` for file parts.
+fn get_multipart_fields(lines: Lines) -> Result<Vec<MultipartField>, String> {
+    let mut fields = vec![];
+    for line in lines.map(str::trim) {
+        if line.is_empty() || (fields.is_empty() && is_header_line(line)) {
+            continue;
+        }
+
+        let (name, value) = line
+            .split_once('=')
+            .ok_or(format!("invalid multipart field {}", line))?;
+        let field = match value.strip_prefix(FILE_PART_PREFIX) {
+            Some(path) => MultipartField::File {
+                name: name.to_string(),
+                file_name: std::path::Path::new(path)
+                    .file_name()
+                    .map(|file_name| file_name.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                bytes: fs::read(path)
+                    .map_err(|e| format!("invalid multipart file {}: {}", path, e))?,
+            },
+            None => MultipartField::Text {
+                name: name.to_string(),
+                value: value.to_string(),
+            },
+        };
+        fields.push(field);
+    }
+
+    Ok(fields)
+}
+
+/// Parses the fields of a URL-encoded form, one `field: value` per line. A body which is
+/// already encoded, like `title=Hello&draft=true`, is sent as it is, so no fields are returned.
+/// The line is encoded when its first `=` comes before any `:`, which the values may contain.
+fn get_form_fields(body: &str) -> Result<Option<Vec<(String, String)>>, String> {
+    let lines = body
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>();
+    if let [line] = lines[..] {
+        if line
+            .split_once('=')
+            .is_some_and(|(name, _)| !name.contains(':'))
+        {
+            return Ok(None);
+        }
+    }
+
+    lines
+        .into_iter()
+        .map(|line| {
+            line.split_once(':')
+                .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+                .ok_or(format!("invalid form field {}", line))
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(Some)
+}
+
+fn get_response(code_block_line_no: usize, code: String) -> Result<Response, String> {
+    let mut lines = code.lines();
+
+    // Parse HTTP method and URL
+    let parts = lines
+        .next()
+        .ok_or("response code line not found".to_string())
+        .map(|line| line.split_whitespace().collect::<Vec<&str>>())?;
+    if parts.len() != 2 {
+        return Err(format!("invalid response code line {}", parts.join(" ")));
+    }
+    let http_code = ExpectedStatus::from_str(parts[1])?;
+
+    let (headers, body) = get_headers_and_body(lines)?;
+
+    Ok(Response {
+        code: http_code,
+        headers,
+        ignore_paths: vec![],
+        ignore_orders: vec![],
+        set_equals: vec![],
+        contains: vec![],
+        key_orders: vec![],
+        parse_json: vec![],
+        exact_lengths: vec![],
+        absent_paths: vec![],
+        body,
+        line_number: code_block_line_no,
+        variables: HashMap::new(),
+        header_variables: HashMap::new(),
+        cookie_variables: HashMap::new(),
+        etag: None,
+        retries: RetryPolicy::default(),
+        stage: Stage::default(),
+        count_matchings: vec![],
+        each: vec![],
+        transforms: vec![],
+        patterns: vec![],
+        type_checks: vec![],
+        path_assertions: vec![],
+        cleared_cookies: vec![],
+        cookies: vec![],
+        tls_checks: vec![],
+        rate_limit: None,
+        poll: None,
+        repeat: None,
+        p95: None,
+        max_time: None,
+        stream: None,
+        numeric_mode: None,
+        no_unexpected_errors: false,
+        continue_on_capture_failure: false,
+        report_stale_example: false,
+        text: false,
+        compressed: false,
+        problem: false,
+        schema: None,
+        base_body: None,
+        diff_format: DiffFormat::default(),
+    })
+}
+
+/// Splits the lines following the request or status line into the headers and the body.
+/// The body starts after the first blank line or, when the headers are directly followed
+/// by a JSON, multipart, form or raw body, at the first line which isn't a header.
+/// The comments are skipped among the headers and in a JSON body, which can't contain such
+/// lines, while the other bodies are kept as written.
+fn get_headers_and_body(
+    mut lines: Lines,
+) -> Result<(HashMap<String, String>, Option<String>), String> {
+    let mut headers = HashMap::new();
+    let mut body = vec![];
+    let mut in_headers = true;
+    let mut after_comment = None;
+    let mut json = false;
+    for line in &mut lines {
+        if !in_headers {
+            if body.iter().all(|line: &&str| line.trim().is_empty()) {
+                json = line.trim_start().starts_with(['{', '[']);
+            }
+            if !(json && is_comment_line(line)) {
+                body.push(line);
+            }
+            continue;
+        }
+        if line.trim().is_empty() {
+            in_headers = false;
+            continue;
+        }
+        if is_comment_line(line) {
+            after_comment = Some(line);
+            continue;
+        }
+        if is_header_line(line) {
+            let (name, value) = line.split_once(':').unwrap();
+            headers.insert(name.trim().to_string(), value.trim().to_string());
+            after_comment = None;
+            continue;
+        }
+        if is_ambiguous_line(line) {
+            return Err(format!(
+                "line {} is neither a header nor the start of the body, separate the body from the headers with a blank line",
+                line
+            ));
+        }
+        // the preceding lines may as well be the first lines of a text or YAML body
+        if !headers.is_empty() && !is_structured_line(line) {
+            return Err(format!(
+                "line {} directly follows the headers, separate the body from the headers with a blank line",
+                line
+            ));
+        }
+        // the comment may as well be the first line of a text body, like a Markdown heading
+        if after_comment.is_some() && !is_structured_line(line) {
+            return Err(format!(
+                "line {} directly follows a comment, separate the body from the comments with a blank line",
+                line
+            ));
+        }
+        in_headers = false;
+        json = line.trim_start().starts_with(['{', '[']);
+        body.push(line);
+    }
+    if let (true, Some(comment)) = (in_headers, after_comment) {
+        return Err(format!(
+            "the block ends with the comment {}, which may be a text body, separate the body from the headers with a blank line or move the comment above them",
+            comment
+        ));
+    }
+    // the lines are kept as they are, so are the spaces within the string values
+    let body = body.join("\n");
+    let body = if body.trim().is_empty() {
+        None
+    } else {
+        Some(body)
+    };
+    Ok((headers, body))
+}
+
+fn is_comment_line(line: &str) -> bool {
+    let code = line.trim_start();
+    COMMENT_PREFIXES
+        .iter()
+        .any(|prefix| code.starts_with(prefix))
+}
+
+fn is_header_line(line: &str) -> bool {
+    // header names are tokens, so they never contain the braces or quotes of a JSON body, the `=`
+    // separating the names of multipart fields from their values or the `@` of the raw bodies
+    Regex::new(r"^[!#$%&'*+.^_`|~0-9A-Za-z-]+:")
+        .unwrap()
+        .is_match(line)
+}
+
+/// Checks if the line directly following the headers looks like a header with an invalid name,
+/// e.g. `Cache Control: no-cache`, rather than a body, which starts like JSON, a multipart field,
+/// a raw body or a text without a colon.
+fn is_ambiguous_line(line: &str) -> bool {
+    line.split_once(':')
+        .is_some_and(|(name, _)| !name.contains('=') && !name.starts_with(['{', '[', '"', '@']))
+}
+
+/// Checks if the line starts like a JSON, multipart, form or raw body, which can't be mistaken
+/// for headers, unlike a text or YAML body.
+fn is_structured_line(line: &str) -> bool {
+    line.trim_start().starts_with(['{', '[', '"', '@'])
+        || line
+            .split_once('=')
+            .is_some_and(|(name, _)| !name.contains(':'))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use crate::{
+        domain::{
+            Capture, Comparison, CookieCheck, CountMatching, Each, Etag, EtagExpectation,
+            ExpectedStatus, HttpMethod, MultipartField, PatternMatch, Poll, RateLimit, RetryOn,
+            RetryPolicy, Stage, Stream, TlsCheck, Transform, Transformation, TypeCheck,
+        },
+        json_diff::{path::JSONPath, JsonType, NumericMode},
+        parser::{
+            annotated_case, get_cookie_check, get_cookie_name, get_cookie_variable_template,
+            get_count_matching, get_each, get_etag, get_header_variable_template, get_ignore_path,
+            get_key_order_paths, get_max_time, get_numeric_mode, get_p95, get_pattern_match,
+            get_poll, get_rate_limit, get_repeat, get_request, get_response, get_retry_policy,
+            get_schema, get_stream, get_timeout, get_tls_check, get_tolerance, get_transform,
+            get_type_check, get_variable_template, parse, parse_with_spans, BlockKind,
+        },
+    };
+
+    #[test]
+    fn test_parse() {
+        let result = parse("tests/data/README.md".to_string(), false);
+        assert!(result.is_ok());
+        let test_cases = result.unwrap();
+        assert_eq!(test_cases.len(), 2);
+        // request
+        assert_eq!(
+            test_cases[0].request.http_method,
+            crate::domain::HttpMethod::Post
+        );
+        assert_eq!(test_cases[0].request.uri, "/api/user");
+        assert_eq!(
+            test_cases[0].request.headers.get("Content-Type").unwrap(),
+            "application/json"
+        );
+        assert_eq!(
             test_cases[0].request.body.as_ref().unwrap(),
-            "{\"name\": \"test\"}"
+            "{\n    \"name\": \"test\"\n}"
+        );
+        // response
+        assert_eq!(test_cases[0].response.code, ExpectedStatus::Exact(201));
+        assert_eq!(
+            test_cases[0].response.headers.get("Content-Type").unwrap(),
+            "application/json"
+        );
+        assert_eq!(
+            test_cases[0].response.body.as_ref().unwrap(),
+            "{\n    \"id\": 1,\n    \"name\": \"test\"\n}"
+        );
+        assert_eq!(test_cases[0].response.ignore_paths[0], "$.id".to_string());
+
+        assert_eq!(
+            test_cases[0]
+                .response
+                .variables
+                .get("name")
+                .unwrap()
+                .to_string(),
+            ".name".to_string()
+        );
+
+        assert_eq!(
+            &test_cases[0].response.retries,
+            &RetryPolicy {
+                max_retries: 3,
+                delay: 4500,
+                on: RetryOn::Both,
+                statuses: vec![],
+            }
+        );
+        assert_eq!(
+            test_cases[0].request.timeout,
+            Some(Duration::from_millis(5000))
+        );
+        assert_eq!(test_cases[1].request.timeout, None);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = parse("tests/data/misplaced.md".to_string(), false).unwrap_err();
+        assert_eq!(err.file(), "tests/data/misplaced.md");
+        assert_eq!(err.line(), 7);
+        assert_eq!(
+            err.message(),
+            "misplaced ignore: [ignore]: # ($.date_upd) appears before any response"
+        );
+
+        let err = parse("tests/data/unanswered.md".to_string(), false).unwrap_err();
+        assert_eq!(err.line(), 19);
+        assert_eq!(err.message(), "the request has no response below it");
+
+        let requests = [get_request(3, "GET /blog".to_string()).unwrap()];
+        let responses = [get_response(7, "HTTP 200".to_string()).unwrap()];
+        assert_eq!(
+            annotated_case("ignore", "[ignore]: # ($.id)", &requests, &responses),
+            Ok(0)
+        );
+        assert_eq!(
+            annotated_case("ignore", "[ignore]: # ($.id)", &requests, &responses[..0]),
+            Err("misplaced ignore: [ignore]: # ($.id) appears before any response".to_string())
+        );
+        let requests = [
+            requests[0].clone(),
+            get_request(11, "GET /blog/1".to_string()).unwrap(),
+        ];
+        assert_eq!(
+            annotated_case("ignore", "[ignore]: # ($.id)", &requests, &responses),
+            Err("misplaced ignore: [ignore]: # ($.id) appears between the request at line 11 and its response".to_string())
+        );
+
+        let err = parse("tests/data/invalid_request.md".to_string(), false).unwrap_err();
+        assert_eq!(err.line(), 11);
+        assert_eq!(
+            err.message(),
+            "parsing error of a request code block: FETCH is not a valid http method"
+        );
+        assert_eq!(
+            err.to_string(),
+            "tests/data/invalid_request.md:11: parsing error of a request code block: FETCH is not a valid http method"
+        );
+
+        let err = parse("tests/data/missing.md".to_string(), false).unwrap_err();
+        assert_eq!(err.line(), 0);
+        assert!(err.to_string().starts_with("tests/data/missing.md: "));
+    }
+
+    #[test]
+    fn test_parse_strict() {
+        let test_cases = parse("tests/data/misspelled.md".to_string(), false).unwrap();
+        assert!(test_cases[0].response.ignore_paths.is_empty());
+
+        let err = parse("tests/data/misspelled.md".to_string(), true).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "tests/data/misspelled.md:16: unknown annotation [ignroe]: [ignroe]: # ($.id)"
+        );
+        assert!(parse("tests/data/README.md".to_string(), true).is_ok());
+        assert!(parse("tests/data/stages.md".to_string(), true).is_ok());
+    }
+
+    #[test]
+    fn test_parse_comments() {
+        let test_cases = parse("tests/data/comments.md".to_string(), false).unwrap();
+        let request = &test_cases[0].request;
+        assert_eq!(request.line_number, 3);
+        assert_eq!(request.uri, "/blog");
+        assert_eq!(request.headers.len(), 1);
+        assert_eq!(
+            request.body,
+            Some(
+                "{\n    \"title\": \"My First Blog\",\n    \"body\": \"Blog content\"\n}"
+                    .to_string()
+            )
+        );
+        let response = &test_cases[0].response;
+        assert_eq!(response.code, ExpectedStatus::Exact(201));
+        assert_eq!(response.headers.len(), 1);
+        assert_eq!(
+            response.body,
+            Some("{\n    \"title\": \"My First Blog\"\n}".to_string())
+        );
+
+        let request = &test_cases[1].request;
+        assert_eq!(request.headers.len(), 1);
+        assert_eq!(request.body, Some("# Shopping list\n// milk".to_string()));
+
+        // a text body directly after the status line is kept, unless it follows a comment
+        let body = |code: &str| get_response(1, code.to_string()).map(|r| r.body);
+        assert_eq!(
+            body("HTTP 200\n# Title\n\n# Title\nSome text\n"),
+            Ok(Some("# Title\nSome text".to_string()))
+        );
+        assert_eq!(
+            body("HTTP 200\nOK # not a comment\n"),
+            Ok(Some("OK # not a comment".to_string()))
+        );
+        assert_eq!(
+            body("HTTP 200\n# Title\nSome text\n"),
+            Err("line Some text directly follows a comment, separate the body from the comments with a blank line".to_string())
+        );
+        assert_eq!(
+            body("HTTP 200\n# Title\n"),
+            Err("the block ends with the comment # Title, which may be a text body, separate the body from the headers with a blank line or move the comment above them".to_string())
+        );
+        // comments after the blank line are skipped in a JSON body
+        assert_eq!(
+            body("HTTP 200\n\n[\n  # the first post\n  1\n]\n"),
+            Ok(Some("[\n  1\n]".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_get_response_code() {
+        let code = |line: &str| get_response(1, line.to_string()).map(|r| r.code);
+        assert_eq!(code("HTTP 204"), Ok(ExpectedStatus::Exact(204)));
+        assert_eq!(code("HTTP 2xx"), Ok(ExpectedStatus::Class(2)));
+        assert_eq!(code("HTTP 4XX"), Ok(ExpectedStatus::Class(4)));
+        assert_eq!(code("HTTP 200-299"), Ok(ExpectedStatus::Range(200, 299)));
+        assert_eq!(code("HTTP 200-200"), Ok(ExpectedStatus::Range(200, 200)));
+        assert_eq!(
+            code("HTTP 299-200"),
+            Err("invalid HTTP code range 299-200".to_string())
+        );
+        assert_eq!(
+            code("HTTP 200-600"),
+            Err("HTTP code 600 outside of valid range".to_string())
+        );
+        assert_eq!(
+            code("HTTP 6xx"),
+            Err("invalid HTTP code class 6xx".to_string())
+        );
+        assert_eq!(
+            code("HTTP 20xx"),
+            Err("invalid HTTP code class 20xx".to_string())
+        );
+        assert!(code("HTTP 2x").is_err());
+    }
+
+    #[test]
+    fn test_parse_text() {
+        let test_cases = parse("tests/data/text.md".to_string(), false).unwrap();
+        assert!(test_cases[0].response.text);
+        assert_eq!(test_cases[0].response.body, Some("OK".to_string()));
+        assert_eq!(
+            test_cases[1].response.body,
+            Some("Welcome to the blog\n  - posts are moderated\n  - be nice".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_problem() {
+        let test_cases = parse("tests/data/problem.md".to_string(), false).unwrap();
+        assert!(test_cases[0].response.problem);
+        assert_eq!(test_cases[0].response.code, ExpectedStatus::Exact(404));
+    }
+
+    #[test]
+    fn test_parse_no_redirect() {
+        let test_cases = parse("tests/data/redirect.md".to_string(), false).unwrap();
+        assert!(test_cases[0].request.no_redirect);
+        assert!(!test_cases[1].request.no_redirect);
+    }
+
+    #[test]
+    fn test_parse_contains() {
+        let test_cases = parse("tests/data/contains.md".to_string(), true).unwrap();
+        assert_eq!(
+            test_cases[0].response.contains,
+            vec!["$.releases".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_parse_absent() {
+        let test_cases = parse("tests/data/absent.md".to_string(), true).unwrap();
+        assert_eq!(
+            test_cases[0].response.absent_paths,
+            vec!["$.password".to_string(), "$..token".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_parse_stages() {
+        let test_cases = parse("tests/data/stages.md".to_string(), false).unwrap();
+        let order = test_cases
+            .iter()
+            .map(|tc| (tc.request.uri.as_str(), tc.response.stage))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            order,
+            vec![
+                ("/session", Stage::BeforeAll),
+                ("/items/`token`", Stage::Main),
+                ("/items", Stage::Main),
+                ("/session/`token`", Stage::AfterAll),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_with_spans() {
+        let content = std::fs::read_to_string("tests/data/stages.md").unwrap();
+        let spans = parse_with_spans(&content);
+
+        let annotation = |name: &str| BlockKind::Annotation(name.to_string());
+        assert_eq!(
+            spans,
+            vec![
+                (BlockKind::Request, 5..8),
+                (BlockKind::Response, 9..12),
+                (annotation("after-all"), 13..14),
+                (BlockKind::Request, 15..18),
+                (BlockKind::Response, 19..22),
+                (BlockKind::Request, 23..26),
+                (BlockKind::Response, 27..30),
+                (BlockKind::Request, 33..36),
+                (BlockKind::Response, 37..43),
+                (annotation("let"), 44..45),
+                (annotation("before-all"), 45..46),
+            ]
+        );
+
+        assert_eq!(
+            parse_with_spans("text\n```docassertrequest\nGET /blog\n"),
+            vec![(BlockKind::Request, 2..4)]
+        );
+    }
+
+    #[test]
+    fn test_json_pointer() {
+        assert_eq!(
+            get_ignore_path("[ignore]: # (/data/0/date_upd)"),
+            Ok("/data/0/date_upd".to_string())
+        );
+        assert!(get_ignore_path("[ignore]: # (/data/~2)").is_err());
+        assert_eq!(
+            get_variable_template("[let id]: # (/data/0/id)"),
+            Ok((
+                "id".to_string(),
+                Capture::Value("$.data[0].id".jsonpath().unwrap())
+            ))
+        );
+        assert_eq!(
+            get_variable_template("[let id]: # ($.data[0].id)"),
+            Ok((
+                "id".to_string(),
+                Capture::Value("$.data[0].id".jsonpath().unwrap())
+            ))
+        );
+        assert_eq!(
+            get_variable_template("[let count]: # (length /data)"),
+            Ok((
+                "count".to_string(),
+                Capture::Length("$.data".jsonpath().unwrap())
+            ))
+        );
+    }
+
+    #[test]
+    fn test_get_count_matching() {
+        let count_matching =
+            get_count_matching(r#"[count-matching]: # ($.tasks {"status": "done"} @gte:2)"#);
+        assert_eq!(
+            count_matching,
+            Ok(CountMatching {
+                path: "$.tasks".to_string(),
+                fragment: json!({"status": "done"}),
+                comparison: Comparison::Gte,
+                count: 2,
+            })
         );
-        // response
-        assert_eq!(test_cases[0].response.code, 201);
+
+        let count_matching = get_count_matching(r#"[count-matching]: # ($ {"id": 1} @eq:0)"#);
         assert_eq!(
-            test_cases[0].response.headers.get("Content-Type").unwrap(),
-            "application/json"
+            count_matching.map(|c| (c.path, c.comparison, c.count)),
+            Ok(("$".to_string(), Comparison::Eq, 0))
+        );
+
+        assert!(get_count_matching(r#"[count-matching]: # ($.tasks {"status": "done"})"#).is_err());
+        assert!(get_count_matching(r#"[count-matching]: # ($.tasks {status} @gte:2)"#).is_err());
+        assert!(
+            get_count_matching(r#"[count-matching]: # ($.tasks {"status": "done"} @ge:2)"#)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_get_request_methods() {
+        let request = get_request(
+            1,
+            "PATCH /blog/1\nContent-Type: application/json\n{\"title\": \"Updated\"}\n".to_string(),
+        )
+        .unwrap();
+        assert_eq!(request.http_method, HttpMethod::Patch);
+        assert_eq!(request.uri, "/blog/1");
+        assert_eq!(request.body.unwrap(), "{\"title\": \"Updated\"}");
+
+        for (method, http_method) in [("HEAD", HttpMethod::Head), ("OPTIONS", HttpMethod::Options)]
+        {
+            let request = get_request(1, format!("{} /blog/1\n", method)).unwrap();
+            assert_eq!(request.http_method, http_method);
+            assert_eq!(request.http_method.to_string(), method);
+        }
+    }
+
+    #[test]
+    fn test_get_request_headers_and_body() {
+        // the blank line separates the headers from a body whose first line looks like a header
+        let request = get_request(
+            1,
+            "POST /links\nContent-Type: application/json\nReferer: http://localhost:8080/docs\n\n\"url\": \"http://example.com\"\n"
+                .to_string(),
+        )
+        .unwrap();
+        assert_eq!(
+            request.headers.get("Referer").unwrap(),
+            "http://localhost:8080/docs"
         );
         assert_eq!(
-            test_cases[0].response.body.as_ref().unwrap(),
-            "{\"id\": 1,\"name\": \"test\"}"
+            request.body,
+            Some(r#""url": "http://example.com""#.to_string())
         );
-        assert_eq!(test_cases[0].response.ignore_paths[0], "$.id".to_string());
 
+        let request = get_request(
+            1,
+            "POST /links\nContent-Type: application/json\n\nLocation: http://example.com\n"
+                .to_string(),
+        )
+        .unwrap();
+        assert_eq!(request.headers.len(), 1);
         assert_eq!(
-            test_cases[0]
-                .response
-                .variables
-                .get("name")
-                .unwrap()
+            request.body,
+            Some("Location: http://example.com".to_string())
+        );
+
+        // without the blank line the body starts at the first line which isn't a header
+        let request = get_request(
+            1,
+            "POST /links\nContent-Type: application/json\n{ \"url\": \"http://example.com\" }\n"
                 .to_string(),
-            ".name".to_string()
+        )
+        .unwrap();
+        assert_eq!(request.headers.len(), 1);
+        assert_eq!(
+            request.body,
+            Some(r#"{ "url": "http://example.com" }"#.to_string())
         );
 
+        let response = get_response(
+            1,
+            "HTTP 201\nLocation: http://localhost:8080/links/1\nX-Filter: {\"id\": 1}\n"
+                .to_string(),
+        )
+        .unwrap();
         assert_eq!(
-            &test_cases[0].response.retries,
-            &RetryPolicy {
-                max_retries: 3,
-                delay: 4500
-            }
+            response.headers.get("Location").unwrap(),
+            "http://localhost:8080/links/1"
+        );
+        assert_eq!(response.headers.get("X-Filter").unwrap(), r#"{"id": 1}"#);
+        assert_eq!(response.body, None);
+
+        assert_eq!(
+            get_request(1, "POST /links\nCache Control: no-cache\n".to_string()).unwrap_err(),
+            "line Cache Control: no-cache is neither a header nor the start of the body, separate the body from the headers with a blank line"
+        );
+
+        // a YAML body is told apart from the headers by the blank line only
+        let yaml = "name: doc-assert\ntags:\n  - rust";
+        assert_eq!(
+            get_request(
+                1,
+                format!("POST /config\nContent-Type: application/yaml\n{}\n", yaml)
+            )
+            .unwrap_err(),
+            "line   - rust directly follows the headers, separate the body from the headers with a blank line"
+        );
+        let request = get_request(
+            1,
+            format!("POST /config\nContent-Type: application/yaml\n\n{}\n", yaml),
+        )
+        .unwrap();
+        assert_eq!(request.headers.len(), 1);
+        assert_eq!(request.body, Some(yaml.to_string()));
+
+        assert_eq!(
+            get_response(1, "HTTP 200\nContent-Type: text/plain\nNote: draft\nto be reviewed\n".to_string())
+                .unwrap_err(),
+            "line to be reviewed directly follows the headers, separate the body from the headers with a blank line"
+        );
+    }
+
+    #[test]
+    fn test_get_body_whitespace() {
+        let request = get_request(
+            1,
+            "POST /blog\nContent-Type: application/json\n\n{\n  \"title\": \"My   First  Blog\"\n}\n"
+                .to_string(),
+        )
+        .unwrap();
+        assert_eq!(
+            request.body,
+            Some("{\n  \"title\": \"My   First  Blog\"\n}".to_string())
+        );
+
+        let response = get_response(
+            1,
+            "HTTP 200\nContent-Type: application/yaml\n\nblog:\n  title: My First Blog\n\n  tags: []\n"
+                .to_string(),
+        )
+        .unwrap();
+        assert_eq!(
+            response.body,
+            Some("blog:\n  title: My First Blog\n\n  tags: []".to_string())
+        );
+    }
+
+    #[test]
+    fn test_get_request_form() {
+        let request = get_request(
+            1,
+            "POST /login\nContent-Type: application/x-www-form-urlencoded\n\nusername: admin\nredirect: http://localhost/home\n"
+                .to_string(),
+        )
+        .unwrap();
+        assert_eq!(request.body, None);
+        assert_eq!(
+            request.form,
+            Some(vec![
+                ("username".to_string(), "admin".to_string()),
+                ("redirect".to_string(), "http://localhost/home".to_string()),
+            ])
+        );
+
+        let request = get_request(
+            1,
+            "POST /login\nContent-Type: application/x-www-form-urlencoded\n\nusername=admin&remember=true\n"
+                .to_string(),
+        )
+        .unwrap();
+        assert_eq!(request.form, None);
+        assert_eq!(
+            request.body,
+            Some("username=admin&remember=true".to_string())
+        );
+
+        let request = get_request(
+            1,
+            "POST /login\nContent-Type: application/x-www-form-urlencoded\n\nredirect=http://x&a=b\n"
+                .to_string(),
+        )
+        .unwrap();
+        assert_eq!(request.form, None);
+        assert_eq!(request.body, Some("redirect=http://x&a=b".to_string()));
+
+        let request = get_request(
+            1,
+            "POST /login\nContent-Type: application/x-www-form-urlencoded\n\nredirect: http://x?a=b\n"
+                .to_string(),
+        )
+        .unwrap();
+        assert_eq!(
+            request.form,
+            Some(vec![("redirect".to_string(), "http://x?a=b".to_string())])
+        );
+
+        assert!(get_request(
+            1,
+            "POST /login\nContent-Type: application/x-www-form-urlencoded\n\nusername: admin\nremember\n"
+                .to_string(),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_get_request_raw_body() {
+        let request = get_request(
+            1,
+            "POST /upload\nContent-Type: application/octet-stream\n@bytes:base64,AP8Q\n"
+                .to_string(),
+        )
+        .unwrap();
+        assert_eq!(request.body, None);
+        assert_eq!(request.raw_body, Some(vec![0x00, 0xff, 0x10]));
+        assert_eq!(
+            request.headers.get("Content-Type").unwrap(),
+            "application/octet-stream"
+        );
+
+        let request = get_request(
+            1,
+            "POST /upload\n@file-bytes:tests/data/expected.json\n".to_string(),
+        )
+        .unwrap();
+        assert_eq!(
+            request.raw_body,
+            Some(std::fs::read("tests/data/expected.json").unwrap())
+        );
+
+        assert!(get_request(1, "POST /upload\n@bytes:base64,!!!\n".to_string()).is_err());
+        assert!(get_request(1, "POST /upload\n@file-bytes:not/a/file\n".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_get_request_multipart() {
+        let request = get_request(
+            1,
+            "POST /upload\nContent-Type: multipart/form-data\n\ntitle=My First Blog\nattachment=@file:tests/data/expected.json\n"
+                .to_string(),
+        )
+        .unwrap();
+        assert_eq!(request.body, None);
+        assert_eq!(
+            request.multipart,
+            Some(vec![
+                MultipartField::Text {
+                    name: "title".to_string(),
+                    value: "My First Blog".to_string(),
+                },
+                MultipartField::File {
+                    name: "attachment".to_string(),
+                    file_name: "expected.json".to_string(),
+                    bytes: std::fs::read("tests/data/expected.json").unwrap(),
+                },
+            ])
+        );
+
+        assert!(get_request(
+            1,
+            "POST /upload\nContent-Type: multipart/form-data\nattachment=@file:not/a/file\n"
+                .to_string()
+        )
+        .is_err());
+        assert!(get_request(
+            1,
+            "POST /upload\nContent-Type: multipart/form-data\ntitle\n".to_string()
         )
+        .is_err());
+    }
+
+    #[test]
+    fn test_get_each() {
+        assert_eq!(
+            get_each(r#"[each]: # ($.items {"status": "done", "tags": []})"#),
+            Ok(Each {
+                path: "$.items".to_string(),
+                fragment: json!({"status": "done", "tags": []}),
+            })
+        );
+        assert_eq!(
+            get_each(r#"[each]: # ($.orders[*].items {"id": 1})"#)
+                .unwrap()
+                .path,
+            "$.orders[*].items"
+        );
+        assert!(get_each("[each]: # ($.items)").is_err());
+        assert!(get_each("[each]: # ($.items {status})").is_err());
+    }
+
+    #[test]
+    fn test_get_transform() {
+        assert_eq!(
+            get_transform("[transform]: # ($.tags sort)"),
+            Ok(Transform {
+                path: "$.tags".to_string(),
+                transformation: Transformation::Sort,
+            })
+        );
+        assert_eq!(
+            get_transform("[transform]: # ($.items[*].price round:2)"),
+            Ok(Transform {
+                path: "$.items[*].price".to_string(),
+                transformation: Transformation::Round(2),
+            })
+        );
+        assert!(get_transform("[transform]: # ($.tags reverse)").is_err());
+        assert!(get_transform("[transform]: # ($.price round:two)").is_err());
+        assert!(get_transform("[transform]: # (sort)").is_err());
+    }
+
+    #[test]
+    fn test_get_pattern_match() {
+        assert_eq!(
+            get_pattern_match("[match]: # ($.slug, ^[a-z0-9-]+$)"),
+            Ok(PatternMatch {
+                path: "$.slug".to_string(),
+                pattern: "^[a-z0-9-]+$".to_string(),
+            })
+        );
+        assert_eq!(
+            get_pattern_match(
+                "[match]: # ($.posts[*].date_upd, ^\\d{4}-\\d{2}-\\d{2}( \\d{2}:\\d{2})?$)"
+            ),
+            Ok(PatternMatch {
+                path: "$.posts[*].date_upd".to_string(),
+                pattern: "^\\d{4}-\\d{2}-\\d{2}( \\d{2}:\\d{2})?$".to_string(),
+            })
+        );
+        assert!(get_pattern_match("[match]: # ($.slug)").is_err());
+        assert!(get_pattern_match("[match]: # (slug, ^[a-z]+$)").is_err());
+        assert!(get_pattern_match("[match]: # ($.slug, ^[a-z+$)").is_err());
+    }
+
+    #[test]
+    fn test_get_header_variable_template() {
+        assert_eq!(
+            get_header_variable_template("[let token from header]: # (Authorization)"),
+            Ok(("token".to_string(), "Authorization".to_string()))
+        );
+        assert_eq!(
+            get_header_variable_template("[let rateLimit from header]: # (X-RateLimit-Remaining)"),
+            Ok(("rateLimit".to_string(), "X-RateLimit-Remaining".to_string()))
+        );
+        assert!(get_header_variable_template("[let token from header]: # ()").is_err());
+        assert!(get_header_variable_template("[let token from body]: # (Authorization)").is_err());
+    }
+
+    #[test]
+    fn test_get_key_order_paths() {
+        assert_eq!(
+            get_key_order_paths("[key-order]: # ($.data)"),
+            Ok(vec!["$.data".to_string()])
+        );
+        assert_eq!(
+            get_key_order_paths("[key-order]: # ($.data, $.items[*].meta,/links)"),
+            Ok(vec![
+                "$.data".to_string(),
+                "$.items[*].meta".to_string(),
+                "/links".to_string()
+            ])
+        );
+        assert!(get_key_order_paths("[key-order]: # ()").is_err());
+        assert!(get_key_order_paths("[key-order]: # ($.data, data)").is_err());
+    }
+
+    #[test]
+    fn test_get_type_check() {
+        assert_eq!(
+            get_type_check("[type]: # ($.id, number)"),
+            Ok(TypeCheck {
+                path: "$.id".to_string(),
+                json_type: JsonType::Number,
+            })
+        );
+        assert_eq!(
+            get_type_check("[type]: # ($.posts[*].published,boolean)"),
+            Ok(TypeCheck {
+                path: "$.posts[*].published".to_string(),
+                json_type: JsonType::Boolean,
+            })
+        );
+        assert!(get_type_check("[type]: # ($.id)").is_err());
+        assert!(get_type_check("[type]: # ($.id, integer)").is_err());
+    }
+
+    #[test]
+    fn test_get_numeric_mode() {
+        assert_eq!(
+            get_numeric_mode("[numeric]: # (strict)"),
+            Ok(NumericMode::Strict)
+        );
+        assert_eq!(
+            get_numeric_mode("[numeric]: # (float)"),
+            Ok(NumericMode::AssumeFloat)
+        );
+        assert_eq!(
+            get_numeric_mode("[numeric]: # (tolerance 0.01)"),
+            Ok(NumericMode::Tolerance(0.01))
+        );
+        assert_eq!(
+            get_numeric_mode("[numeric]: # (sigfig 3)"),
+            Ok(NumericMode::SignificantFigures(3))
+        );
+        assert!(get_numeric_mode("[numeric]: # (tolerance)").is_err());
+        assert!(get_numeric_mode("[numeric]: # (sigfig 0)").is_err());
+        assert!(get_numeric_mode("[numeric]: # (sigfig 2.5)").is_err());
+        assert!(get_numeric_mode("[numeric]: # (tolerance -1)").is_err());
+        assert!(get_numeric_mode("[numeric]: # (strict 0.01)").is_err());
+        assert!(get_numeric_mode("[numeric]: # (loose)").is_err());
+    }
+
+    #[test]
+    fn test_get_tolerance() {
+        assert_eq!(get_tolerance("[tolerance]: # (0.001)"), Ok(0.001));
+        assert_eq!(get_tolerance("[tolerance]: # (1)"), Ok(1.0));
+        assert!(get_tolerance("[tolerance]: # (-0.1)").is_err());
+        assert!(get_tolerance("[tolerance]: # (small)").is_err());
+        assert!(get_tolerance("[tolerance]: # ()").is_err());
+    }
+
+    #[test]
+    fn test_get_cookie_name() {
+        assert_eq!(
+            get_cookie_name("[cookie-cleared]: # (session)"),
+            Ok("session".to_string())
+        );
+        assert!(get_cookie_name("[cookie-cleared]: # ()").is_err());
+        assert!(get_cookie_name("[cookie-cleared]: # (session, other)").is_err());
+    }
+
+    #[test]
+    fn test_get_cookie_check() {
+        assert_eq!(
+            get_cookie_check("[cookie]: # (session, HttpOnly, Secure, SameSite=Strict)"),
+            Ok(CookieCheck {
+                name: "session".to_string(),
+                attributes: vec![
+                    "HttpOnly".to_string(),
+                    "Secure".to_string(),
+                    "SameSite=Strict".to_string()
+                ],
+            })
+        );
+        assert_eq!(
+            get_cookie_check("[cookie]: # (session)")
+                .unwrap()
+                .attributes,
+            Vec::<String>::new()
+        );
+        assert!(get_cookie_check("[cookie]: # ()").is_err());
+
+        assert_eq!(
+            get_cookie_variable_template("[let sid from cookie]: # (session)"),
+            Ok(("sid".to_string(), "session".to_string()))
+        );
+        assert!(get_cookie_variable_template("[let sid from cookie]: # (session, id)").is_err());
+    }
+
+    #[test]
+    fn test_get_tls_check() {
+        assert_eq!(
+            get_tls_check("[tls]: # (cn=example.com)"),
+            Ok(TlsCheck::CommonName("example.com".to_string()))
+        );
+        assert_eq!(
+            get_tls_check("[tls]: # (not-expired)"),
+            Ok(TlsCheck::NotExpired)
+        );
+        assert!(get_tls_check("[tls]: # (cn=)").is_err());
+        assert!(get_tls_check("[tls]: # (expired)").is_err());
+    }
+
+    #[test]
+    fn test_get_retry_policy() {
+        assert_eq!(
+            get_retry_policy("[retry]: # (3, 200)"),
+            Ok(RetryPolicy {
+                max_retries: 3,
+                delay: 200,
+                on: RetryOn::Both,
+                statuses: vec![],
+            })
+        );
+        assert_eq!(
+            get_retry_policy("[retry]: # (3, 200, on=error)"),
+            Ok(RetryPolicy {
+                max_retries: 3,
+                delay: 200,
+                on: RetryOn::Error,
+                statuses: vec![],
+            })
+        );
+        assert_eq!(
+            get_retry_policy("[retry]: # (3,200,on=assert)").unwrap().on,
+            RetryOn::Assert
+        );
+        assert_eq!(
+            get_retry_policy("[retry]: # (5, 1000, 503)"),
+            Ok(RetryPolicy {
+                max_retries: 5,
+                delay: 1000,
+                on: RetryOn::Both,
+                statuses: vec![503],
+            })
+        );
+        assert_eq!(
+            get_retry_policy("[retry]: # (5,1000,502,503,on=error)"),
+            Ok(RetryPolicy {
+                max_retries: 5,
+                delay: 1000,
+                on: RetryOn::Error,
+                statuses: vec![502, 503],
+            })
+        );
+        assert!(get_retry_policy("[retry]: # (3, 200, on=timeout)").is_err());
+    }
+
+    #[test]
+    fn test_get_rate_limit() {
+        assert_eq!(
+            get_rate_limit("[rate-limit]: # (3, X-RateLimit-Remaining)"),
+            Ok(RateLimit {
+                calls: 3,
+                header: "X-RateLimit-Remaining".to_string()
+            })
+        );
+        assert!(get_rate_limit("[rate-limit]: # (X-RateLimit-Remaining)").is_err());
+    }
+
+    #[test]
+    fn test_get_etag() {
+        assert_eq!(
+            get_etag("[etag]: # (blogEtag)"),
+            Ok(Etag {
+                variable: "blogEtag".to_string(),
+                expectation: EtagExpectation::Capture
+            })
+        );
+        assert_eq!(
+            get_etag("[etag]: # (blogEtag changed)").map(|etag| etag.expectation),
+            Ok(EtagExpectation::Changed)
+        );
+        assert_eq!(
+            get_etag("[etag]: # (blogEtag unchanged)").map(|etag| etag.expectation),
+            Ok(EtagExpectation::Unchanged)
+        );
+        assert!(get_etag("[etag]: # (blogEtag stale)").is_err());
+    }
+
+    #[test]
+    fn test_get_repeat() {
+        assert_eq!(get_repeat("[repeat]: # (20)"), Ok(20));
+        assert!(get_repeat("[repeat]: # (0)").is_err());
+        assert!(get_repeat("[repeat]: # (many)").is_err());
+    }
+
+    #[test]
+    fn test_get_schema() {
+        assert_eq!(
+            get_schema("[schema]: # (tests/data/blog.schema.json)"),
+            Ok("tests/data/blog.schema.json".to_string())
+        );
+        assert!(get_schema("[schema]: # ()").is_err());
+    }
+
+    #[test]
+    fn test_get_timeout() {
+        assert_eq!(
+            get_timeout("[timeout]: # (5000)"),
+            Ok(Duration::from_millis(5000))
+        );
+        assert_eq!(
+            get_timeout("[timeout]: # (2s)"),
+            Ok(Duration::from_millis(2000))
+        );
+        assert_eq!(
+            get_timeout("[timeout]: # (0)"),
+            Err("invalid timeout: 0".to_string())
+        );
+        assert!(get_timeout("[timeout]: # (soon)").is_err());
+    }
+
+    #[test]
+    fn test_get_p95() {
+        assert_eq!(get_p95("[p95]: # (300)"), Ok(300));
+        assert_eq!(get_p95("[p95]: # (1s)"), Ok(1000));
+        assert!(get_p95("[p95]: # (fast)").is_err());
+    }
+
+    #[test]
+    fn test_get_max_time() {
+        assert_eq!(get_max_time("[max-time]: # (250)"), Ok(250));
+        assert_eq!(get_max_time("[max-time]: # (2s)"), Ok(2000));
+        assert!(get_max_time("[max-time]: # (-1)").is_err());
+    }
+
+    #[test]
+    fn test_get_stream() {
+        assert_eq!(
+            get_stream("[stream]: # (first-byte=100ms, min-chunks=3, within=2s)"),
+            Ok(Stream {
+                first_byte: Some(100),
+                min_chunks: Some(3),
+                within: Some(2000),
+            })
+        );
+        assert_eq!(
+            get_stream("[stream]: # (min-chunks=2)"),
+            Ok(Stream {
+                min_chunks: Some(2),
+                ..Stream::default()
+            })
+        );
+        assert!(get_stream("[stream]: # (chunks=2)").is_err());
+        assert!(get_stream("[stream]: # (first-byte=soon)").is_err());
+    }
+
+    #[test]
+    fn test_get_poll() {
+        assert_eq!(
+            get_poll("[poll]: # (3000, 200ms, $.status == ready)"),
+            Ok(Poll {
+                timeout: 3000,
+                interval: 200,
+                path: "$.status".to_string(),
+                value: json!("ready"),
+            })
+        );
+        assert_eq!(
+            get_poll("[poll]: # (3s, 1s, $.items[0].done == true)"),
+            Ok(Poll {
+                timeout: 3000,
+                interval: 1000,
+                path: "$.items[0].done".to_string(),
+                value: json!(true),
+            })
+        );
+        assert!(get_poll("[poll]: # (3000, $.status == ready)").is_err());
+        assert!(get_poll("[poll]: # (3000, 200ms, $.status)").is_err());
     }
 }